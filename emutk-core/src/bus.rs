@@ -1,6 +1,7 @@
 use byterepr::ByteRepr;
 use std::mem::size_of;
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use crate::cycles::Cycles;
 
@@ -20,6 +21,167 @@ pub trait Bus<Err> {
     /// ## Panics
     /// Write sizes larger than MAX_OPERATION_SIZE will panic.
     fn write_val<T: ByteRepr>(&mut self, addr: usize, data: T) -> (Cycles, Result<(), Err>);
+
+    /// Borrow `len` bytes at the specified address directly, if they're plain memory.
+    /// Buses return `None` for anything with side effects (like MMIO), or that
+    /// isn't one contiguous piece of memory, and the block operations below
+    /// fall back to going an element at a time.
+    fn slice(&self, _addr: usize, _len: usize) -> Option<&[u8]> {
+        None
+    }
+    /// Mutable version of `slice`.
+    fn slice_mut(&mut self, _addr: usize, _len: usize) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Read `buf.len()` bytes from the bus at the specified address.
+    fn read_block(&mut self, addr: usize, buf: &mut [u8]) -> (Cycles, Result<(), Err>) {
+        if let Some(s) = self.slice(addr, buf.len()) {
+            buf.copy_from_slice(s);
+            return (block_cycles(buf.len()), Ok(()));
+        }
+        let mut cyc = Cycles(0);
+        for (i, b) in buf.iter_mut().enumerate() {
+            let (c, res) = self.read_val(addr + i);
+            cyc += c;
+            match res {
+                Ok(v) => *b = v,
+                Err(e) => return (cyc, Err(e)),
+            }
+        }
+        (cyc, Ok(()))
+    }
+    /// Write all of `buf` to the bus at the specified address.
+    fn write_block(&mut self, addr: usize, buf: &[u8]) -> (Cycles, Result<(), Err>) {
+        if let Some(s) = self.slice_mut(addr, buf.len()) {
+            s.copy_from_slice(buf);
+            return (block_cycles(buf.len()), Ok(()));
+        }
+        let mut cyc = Cycles(0);
+        for (i, b) in buf.iter().enumerate() {
+            let (c, res) = self.write_val(addr + i, *b);
+            cyc += c;
+            if let Err(e) = res {
+                return (cyc, Err(e));
+            }
+        }
+        (cyc, Ok(()))
+    }
+    /// Write `len` copies of `val` to the bus at the specified address.
+    fn fill_block(&mut self, addr: usize, val: u8, len: usize) -> (Cycles, Result<(), Err>) {
+        if let Some(s) = self.slice_mut(addr, len) {
+            for b in s.iter_mut() {
+                *b = val;
+            }
+            return (block_cycles(len), Ok(()));
+        }
+        self.write_block(addr, &vec![val; len])
+    }
+    /// Copy `len` bytes from `src` to `dst`. Overlapping ranges are fine, the
+    /// source is read as it was before the copy started.
+    fn copy_block(&mut self, src: usize, dst: usize, len: usize) -> (Cycles, Result<(), Err>)
+        where Self: Sized
+    {
+        copy_block_buffered(self, src, dst, len)
+    }
+
+    /// Read a value, pass it through `op` and write back the result, returning
+    /// the original value. Buses shared between several processors must make
+    /// sure nothing else gets at the bus in between, the default is just a
+    /// read followed by a write.
+    fn interlocked_update<T: ByteRepr, F: FnOnce(T) -> T>(&mut self, addr: usize, op: F)
+        -> (Cycles, Result<T, Err>)
+        where T: Clone, Self: Sized
+    {
+        let (mut cyc, res) = self.read_val::<T>(addr);
+        let old = match res {
+            Ok(v) => v,
+            Err(e) => return (cyc, Err(e)),
+        };
+        let (c, res) = self.write_val(addr, op(old.clone()));
+        cyc += c;
+        (cyc, res.map(|_| old))
+    }
+}
+
+/// Default `copy_block`, which reads the whole source into a buffer before
+/// writing it back out. For buses to fall back on when they can't do better.
+pub fn copy_block_buffered<B: Bus<Err>, Err>(bus: &mut B, src: usize, dst: usize, len: usize)
+    -> (Cycles, Result<(), Err>)
+{
+    let mut buf = vec![0; len];
+    let (mut cyc, res) = bus.read_block(src, &mut buf);
+    if let Err(e) = res {
+        return (cyc, Err(e));
+    }
+    let (c, res) = bus.write_block(dst, &buf);
+    cyc += c;
+    (cyc, res)
+}
+
+/// Cycles taken by a block transfer of `len` bytes done through `slice`,
+/// assuming memory moves a longword at a time.
+pub fn block_cycles(len: usize) -> Cycles {
+    Cycles(len.div_ceil(4))
+}
+
+/// A handle to a bus shared between several processors, possibly on different
+/// threads. Every access locks the bus for its duration, and interlocked
+/// updates hold the lock across both the read and the write.
+pub struct SharedBus<B> {
+    inner: Arc<Mutex<B>>,
+}
+
+impl<B> SharedBus<B> {
+    pub fn new(bus: B) -> Self {
+        SharedBus {
+            inner: Arc::new(Mutex::new(bus)),
+        }
+    }
+
+    /// Lock the bus for direct access, e.g. to poke at devices.
+    pub fn lock(&self) -> MutexGuard<'_, B> {
+        // A processor panicking mid-access doesn't leave the bus itself broken.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<B> Clone for SharedBus<B> {
+    fn clone(&self) -> Self {
+        SharedBus {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<B: Bus<Err>, Err> Bus<Err> for SharedBus<B> {
+    const MAX_OPERATION_SIZE: usize = B::MAX_OPERATION_SIZE;
+    const MAX_ADDRESS: usize = B::MAX_ADDRESS;
+
+    fn read_val<T: ByteRepr>(&mut self, addr: usize) -> (Cycles, Result<T, Err>) {
+        self.lock().read_val(addr)
+    }
+    fn write_val<T: ByteRepr>(&mut self, addr: usize, data: T) -> (Cycles, Result<(), Err>) {
+        self.lock().write_val(addr, data)
+    }
+    fn read_block(&mut self, addr: usize, buf: &mut [u8]) -> (Cycles, Result<(), Err>) {
+        self.lock().read_block(addr, buf)
+    }
+    fn write_block(&mut self, addr: usize, buf: &[u8]) -> (Cycles, Result<(), Err>) {
+        self.lock().write_block(addr, buf)
+    }
+    fn fill_block(&mut self, addr: usize, val: u8, len: usize) -> (Cycles, Result<(), Err>) {
+        self.lock().fill_block(addr, val, len)
+    }
+    fn copy_block(&mut self, src: usize, dst: usize, len: usize) -> (Cycles, Result<(), Err>) {
+        self.lock().copy_block(src, dst, len)
+    }
+    fn interlocked_update<T: ByteRepr, F: FnOnce(T) -> T>(&mut self, addr: usize, op: F)
+        -> (Cycles, Result<T, Err>)
+        where T: Clone
+    {
+        self.lock().interlocked_update(addr, op)
+    }
 }
 
 /// An alternative to Bus that adds places to transport arbitrary data.
@@ -41,4 +203,62 @@ pub trait TaggedBus<Err, InTag, OutTag> {
     /// Write sizes larger than MAX_OPERATION_SIZE will panic.
     fn write_val_tagged<T: ByteRepr + Clone>(&mut self, addr: usize, data: T, tag: InTag)
         -> (Cycles, Result<OutTag, Err>);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bus with no `slice`, like one full of MMIO, which fails past the end
+    /// of its memory.
+    struct ByteBus {
+        mem: Vec<u8>,
+    }
+
+    impl Bus<()> for ByteBus {
+        const MAX_OPERATION_SIZE: usize = 16;
+        const MAX_ADDRESS: usize = 0xFF;
+
+        fn read_val<T: ByteRepr>(&mut self, addr: usize) -> (Cycles, Result<T, ()>) {
+            match self.mem.get(addr..addr + T::BYTE_LEN) {
+                Some(b) => (Cycles(1), Ok(T::from_le_bytes(b))),
+                None => (Cycles(1), Err(())),
+            }
+        }
+
+        fn write_val<T: ByteRepr>(&mut self, addr: usize, data: T) -> (Cycles, Result<(), ()>) {
+            match self.mem.get_mut(addr..addr + T::BYTE_LEN) {
+                Some(b) => {
+                    data.copy_to_le_bytes(b);
+                    (Cycles(1), Ok(()))
+                }
+                None => (Cycles(1), Err(())),
+            }
+        }
+    }
+
+    #[test]
+    fn block_fallbacks() {
+        let mut bus = ByteBus { mem: (0..16).collect() };
+        let mut buf = [0; 4];
+        assert_eq!(bus.read_block(2, &mut buf), (Cycles(4), Ok(())));
+        assert_eq!(buf, [2, 3, 4, 5]);
+        assert_eq!(bus.write_block(0, &[9, 8]), (Cycles(2), Ok(())));
+        assert_eq!(bus.fill_block(14, 0xAA, 2), (Cycles(2), Ok(())));
+        assert_eq!(bus.mem[..3], [9, 8, 2]);
+        assert_eq!(bus.mem[13..], [13, 0xAA, 0xAA]);
+
+        // Overlapping copies either way see the source as it was.
+        assert_eq!(bus.copy_block(2, 4, 6), (Cycles(12), Ok(())));
+        assert_eq!(bus.mem[2..10], [2, 3, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(bus.copy_block(4, 2, 6), (Cycles(12), Ok(())));
+        assert_eq!(bus.mem[2..10], [2, 3, 4, 5, 6, 7, 6, 7]);
+
+        // Errors stop the transfer where they happen.
+        assert_eq!(bus.write_block(15, &[1, 2]), (Cycles(2), Err(())));
+        assert_eq!(bus.mem[15], 1);
+        assert_eq!(bus.read_block(15, &mut buf), (Cycles(2), Err(())));
+        assert_eq!(bus.copy_block(14, 0, 4).1, Err(()));
+        assert_eq!(bus.mem[..2], [9, 8]);
+    }
+}
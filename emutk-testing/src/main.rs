@@ -21,7 +21,8 @@ fn main() {
                 icount+=1;
             },
             Err(e) => {
-                panic!("{:?}", e);
+                println!("{} at PC {:08x}", e, cpu.regfile.get_conpc());
+                break;
             }
         }
     }
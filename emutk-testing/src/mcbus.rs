@@ -2,14 +2,20 @@ use emutk_core::{
     cycles::Cycles,
     bus::TaggedBus,
     bus::Bus,
+    bus::block_cycles,
+    bus::copy_block_buffered,
     ByteRepr,
 };
+use emutk_vax::interrupt::{
+    InterruptController,
+    InterruptLine,
+};
 
 
 pub struct VirtVAXBus {
     boot_rom: &'static [u8],
     ram: Vec<u8>,
-    devices: Vec<Box<dyn Device>>,
+    devices: Vec<(Box<dyn Device>, InterruptLine)>,
 }
 
 pub enum AddressSpace {
@@ -65,6 +71,8 @@ pub trait Device {
 
     fn device_origin(&mut self) -> DeviceOrigin;
 
+    /// Check if the device wants an interrupt. Once the CPU has taken it, the
+    /// device will be asked again, so this should go false when serviced.
     fn interrupt_pending(&mut self) -> bool;
 
     fn tick(&mut self);
@@ -81,6 +89,27 @@ impl VirtVAXBus {
             devices: vec![],
         }
     }
+
+    /// Attach a device, which interrupts at `ipl` through SCB offset `vector`.
+    pub fn attach_device(&mut self, dev: Box<dyn Device>, ic: &InterruptController, ipl: u8, vector: u32) {
+        self.devices.push((dev, ic.line(ipl, vector)));
+    }
+
+    /// Tick every device, forwarding their interrupt requests to the CPU and
+    /// withdrawing any they no longer make.
+    pub fn poll_devices(&mut self) {
+        for (dev, line) in self.devices.iter_mut() {
+            dev.tick();
+            if dev.interrupt_pending() {
+                if !line.is_asserted() {
+                    line.assert();
+                }
+            } else if line.is_asserted() {
+                // The device stopped asking before the CPU got to it.
+                line.deassert();
+            }
+        }
+    }
 }
 
 impl Bus<()> for VirtVAXBus {
@@ -136,4 +165,26 @@ impl Bus<()> for VirtVAXBus {
         };
         (cyc, Ok(()))
     }
+    fn slice(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        let end = addr.checked_add(len)?;
+        match (addr >> 28, (end.max(1) - 1) >> 28) {
+            (0x0, 0x0) => self.ram.get(addr..end),
+            (0x1, 0x1) => self.boot_rom.get(addr - 0x1000_0000..end - 0x1000_0000),
+            _ => None,
+        }
+    }
+    fn slice_mut(&mut self, addr: usize, len: usize) -> Option<&mut [u8]> {
+        let end = addr.checked_add(len)?;
+        match (addr >> 28, (end.max(1) - 1) >> 28) {
+            (0x0, 0x0) => self.ram.get_mut(addr..end),
+            _ => None,
+        }
+    }
+    fn copy_block(&mut self, src: usize, dst: usize, len: usize) -> (Cycles, Result<(), ()>) {
+        if self.slice_mut(src, len).is_some() && self.slice_mut(dst, len).is_some() {
+            self.ram.copy_within(src..src+len, dst);
+            return (block_cycles(len), Ok(()));
+        }
+        copy_block_buffered(self, src, dst, len)
+    }
 }
\ No newline at end of file
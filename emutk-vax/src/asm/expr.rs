@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::disasm::Syntax;
+
+/// A symbol's value, and whether it's the address of a label rather than a
+/// constant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Value {
+    pub val: i64,
+    /// Label addresses can move from one pass to the next, so anything sized
+    /// by them has to be sized without looking at the value.
+    pub label: bool,
+}
+
+impl Value {
+    pub fn constant(val: i64) -> Self {
+        Value { val, label: false }
+    }
+}
+
+/// What expressions are evaluated against.
+pub struct Env<'a> {
+    pub symbols: &'a HashMap<String, Value>,
+    /// Address of the start of the current statement, which is what `.` means.
+    pub dot: u32,
+    /// Whether undefined symbols are errors. Before the layout settles they
+    /// may just not have been seen yet.
+    pub strict: bool,
+    pub syntax: Syntax,
+}
+
+/// Evaluate an expression. Integers are decimal, `0x` hex or MACRO-32 style
+/// `^X`, `^O`, `^B` and `^D` radix prefixed, or octal with a leading 0 in GNU
+/// syntax. The usual arithmetic and bitwise operators work, and `<>` groups in
+/// MACRO-32 as it has no shift operators.
+pub fn eval(text: &str, env: &Env) -> Result<Value, String> {
+    let mut p = Parser { s: text.as_bytes(), pos: 0, env };
+    let v = p.or()?;
+    p.skip_ws();
+    if p.pos != p.s.len() {
+        return Err(format!("unexpected `{}` in expression `{}`", &text[p.pos..], text.trim()));
+    }
+    Ok(v)
+}
+
+pub fn is_ident_start(c: char, syntax: Syntax) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.' || (c == '$' && syntax == Syntax::Macro32)
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+struct Parser<'a, 'e> {
+    s: &'a [u8],
+    pos: usize,
+    env: &'e Env<'e>,
+}
+
+impl Parser<'_, '_> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.s.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, tok: &str) -> bool {
+        self.skip_ws();
+        if self.s[self.pos..].starts_with(tok.as_bytes()) {
+            self.pos += tok.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Value, String> {
+        let mut v = self.and()?;
+        while self.eat("|") {
+            v = combine(v, self.and()?, |a, b| a | b);
+        }
+        Ok(v)
+    }
+
+    fn and(&mut self) -> Result<Value, String> {
+        let mut v = self.shift()?;
+        while self.eat("&") {
+            v = combine(v, self.shift()?, |a, b| a & b);
+        }
+        Ok(v)
+    }
+
+    fn shift(&mut self) -> Result<Value, String> {
+        let mut v = self.sum()?;
+        if self.env.syntax == Syntax::Macro32 {
+            return Ok(v);
+        }
+        loop {
+            if self.eat("<<") {
+                v = combine(v, self.sum()?, |a, b| a.wrapping_shl(b as u32));
+            } else if self.eat(">>") {
+                v = combine(v, self.sum()?, |a, b| a.wrapping_shr(b as u32));
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn sum(&mut self) -> Result<Value, String> {
+        let mut v = self.product()?;
+        loop {
+            if self.eat("+") {
+                v = combine(v, self.product()?, i64::wrapping_add);
+            } else if self.eat("-") {
+                let rhs = self.product()?;
+                // The distance between two labels doesn't depend on where
+                // they end up.
+                let label = v.label != rhs.label;
+                v = Value { val: v.val.wrapping_sub(rhs.val), label };
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Value, String> {
+        let mut v = self.unary()?;
+        loop {
+            if self.eat("*") {
+                v = combine(v, self.unary()?, i64::wrapping_mul);
+            } else if self.eat("/") || self.eat("%") {
+                let op = self.s[self.pos - 1];
+                let rhs = self.unary()?;
+                if rhs.val == 0 {
+                    if !self.env.strict {
+                        return Ok(Value { val: 0, label: true });
+                    }
+                    return Err("division by zero".to_string());
+                }
+                v = if op == b'/' {
+                    combine(v, rhs, i64::wrapping_div)
+                } else {
+                    combine(v, rhs, i64::wrapping_rem)
+                };
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat("-") {
+            let v = self.unary()?;
+            Ok(Value { val: v.val.wrapping_neg(), ..v })
+        } else if self.eat("~") {
+            let v = self.unary()?;
+            Ok(Value { val: !v.val, ..v })
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        let close = match self.peek() {
+            Some(b'(') => Some(")"),
+            Some(b'<') if self.env.syntax == Syntax::Macro32 => Some(">"),
+            _ => None,
+        };
+        if let Some(close) = close {
+            self.pos += 1;
+            let v = self.or()?;
+            if !self.eat(close) {
+                return Err(format!("missing `{}`", close));
+            }
+            return Ok(v);
+        }
+
+        let rest = std::str::from_utf8(&self.s[self.pos..]).unwrap();
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return Err("missing value".to_string()),
+        };
+        if c == '^' {
+            let radix = match rest.get(1..2).map(|r| r.to_ascii_uppercase()) {
+                Some(ref r) if r == "X" => 16,
+                Some(ref r) if r == "O" => 8,
+                Some(ref r) if r == "B" => 2,
+                Some(ref r) if r == "D" => 10,
+                _ => return Err(format!("unknown radix in `{}`", rest)),
+            };
+            self.pos += 2;
+            return self.number(radix);
+        }
+        if c.is_ascii_digit() {
+            let lower = rest.to_ascii_lowercase();
+            if lower.starts_with("0x") {
+                self.pos += 2;
+                return self.number(16);
+            }
+            if lower.starts_with("0b") && self.env.syntax == Syntax::Gnu {
+                self.pos += 2;
+                return self.number(2);
+            }
+            if c == '0' && self.env.syntax == Syntax::Gnu {
+                return self.number(8);
+            }
+            return self.number(10);
+        }
+        if is_ident_start(c, self.env.syntax) {
+            let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+            let name = &rest[..len];
+            self.pos += len;
+            if name == "." {
+                return Ok(Value { val: self.env.dot as i64, label: true });
+            }
+            return match self.env.symbols.get(name) {
+                Some(v) => Ok(*v),
+                None if !self.env.strict => Ok(Value { val: 0, label: true }),
+                None => Err(format!("undefined symbol `{}`", name)),
+            };
+        }
+        Err(format!("unexpected `{}`", rest))
+    }
+
+    fn number(&mut self, radix: u32) -> Result<Value, String> {
+        let start = self.pos;
+        while self.pos < self.s.len() && (self.s[self.pos] as char).is_digit(radix) {
+            self.pos += 1;
+        }
+        let digits = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+        // Allow the full range of unsigned quadwords as well as signed ones.
+        u64::from_str_radix(digits, radix)
+            .map(|v| Value::constant(v as i64))
+            .map_err(|_| format!("bad number `{}`", digits))
+    }
+}
+
+fn combine(a: Value, b: Value, op: impl Fn(i64, i64) -> i64) -> Value {
+    Value { val: op(a.val, b.val), label: a.label || b.label }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions() {
+        let mut symbols = HashMap::new();
+        symbols.insert("start".to_string(), Value { val: 0x100, label: true });
+        symbols.insert("end".to_string(), Value { val: 0x140, label: true });
+        symbols.insert("SIZE".to_string(), Value::constant(4));
+        let env = Env { symbols: &symbols, dot: 0x120, strict: true, syntax: Syntax::Gnu };
+        assert_eq!(eval("(SIZE+4)*2 - 1", &env), Ok(Value::constant(15)));
+        assert_eq!(eval("0x10 | 010 | 1 << 2", &env), Ok(Value::constant(0x1C)));
+        assert_eq!(eval("end - start", &env), Ok(Value::constant(0x40)));
+        assert_eq!(eval("start + SIZE", &env), Ok(Value { val: 0x104, label: true }));
+        assert_eq!(eval(". - 2", &env), Ok(Value { val: 0x11E, label: true }));
+        assert!(eval("missing", &env).is_err());
+        assert!(eval("4 4", &env).is_err());
+
+        let env = Env { syntax: Syntax::Macro32, ..env };
+        assert_eq!(eval("<^X10 + ^B11> * 010", &env), Ok(Value::constant(190)));
+        assert_eq!(eval("-^O17", &env), Ok(Value::constant(-15)));
+    }
+}
@@ -0,0 +1,757 @@
+//! A small VAX assembler, so tests can be written as assembly and the bootrom
+//! can be built without a cross toolchain.
+//!
+//! It takes either MACRO-32 or GNU as syntax, the same two the disassembler
+//! produces, and encodes operands with the same signature table and addressing
+//! mode rules the CPU decodes them with. Sources are assembled in order into
+//! sections, which are laid out one after another from the origin to make a
+//! flat image. There's no linking: every symbol is visible everywhere.
+
+mod expr;
+mod operand;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::instrs::InstructionType;
+use crate::cpu::instrs::operands::AccessType;
+pub use crate::disasm::Syntax;
+use expr::{eval, is_ident_char, is_ident_start, Env, Value};
+
+/// Passes to make before giving up on the layout settling.
+const MAX_PASSES: usize = 16;
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// Largest block `.blkb` and friends will reserve.
+const MAX_BLOCK_LEN: usize = 1 << 24;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled program.
+#[derive(Clone, Debug)]
+pub struct Image {
+    /// Address of the first byte.
+    pub origin: u32,
+    pub bytes: Vec<u8>,
+    /// Every label and assigned symbol.
+    pub symbols: HashMap<String, u32>,
+}
+
+/// Assemble a single source, laid out from `origin`.
+pub fn assemble(src: &str, origin: u32, syntax: Syntax) -> Result<Vec<u8>, AsmError> {
+    Ok(Assembler::new(syntax).origin(origin).source("<input>", src).assemble()?.bytes)
+}
+
+struct Source {
+    name: String,
+    text: String,
+    /// Where includes are looked for.
+    dir: Option<PathBuf>,
+}
+
+pub struct Assembler {
+    syntax: Syntax,
+    origin: u32,
+    section_order: Vec<String>,
+    sources: Vec<Source>,
+}
+
+impl Assembler {
+    pub fn new(syntax: Syntax) -> Self {
+        Assembler {
+            syntax,
+            origin: 0,
+            section_order: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Set the address the image starts at.
+    pub fn origin(&mut self, origin: u32) -> &mut Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Lay these sections out first, in this order. Any others follow in the
+    /// order they first appear.
+    pub fn section_order(&mut self, names: &[&str]) -> &mut Self {
+        self.section_order = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Add a source. Includes are looked for in the working directory.
+    pub fn source(&mut self, name: &str, text: &str) -> &mut Self {
+        self.sources.push(Source { name: name.to_string(), text: text.to_string(), dir: None });
+        self
+    }
+
+    /// Add a source file. Includes are looked for next to it.
+    pub fn file(&mut self, path: impl AsRef<Path>) -> std::io::Result<&mut Self> {
+        let path = path.as_ref();
+        self.sources.push(Source {
+            name: path.display().to_string(),
+            text: std::fs::read_to_string(path)?,
+            dir: path.parent().map(Path::to_path_buf),
+        });
+        Ok(self)
+    }
+
+    pub fn assemble(&self) -> Result<Image, AsmError> {
+        // Label addresses depend on the size of everything before them, which
+        // depends on how operands referring to them were encoded, so make
+        // passes until nothing moves. Only then are undefined symbols and out
+        // of range values errors.
+        let mut symbols = HashMap::new();
+        let mut layout = Vec::new();
+        let mut includes = HashMap::new();
+        let mut strict = false;
+        for _ in 0..MAX_PASSES {
+            let mut pass = Pass {
+                syntax: self.syntax,
+                strict,
+                bases: self.bases(&layout),
+                symbols: symbols.clone(),
+                defined: HashSet::new(),
+                sections: Vec::new(),
+                cur: 0,
+                conds: Vec::new(),
+                include: None,
+                includes: &mut includes,
+            };
+            for src in &self.sources {
+                pass.source(&src.name, &src.text, src.dir.as_deref(), 0)?;
+            }
+            let new_layout: Vec<(String, usize)> = pass.sections.iter()
+                .map(|(name, bytes)| (name.clone(), bytes.len()))
+                .collect();
+            let settled = new_layout == layout && pass.symbols == symbols;
+            if strict && settled {
+                return Ok(self.image(pass));
+            }
+            strict = settled;
+            symbols = pass.symbols;
+            layout = new_layout;
+        }
+        Err(AsmError { file: String::new(), line: 0, message: "layout never settled".to_string() })
+    }
+
+    /// Sections in the order they're laid out.
+    fn ordered<'s>(&self, names: impl Iterator<Item = &'s String> + Clone) -> Vec<&'s String> {
+        let mut out: Vec<&String> = self.section_order.iter()
+            .filter_map(|want| names.clone().find(|n| *n == want))
+            .collect();
+        out.extend(names.filter(|n| !self.section_order.contains(n)));
+        out
+    }
+
+    /// Start address of each section, given their sizes. Each starts on a
+    /// longword boundary.
+    fn bases(&self, layout: &[(String, usize)]) -> HashMap<String, u32> {
+        let mut bases = HashMap::new();
+        let mut addr = self.origin;
+        for name in self.ordered(layout.iter().map(|(n, _)| n)) {
+            bases.insert(name.clone(), addr);
+            let len = layout.iter().find(|(n, _)| n == name).unwrap().1;
+            addr = addr.wrapping_add(len as u32 + 3) & !3;
+        }
+        bases
+    }
+
+    fn image(&self, pass: Pass<'_>) -> Image {
+        let mut bytes = Vec::new();
+        for name in self.ordered(pass.sections.iter().map(|(n, _)| n)) {
+            let start = (pass.bases[name] - self.origin) as usize;
+            bytes.resize(start, 0);
+            bytes.extend(&pass.sections.iter().find(|(n, _)| n == name).unwrap().1);
+        }
+        Image {
+            origin: self.origin,
+            bytes,
+            symbols: pass.symbols.into_iter().map(|(k, v)| (k, v.val as u32)).collect(),
+        }
+    }
+}
+
+struct Pass<'a> {
+    syntax: Syntax,
+    strict: bool,
+    bases: HashMap<String, u32>,
+    /// Symbols as of the last pass, updated as they're defined in this one.
+    symbols: HashMap<String, Value>,
+    /// Symbols defined so far in this pass.
+    defined: HashSet<String>,
+    sections: Vec<(String, Vec<u8>)>,
+    cur: usize,
+    /// Whether each enclosing conditional is assembling its body.
+    conds: Vec<bool>,
+    /// A file the last statement asked to include.
+    include: Option<String>,
+    includes: &'a mut HashMap<PathBuf, String>,
+}
+
+impl Pass<'_> {
+    fn source(&mut self, name: &str, text: &str, dir: Option<&Path>, depth: usize) -> Result<(), AsmError> {
+        if self.sections.is_empty() {
+            self.switch_section(".text");
+        }
+        for (n, line) in text.lines().enumerate() {
+            let err = |message| AsmError { file: name.to_string(), line: n + 1, message };
+            self.statement(line).map_err(err)?;
+            if let Some(file) = self.include.take() {
+                if depth == MAX_INCLUDE_DEPTH {
+                    return Err(err("includes are nested too deeply".to_string()));
+                }
+                let path = dir.map_or_else(|| PathBuf::from(&file), |d| d.join(&file));
+                if !self.includes.contains_key(&path) {
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|e| err(format!("can't include `{}`: {}", file, e)))?;
+                    self.includes.insert(path.clone(), text);
+                }
+                let text = self.includes[&path].clone();
+                self.source(&path.display().to_string(), &text, path.parent(), depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn switch_section(&mut self, name: &str) {
+        self.cur = match self.sections.iter().position(|(n, _)| n == name) {
+            Some(i) => i,
+            None => {
+                self.sections.push((name.to_string(), Vec::new()));
+                self.sections.len() - 1
+            }
+        };
+    }
+
+    fn dot(&self) -> u32 {
+        let (name, bytes) = &self.sections[self.cur];
+        // Sections new this pass go after the others, for now.
+        let base = self.bases.get(name).copied().unwrap_or(0);
+        base.wrapping_add(bytes.len() as u32)
+    }
+
+    fn env(&self) -> Env<'_> {
+        Env { symbols: &self.symbols, dot: self.dot(), strict: self.strict, syntax: self.syntax }
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.sections[self.cur].1.extend_from_slice(bytes);
+    }
+
+    fn define(&mut self, name: &str, value: Value, label: bool) -> Result<(), String> {
+        if label && self.defined.contains(name) {
+            return Err(format!("`{}` is already defined", name));
+        }
+        self.defined.insert(name.to_string());
+        self.symbols.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+        let mut s = strip_comment(line, self.syntax).trim();
+
+        let word_end = |s: &str| s.find(|c: char| c.is_whitespace()).unwrap_or(s.len());
+        let first = s[..word_end(s)].to_ascii_lowercase();
+        if !self.conds.iter().all(|&c| c) {
+            // Skipping, so only keep track of nesting.
+            match first.as_str() {
+                ".if" | ".ifdef" | ".ifndef" => self.conds.push(false),
+                ".else" => {
+                    let i = self.conds.len() - 1;
+                    if self.conds[..i].iter().all(|&c| c) {
+                        self.conds[i] = !self.conds[i];
+                    }
+                }
+                ".endif" => { self.conds.pop(); }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        // Labels, with `::` being MACRO-32's global label.
+        while let Some(len) = ident_len(s, self.syntax) {
+            let rest = &s[len..];
+            let rest = match rest.strip_prefix("::").or_else(|| rest.strip_prefix(':')) {
+                Some(rest) => rest,
+                None => break,
+            };
+            let addr = Value { val: self.dot() as i64, label: true };
+            self.define(&s[..len], addr, true)?;
+            s = rest.trim_start();
+        }
+        if s.is_empty() {
+            return Ok(());
+        }
+
+        // `name = value`
+        if let Some(len) = ident_len(s, self.syntax) {
+            let rest = s[len..].trim_start();
+            if rest.starts_with('=') && !rest.starts_with("==") {
+                let v = eval(&rest[1..], &self.env())?;
+                return self.define(&s[..len], v, false);
+            }
+        }
+
+        let len = word_end(s);
+        let (word, rest) = (&s[..len], s[len..].trim());
+        if word.starts_with('.') {
+            self.directive(&word.to_ascii_lowercase(), rest)
+        } else {
+            self.instruction(word, rest)
+        }
+    }
+
+    fn instruction(&mut self, mnemonic: &str, rest: &str) -> Result<(), String> {
+        let instr = InstructionType::from_str(&mnemonic.to_ascii_uppercase())
+            .ok_or_else(|| format!("unknown instruction `{}`", mnemonic))?;
+        let specs = instr.operands()
+            .ok_or_else(|| format!("`{}` can't be assembled", mnemonic))?;
+        let args = split_args(rest);
+        if args.len() != specs.len() {
+            return Err(format!("`{}` takes {} operands", mnemonic, specs.len()));
+        }
+
+        let start = self.dot();
+        let opcode = (instr as u16).to_le_bytes();
+        let mut bytes = opcode[..instr.opcode_len()].to_vec();
+        let env = self.env();
+        for (arg, spec) in args.iter().zip(specs) {
+            let addr = start.wrapping_add(bytes.len() as u32);
+            let operand = match instr {
+                // These carry a message code rather than a displacement.
+                InstructionType::BUGW | InstructionType::BUGL => {
+                    data(arg, spec.data.byte_len(), &env)?
+                }
+                _ if spec.access == AccessType::Branch => {
+                    let end = addr.wrapping_add(spec.data.byte_len() as u32);
+                    operand::branch(arg, *spec, end, &env)?
+                }
+                _ => operand::specifier(arg, *spec, addr, &env)?,
+            };
+            bytes.extend(operand);
+        }
+        self.emit(&bytes);
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, rest: &str) -> Result<(), String> {
+        let args = split_args(rest);
+        let arg = |i: usize| args.get(i).copied().ok_or_else(|| format!("`{}` needs more arguments", name));
+        match name {
+            ".byte" | ".word" | ".long" | ".int" | ".quad" => {
+                let len = match name {
+                    ".byte" => 1,
+                    ".word" => 2,
+                    ".quad" => 8,
+                    _ => 4,
+                };
+                let env = self.env();
+                let mut bytes = Vec::new();
+                for a in &args {
+                    bytes.extend(data(a, len, &env)?);
+                }
+                self.emit(&bytes);
+            }
+            ".ascii" | ".asciz" | ".string" => {
+                let mut bytes = strings(rest)?;
+                if name != ".ascii" {
+                    bytes.push(0);
+                }
+                self.emit(&bytes);
+            }
+            ".blkb" | ".blkw" | ".blkl" | ".blkq" | ".space" | ".skip" => {
+                let unit = match name {
+                    ".blkw" => 2,
+                    ".blkl" => 4,
+                    ".blkq" => 8,
+                    _ => 1,
+                };
+                let text = arg(0)?;
+                let count = self.constant(text)?;
+                if count < 0 {
+                    return Err(format!("`{}` can't reserve {} bytes", name, count));
+                }
+                let len = usize::try_from(count).ok()
+                    .and_then(|c| c.checked_mul(unit))
+                    .filter(|&len| len <= MAX_BLOCK_LEN)
+                    .ok_or_else(|| format!("`{} {}` is too big", name, text.trim()))?;
+                let fill = match args.get(1) {
+                    Some(f) => self.constant(f)? as u8,
+                    None => 0,
+                };
+                self.emit(&vec![fill; len]);
+            }
+            ".align" => {
+                let size = match arg(0)?.to_ascii_uppercase().as_str() {
+                    "BYTE" => 1,
+                    "WORD" => 2,
+                    "LONG" => 4,
+                    "QUAD" => 8,
+                    "OCTA" => 16,
+                    "PAGE" => 512,
+                    a => match self.constant(a)? {
+                        n if n < 0 => return Err(format!("`.align {}` is out of range", a.trim())),
+                        n => 1 << n.min(16),
+                    },
+                };
+                let fill = match args.get(1) {
+                    Some(f) => self.constant(f)? as u8,
+                    None => 0,
+                };
+                let pad = (size - self.dot() % size) % size;
+                self.emit(&vec![fill; pad as usize]);
+            }
+            ".set" | ".equ" => {
+                let v = eval(arg(1)?, &self.env())?;
+                let sym = arg(0)?;
+                self.define(sym, v, false)?;
+            }
+            ".entry" => {
+                // MACRO-32's procedure entry point: a label and an entry mask.
+                let mask = data(arg(1)?, 2, &self.env())?;
+                let addr = Value { val: self.dot() as i64, label: true };
+                self.define(arg(0)?, addr, true)?;
+                self.emit(&mask);
+            }
+            ".include" => self.include = Some(String::from_utf8_lossy(&strings(rest)?).into_owned()),
+            ".section" | ".psect" => self.switch_section(arg(0)?),
+            ".text" | ".data" | ".bss" => self.switch_section(name),
+            ".if" => {
+                let v = self.constant(arg(0)?)?;
+                self.conds.push(v != 0);
+            }
+            ".ifdef" | ".ifndef" => {
+                let defined = self.defined.contains(arg(0)?);
+                self.conds.push(defined == (name == ".ifdef"));
+            }
+            ".else" => match self.conds.last_mut() {
+                Some(c) => *c = !*c,
+                None => return Err("`.else` without `.if`".to_string()),
+            },
+            ".endif" => {
+                if self.conds.pop().is_none() {
+                    return Err("`.endif` without `.if`".to_string());
+                }
+            }
+            // Nothing to do for these without a linker.
+            ".global" | ".globl" | ".extern" | ".extrn" | ".weak" | ".type" | ".size"
+                | ".title" | ".sbttl" | ".ident" | ".file" | ".end" => {}
+            _ => return Err(format!("unknown directive `{}`", name)),
+        }
+        Ok(())
+    }
+
+    /// Evaluate something that the layout depends on, which can't depend on
+    /// where labels are.
+    fn constant(&self, text: &str) -> Result<i64, String> {
+        let v = eval(text, &self.env())?;
+        if v.label {
+            if self.strict {
+                return Err(format!("`{}` must be a constant", text.trim()));
+            }
+            return Ok(0);
+        }
+        Ok(v.val)
+    }
+}
+
+/// Encode an expression as `len` bytes of data.
+fn data(text: &str, len: usize, env: &Env) -> Result<Vec<u8>, String> {
+    operand::data_bytes(text, eval(text, env)?, len, env)
+}
+
+fn ident_len(s: &str, syntax: Syntax) -> Option<usize> {
+    if !s.chars().next().is_some_and(|c| is_ident_start(c, syntax)) {
+        return None;
+    }
+    Some(s.find(|c| !is_ident_char(c)).unwrap_or(s.len()))
+}
+
+fn strip_comment(line: &str, syntax: Syntax) -> &str {
+    let comment = match syntax {
+        Syntax::Macro32 => ';',
+        Syntax::Gnu => '#',
+    };
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == comment && !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Split arguments on commas, leaving those in brackets or strings alone.
+fn split_args(s: &str) -> Vec<&str> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                out.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(s[start..].trim());
+    out
+}
+
+/// Parse strings for `.ascii` and friends: either C style `"quoted\n"` ones,
+/// or MACRO-32 style ones between any delimiter, like `/text/`.
+fn strings(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut chars = s.trim().chars();
+    while let Some(delim) = chars.next() {
+        if delim.is_whitespace() || delim == ',' {
+            continue;
+        }
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+            if c == delim {
+                closed = true;
+                break;
+            }
+            let c = if c == '\\' && delim == '"' {
+                match chars.next() {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some(c) => c,
+                    None => break,
+                }
+            } else {
+                c
+            };
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        if !closed {
+            return Err(format!("unterminated string `{}`", s.trim()));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble_with;
+
+    fn asm(src: &str, syntax: Syntax) -> Vec<u8> {
+        assemble(src, 0x800, syntax).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn err(src: &str) -> String {
+        assemble(src, 0x800, Syntax::Macro32).unwrap_err().message
+    }
+
+    /// Disassemble `bytes` and check assembling the listing gives them back.
+    fn round_trip(bytes: &[u8], syntax: Syntax) {
+        let mut listing = String::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let (len, text) = disassemble_with(&bytes[pos..], 0x800 + pos as u32, syntax);
+            listing.push_str(&text);
+            listing.push('\n');
+            pos += len;
+        }
+        assert_eq!(asm(&listing, syntax), bytes, "{}", listing);
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        let prog: &[u8] = &[
+            0xD0, 0xA1, 0x04, 0x53,                         // MOVL 4(R1), R3
+            0xD0, 0xC1, 0x10, 0x00, 0x53,                   // MOVL W^16(R1), R3
+            0xD0, 0xB1, 0xFC, 0x53,                         // MOVL @-4(R1), R3
+            0xD0, 0x8F, 0x78, 0x56, 0x34, 0x12, 0x53,       // MOVL #^X12345678, R3
+            0xD0, 0x8F, 0x05, 0x00, 0x00, 0x00, 0x53,       // MOVL I^#^X5, R3
+            0xD0, 0x9F, 0x00, 0x11, 0x00, 0x00, 0x53,       // MOVL @#^X1100, R3
+            0xD0, 0xCF, 0xEA, 0x07, 0x53,                   // MOVL W^^X1010, R3
+            0xD0, 0xFF, 0x10, 0x00, 0x00, 0x00, 0x53,       // MOVL @L^^X..., R3
+            0xD0, 0x42, 0x91, 0x7E,                         // MOVL @(R1)+[R2], -(SP)
+            0x9E, 0x43, 0xE1, 0x10, 0x00, 0x00, 0x00, 0x5E, // MOVAB L^16(R1)[R3], SP
+            0x7D, 0x01, 0x50,                               // MOVQ S^#1, R0
+            0x12, 0xFE,                                     // BNEQ .
+            0x31, 0x00, 0x01,                               // BRW .+0x103
+            0xE0, 0x03, 0x50, 0x02,                         // BBS S^#3, R0, .+6
+            0xFD, 0x45, 0x0C, 0x50, 0x52,                   // MULG3 S^#1.5, R0, R2
+            0x50, 0x8F, 0x80, 0x40, 0x00, 0x00, 0x52,       // MOVF I^#^X4080, R2
+            0x2C, 0x50, 0x61, 0x20, 0x50, 0x62,             // MOVC5 R0, (R1), S^#32, R0, (R2)
+            0x8F, 0x50, 0x00, 0x01, 0x04, 0x00, 0x02, 0x00, // CASEB R0, S^#0, S^#1, ...
+            0xFD, 0xFF, 0x34, 0x12,                         // BUGW ^X1234
+            0x04,                                           // RET
+        ];
+        round_trip(prog, Syntax::Macro32);
+        round_trip(prog, Syntax::Gnu);
+    }
+
+    #[test]
+    fn labels_and_directives() {
+        let src = "
+            SIZE = 4
+            start:  MOVL #SIZE, R0          ; a literal
+                    MOVAL table, R1
+            loop:   ADDL2 (R1)+, R2
+                    SOBGTR R0, loop
+                    CASEB R2, #0, #1
+            cases:  .WORD one-cases, two-cases
+            one:    BRB end
+            two:    BRW end
+            table:: .LONG 1, 2, ^X10, -1
+            msg:    .ASCIZ /hi/
+                    .ALIGN LONG
+            end:    HALT
+            .IFNDEF SIZE
+                    .BYTE 1
+            .ENDIF
+        ";
+        let mut asm = Assembler::new(Syntax::Macro32);
+        let image = asm.origin(0x800).source("test", src).assemble().unwrap();
+        let sym = |s: &str| image.symbols[s];
+        assert_eq!(sym("start"), 0x800);
+        assert_eq!(sym("SIZE"), 4);
+        assert_eq!(image.bytes[..3], [0xD0, 0x04, 0x50]);
+        // Forward references to labels get word displacements.
+        let table = sym("table") as i32 - 0x807;
+        assert_eq!(image.bytes[3..8], [0xDE, 0xCF, table as u8, (table >> 8) as u8, 0x51]);
+        assert_eq!(sym("loop"), 0x808);
+        assert_eq!(image.bytes[0x0B..0x0E], [0xF5, 0x50, 0xFA]);
+        assert_eq!(image.bytes[0x12..0x16], [0x04, 0x00, 0x06, 0x00]);
+        let t = (sym("table") - 0x800) as usize;
+        assert_eq!(image.bytes[t..t + 8], [1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(image.bytes[t + 16..t + 19], *b"hi\0");
+        assert_eq!(sym("end") % 4, 0);
+        assert_eq!(image.bytes.len() as u32, sym("end") - 0x800 + 1);
+    }
+
+    #[test]
+    fn gnu_syntax_and_sections() {
+        let src = r#"
+        .set BASE, 0x1000 # constants are absolute
+        .section .rodata
+        hello: .asciz "hi\n"
+        .text
+        _start:
+            movl $0x200, %sp
+            movl $hello, %r0
+            moval (BASE+4), %r1
+            movl *4(%r1), (%r3)[%r4]
+            bsbw _start
+        "#;
+        let mut asm = Assembler::new(Syntax::Gnu);
+        let image = asm.origin(0x1000_0000).section_order(&[".text", ".rodata"])
+            .source("test", src).assemble().unwrap();
+        let hello = image.symbols["hello"];
+        assert_eq!(hello, 0x1000_0020);
+        let h = hello.to_le_bytes();
+        assert_eq!(image.bytes, [
+            0xD0, 0x8F, 0x00, 0x02, 0x00, 0x00, 0x5E,
+            0xD0, 0x8F, h[0], h[1], h[2], h[3], 0x50,
+            0xDE, 0x9F, 0x04, 0x10, 0x00, 0x00, 0x51,
+            0xD0, 0xB1, 0x04, 0x44, 0x63,
+            0x30, 0xE3, 0xFF,
+            0x00, 0x00, 0x00,
+            b'h', b'i', b'\n', 0,
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(err("CLRL #5"), "`#5` can't be used as a .wl operand");
+        assert_eq!(err("MOVAL R1, R2"), "`R1` can't be used as a .al operand");
+        assert_eq!(err("MOVL R1"), "`MOVL` takes 2 operands");
+        assert_eq!(err("FOO R1"), "unknown instruction `FOO`");
+        assert_eq!(err("MOVL nowhere, R1"), "undefined symbol `nowhere`");
+        assert_eq!(err("MOVL S^#64, R1"), "`64` doesn't fit a short literal");
+        assert_eq!(err("a: .BLKB 200\nBRB a"), "branch to `a` is out of range");
+        assert_eq!(err("a: HALT\na: HALT"), "`a` is already defined");
+        assert_eq!(err(".SPACE -1"), "`.space` can't reserve -1 bytes");
+        assert_eq!(err(".BLKL ^X4000000000000000"), "`.blkl ^X4000000000000000` is too big");
+        assert_eq!(err(".SPACE 99999999999"), "`.space 99999999999` is too big");
+        assert_eq!(err(".ALIGN -1"), "`.align -1` is out of range");
+        assert_eq!(err("MOVB #300, R0"), "`300` doesn't fit a byte");
+        assert_eq!(err("MOVW I^#-40000, R0"), "`-40000` doesn't fit a word");
+        assert_eq!(err(".BYTE 256"), "`256` doesn't fit a byte");
+        assert_eq!(err("MOVF S^#100.0, R0"), "`100.0` doesn't fit a short literal");
+        assert_eq!(err("MOVF #1.0E39, R0"), "`1.0E39` doesn't fit F_floating");
+        let e = assemble("HALT\n.BOGUS", 0, Syntax::Macro32).unwrap_err();
+        assert_eq!((e.file.as_str(), e.line), ("<input>", 2));
+    }
+
+    #[test]
+    fn data_ranges() {
+        let src = "MOVB #255, R0\nMOVB #-128, R0\nMOVW #^XFFFF, R0\n.BYTE -1, ^XFF\n.WORD -32768";
+        assert_eq!(asm(src, Syntax::Macro32), [
+            0x90, 0x8F, 0xFF, 0x50,
+            0x90, 0x8F, 0x80, 0x50,
+            0xB0, 0x8F, 0xFF, 0xFF, 0x50,
+            0xFF, 0xFF,
+            0x00, 0x80,
+        ]);
+    }
+
+    #[test]
+    fn float_immediates() {
+        // 100.0 is too big for a short literal, so it's encoded as F_floating.
+        let movf = [0x50, 0x8F, 0xC8, 0x43, 0x00, 0x00, 0x50];
+        assert_eq!(asm("MOVF #100.0, R0", Syntax::Macro32), movf);
+        assert_eq!(asm("movf $0f100.0, %r0", Syntax::Gnu), movf);
+        assert_eq!(asm("MOVF I^#^X43C8, R0", Syntax::Macro32), movf);
+        assert_eq!(asm("MOVF #100, R0", Syntax::Macro32), movf);
+        // 0.1 rounds, and the wider formats lay out their words the same way.
+        assert_eq!(asm("MOVF #0.1, R0", Syntax::Macro32)[2..6], [0xCC, 0x3E, 0xCD, 0xCC]);
+        assert_eq!(asm("MOVG #-2.5, R0", Syntax::Macro32), [
+            0xFD, 0x50, 0x8F, 0x24, 0xC0, 0, 0, 0, 0, 0, 0, 0x50,
+        ]);
+        assert_eq!(asm("MOVH #100.0, R0", Syntax::Macro32)[3..7], [0x07, 0x40, 0x00, 0x90]);
+        // Short literals still win where they can.
+        assert_eq!(asm("MOVF #1.5, R0", Syntax::Macro32), [0x50, 0x0C, 0x50]);
+    }
+
+    #[test]
+    fn bootrom_assembles() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("vsrc/bootrom");
+        let mut asm = Assembler::new(Syntax::Gnu);
+        asm.origin(0x1000_0000).section_order(&[".entry", ".text", ".rodata"]);
+        for f in &["start.s", "util.s", "dict.s", "forth.s", "corewords.s", "incalloc.s"] {
+            asm.file(dir.join(f)).unwrap();
+        }
+        let image = asm.assemble().unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(image.symbols["_start"], 0x1000_0000);
+        assert_eq!(
+            disassemble_with(&image.bytes, 0x1000_0000, Syntax::Gnu).1,
+            "movl $0x200, %sp",
+        );
+    }
+}
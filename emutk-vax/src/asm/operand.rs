@@ -0,0 +1,323 @@
+use super::expr::{eval, Env, Value};
+use crate::cpu::instrs::operands::{DataType, OperandMode, OperandSpec, PC};
+use crate::disasm::Syntax;
+use crate::{FloatFormat, UnpackedFloat};
+
+/// An operand's addressing mode, as written.
+enum Form<'a> {
+    /// `#x`, a literal or immediate, or with `@` an absolute address.
+    Immediate(&'a str),
+    Register(u8),
+    /// `(Rn)`, or `@(Rn)` which is shorthand for `@0(Rn)`.
+    RegisterDeferred(u8),
+    Autodecrement(u8),
+    Autoincrement(u8),
+    Displacement(&'a str, u8),
+    /// A bare address, relative to PC.
+    Relative(&'a str),
+}
+
+struct Parsed<'a> {
+    form: Form<'a>,
+    deferred: bool,
+    /// The letter of a `B^` style prefix forcing the operand's encoding.
+    size: Option<char>,
+    index: Option<u8>,
+}
+
+/// Parse a register name, like `R3`, `AP` or `%r3`.
+pub fn register(text: &str) -> Option<u8> {
+    let text = text.trim();
+    let name = text.strip_prefix('%').unwrap_or(text).to_ascii_uppercase();
+    match name.as_str() {
+        "AP" => Some(12),
+        "FP" => Some(13),
+        "SP" => Some(14),
+        "PC" => Some(15),
+        _ => match name.strip_prefix('R')?.parse() {
+            Ok(r) if r < 16 && !name[1..].starts_with('+') => Some(r),
+            _ => None,
+        },
+    }
+}
+
+fn parse(text: &str, syntax: Syntax) -> Result<Parsed<'_>, String> {
+    let (imm, defer, size_sep) = match syntax {
+        Syntax::Macro32 => ('#', '@', '^'),
+        Syntax::Gnu => ('$', '*', '`'),
+    };
+    let mut s = text.trim();
+
+    let mut index = None;
+    if s.ends_with(']') {
+        let open = s.rfind('[').ok_or_else(|| format!("missing `[` in `{}`", text))?;
+        let x = &s[open + 1..s.len() - 1];
+        index = Some(register(x).ok_or_else(|| format!("`{}` isn't a register", x))?);
+        s = s[..open].trim_end();
+    }
+
+    let deferred = s.starts_with(defer);
+    if deferred {
+        s = s[1..].trim_start();
+    }
+
+    let mut size = None;
+    let b = s.as_bytes();
+    if b.len() > 2 && b[1] == size_sep as u8 && b"SsIiBbWwLl".contains(&b[0]) {
+        size = Some(b[0].to_ascii_uppercase() as char);
+        s = &s[2..];
+    }
+
+    let form = if let Some(rest) = s.strip_prefix(imm) {
+        Form::Immediate(rest)
+    } else if let Some(r) = register(s) {
+        Form::Register(r)
+    } else if let Some(r) = s.strip_prefix("-(").and_then(|r| r.strip_suffix(')')).and_then(register) {
+        Form::Autodecrement(r)
+    } else if let Some(r) = s.strip_prefix('(').and_then(|r| r.strip_suffix(")+")).and_then(register) {
+        Form::Autoincrement(r)
+    } else {
+        match s.strip_suffix(')').and_then(|r| r.rfind('(').map(|open| (open, r))) {
+            Some((open, r)) if register(&r[open + 1..]).is_some() => {
+                let reg = register(&r[open + 1..]).unwrap();
+                let disp = r[..open].trim();
+                if disp.is_empty() {
+                    Form::RegisterDeferred(reg)
+                } else {
+                    Form::Displacement(disp, reg)
+                }
+            }
+            _ => Form::Relative(s),
+        }
+    };
+    Ok(Parsed { form, deferred, size, index })
+}
+
+/// Encode a short literal holding a float, if it fits in one.
+fn float_literal(v: f64) -> Option<u8> {
+    for exp in 0..8 {
+        for frac in 0..8 {
+            if (8.0 + frac as f64) / 16.0 * 2f64.powi(exp) == v {
+                return Some((exp << 3) as u8 | frac);
+            }
+        }
+    }
+    None
+}
+
+/// Parse a float written as a number, like `1.5`, or `0f1.5` in GNU syntax.
+fn float_number(text: &str, syntax: Syntax) -> Option<f64> {
+    let text = text.trim();
+    let num = match syntax {
+        Syntax::Gnu => {
+            let lower = text.to_ascii_lowercase();
+            if ["0f", "0d", "0g", "0h"].iter().any(|p| lower.starts_with(p)) {
+                &text[2..]
+            } else {
+                return None;
+            }
+        }
+        Syntax::Macro32 if text.contains('.') => text,
+        Syntax::Macro32 => return None,
+    };
+    num.parse().ok()
+}
+
+/// Encode a float as immediate data in the operand's format, rounded.
+fn float_data(v: f64, data: DataType) -> Option<Vec<u8>> {
+    let format = match data {
+        DataType::FFloat => FloatFormat::F,
+        DataType::DFloat => FloatFormat::D,
+        DataType::GFloat => FloatFormat::G,
+        _ => FloatFormat::H,
+    };
+    if !v.is_finite() {
+        return None;
+    }
+    let raw = format.pack(UnpackedFloat::from_f64(v), true, true).ok()?;
+    Some(raw.to_le_bytes()[..data.byte_len()].to_vec())
+}
+
+fn le_bytes(v: i64, len: usize) -> Vec<u8> {
+    (v as i128).to_le_bytes()[..len].to_vec()
+}
+
+fn fits(v: i64, len: usize) -> bool {
+    match len {
+        1 => v as i8 as i64 == v,
+        2 => v as i16 as i64 == v,
+        // Longwords can be written unsigned, as they can't be extended.
+        _ => v as i32 as i64 == v || v as u32 as i64 == v,
+    }
+}
+
+/// Whether `v` can be stored as `len` bytes of data, written either signed or
+/// unsigned.
+pub fn fits_data(v: i64, len: usize) -> bool {
+    len >= 8 || v >> (8 * len) == 0 || v >> (8 * len - 1) == -1
+}
+
+fn size_name(len: usize) -> &'static str {
+    match len {
+        1 => "byte",
+        2 => "word",
+        4 => "longword",
+        8 => "quadword",
+        _ => "octaword",
+    }
+}
+
+/// Encode an expression's value as `len` bytes, which it must fit.
+pub fn data_bytes(text: &str, v: Value, len: usize, env: &Env) -> Result<Vec<u8>, String> {
+    if env.strict && !fits_data(v.val, len) {
+        return Err(format!("`{}` doesn't fit a {}", text.trim(), size_name(len)));
+    }
+    Ok(le_bytes(v.val, len))
+}
+
+fn size_len(size: char) -> Option<usize> {
+    match size {
+        'B' => Some(1),
+        'W' => Some(2),
+        'L' => Some(4),
+        _ => None,
+    }
+}
+
+/// Encode a branch displacement operand, which ends at `end`.
+pub fn branch(text: &str, spec: OperandSpec, end: u32, env: &Env) -> Result<Vec<u8>, String> {
+    let len = spec.data.byte_len();
+    let target = eval(text, env)?;
+    let disp = target.val.wrapping_sub(end as i64) as i32 as i64;
+    if env.strict && !fits(disp, len) {
+        return Err(format!("branch to `{}` is out of range", text.trim()));
+    }
+    Ok(le_bytes(disp, len))
+}
+
+/// Encode a general operand specifier starting at `addr`. Modes the operand
+/// can't use are rejected with the same rules the CPU faults on.
+pub fn specifier(text: &str, spec: OperandSpec, addr: u32, env: &Env) -> Result<Vec<u8>, String> {
+    let parsed = parse(text, env.syntax)?;
+    let data_len = spec.data.byte_len();
+    let mut out = Vec::new();
+    if let Some(x) = parsed.index {
+        out.push(0x40 | x);
+    }
+    let spec_addr = addr.wrapping_add(out.len() as u32 + 1);
+
+    let bad_prefix = || format!("can't use that prefix on `{}`", text.trim());
+    let (head, tail) = match parsed.form {
+        Form::Immediate(x) if parsed.deferred => {
+            if parsed.size.is_some() {
+                return Err(bad_prefix());
+            }
+            (0x90 | PC, le_bytes(eval(x, env)?.val, 4))
+        }
+        Form::Immediate(x) => immediate(x, spec.data, parsed.size, env)?,
+        Form::Register(r) if !parsed.deferred && parsed.size.is_none() => (0x50 | r, vec![]),
+        Form::RegisterDeferred(r) if parsed.deferred => (0xB0 | r, vec![0]),
+        Form::RegisterDeferred(r) if parsed.size.is_none() => (0x60 | r, vec![]),
+        Form::Autodecrement(r) if !parsed.deferred && parsed.size.is_none() => (0x70 | r, vec![]),
+        Form::Autoincrement(r) if parsed.size.is_none() => {
+            ((if parsed.deferred { 0x90 } else { 0x80 }) | r, vec![])
+        }
+        Form::Displacement(x, r) => {
+            let v = eval(x, env)?;
+            let len = displacement_len(v, parsed.size, bad_prefix)?;
+            if env.strict && !fits(v.val, len) {
+                return Err(format!("displacement `{}` is out of range", x.trim()));
+            }
+            (disp_mode(len, parsed.deferred) | r, le_bytes(v.val, len))
+        }
+        Form::Relative(x) => {
+            let v = eval(x, env)?;
+            // A constant address is as good as absolute, which is what GNU as
+            // makes of it too.
+            if !v.label && !parsed.deferred && parsed.size.is_none() {
+                (0x90 | PC, le_bytes(v.val, 4))
+            } else {
+                // Labels get a word, like MACRO-32 defaults to.
+                let len = match parsed.size {
+                    Some(s) => size_len(s).ok_or_else(bad_prefix)?,
+                    None if v.label => 2,
+                    None => 4,
+                };
+                let disp = v.val.wrapping_sub(spec_addr.wrapping_add(len as u32) as i64) as i32 as i64;
+                if env.strict && !fits(disp, len) {
+                    return Err(format!("`{}` is out of range", x.trim()));
+                }
+                (disp_mode(len, parsed.deferred) | PC, le_bytes(disp, len))
+            }
+        }
+        _ => return Err(format!("bad operand `{}`", text.trim())),
+    };
+    out.push(head);
+    out.extend(tail);
+
+    let mode = OperandMode::identify_operand([out[0], *out.get(1).unwrap_or(&0)])
+        .and_then(|m| m.check_access(spec.access, data_len));
+    if mode.is_err() {
+        return Err(format!("`{}` can't be used as a {} operand", text.trim(), spec));
+    }
+    Ok(out)
+}
+
+/// Pick a displacement's length: the prefix's if there is one, otherwise the
+/// smallest that fits a constant, or a word for anything depending on a label,
+/// since those can move.
+fn displacement_len(v: Value, size: Option<char>, bad_prefix: impl Fn() -> String)
+    -> Result<usize, String>
+{
+    match size {
+        Some(s) => size_len(s).ok_or_else(bad_prefix),
+        None if v.label => Ok(2),
+        None => Ok([1, 2, 4].iter().copied().find(|&l| fits(v.val, l)).unwrap()),
+    }
+}
+
+fn disp_mode(len: usize, deferred: bool) -> u8 {
+    let mode = match len {
+        1 => 0xA0,
+        2 => 0xC0,
+        _ => 0xE0,
+    };
+    if deferred { mode | 0x10 } else { mode }
+}
+
+/// Encode `#x` as a short literal if it can be one, or immediate data.
+fn immediate(x: &str, data: DataType, size: Option<char>, env: &Env) -> Result<(u8, Vec<u8>), String> {
+    let data_len = data.byte_len();
+
+    if data.is_float() && size != Some('I') {
+        // Float operands take their value as a number, made a short literal if
+        // it can be one and immediate data in the operand's format otherwise.
+        // With I^ the value is the raw bits instead.
+        let v = match float_number(x, env.syntax) {
+            Some(v) => v,
+            None => eval(x, env)?.val as f64,
+        };
+        return match (float_literal(v), size) {
+            (Some(lit), None) | (Some(lit), Some('S')) => Ok((lit, vec![])),
+            (None, Some('S')) => Err(format!("`{}` doesn't fit a short literal", x.trim())),
+            (None, None) => match float_data(v, data) {
+                Some(bytes) => Ok((0x80 | PC, bytes)),
+                None => Err(format!(
+                    "`{}` doesn't fit {}_floating", x.trim(), data.suffix().to_ascii_uppercase(),
+                )),
+            },
+            _ => Err(format!("can't use that prefix on `#{}`", x.trim())),
+        };
+    }
+
+    let v = eval(x, env)?;
+    let literal = !v.label && (0..=63).contains(&v.val);
+    match size {
+        Some('S') if literal => Ok((v.val as u8, vec![])),
+        Some('S') if env.strict => Err(format!("`{}` doesn't fit a short literal", x.trim())),
+        Some('S') => Ok((0, vec![])),
+        None if literal => Ok((v.val as u8, vec![])),
+        Some('I') | None => Ok((0x80 | PC, data_bytes(x, v, data_len, env)?)),
+        Some(_) => Err(format!("can't use that prefix on `#{}`", x.trim())),
+    }
+}
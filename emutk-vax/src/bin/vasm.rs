@@ -0,0 +1,71 @@
+//! Assembles VAX sources into a flat binary image.
+//!
+//! `vasm [--macro32] [--origin ADDR] [--sections NAME,...] -o OUT FILE...`
+//!
+//! Sources are GNU as syntax unless `--macro32` is given.
+
+use std::process::exit;
+
+use emutk_vax::asm::{Assembler, Syntax};
+
+fn usage() -> ! {
+    eprintln!("usage: vasm [--macro32] [--origin ADDR] [--sections NAME,...] -o OUT FILE...");
+    exit(2);
+}
+
+fn parse_addr(s: &str) -> u32 {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.unwrap_or_else(|_| usage())
+}
+
+fn main() {
+    let mut syntax = Syntax::Gnu;
+    let mut origin = 0;
+    let mut sections = Vec::new();
+    let mut out = None;
+    let mut files = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--macro32" => syntax = Syntax::Macro32,
+            "--origin" => origin = parse_addr(&args.next().unwrap_or_else(|| usage())),
+            "--sections" => {
+                let list = args.next().unwrap_or_else(|| usage());
+                sections = list.split(',').map(str::to_string).collect();
+            }
+            "-o" => out = Some(args.next().unwrap_or_else(|| usage())),
+            a if a.starts_with('-') => usage(),
+            _ => files.push(arg),
+        }
+    }
+    let out = out.unwrap_or_else(|| usage());
+    if files.is_empty() {
+        usage();
+    }
+
+    let mut asm = Assembler::new(syntax);
+    let sections: Vec<&str> = sections.iter().map(String::as_str).collect();
+    asm.origin(origin).section_order(&sections);
+    for f in &files {
+        if let Err(e) = asm.file(f) {
+            eprintln!("{}: {}", f, e);
+            exit(1);
+        }
+    }
+    match asm.assemble() {
+        Ok(image) => {
+            if let Err(e) = std::fs::write(&out, &image.bytes) {
+                eprintln!("{}: {}", out, e);
+                exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
@@ -2,6 +2,8 @@ use emutk_core::{
     cycles::Cycles,
     bus::TaggedBus,
     bus::Bus,
+    bus::block_cycles,
+    bus::copy_block_buffered,
     ByteRepr,
 };
 
@@ -67,6 +69,19 @@ impl Bus<()> for RAMBus {
             (cyc, Ok(()))
         }
     }
+    fn slice(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        self.ram.get(addr..addr.checked_add(len)?)
+    }
+    fn slice_mut(&mut self, addr: usize, len: usize) -> Option<&mut [u8]> {
+        self.ram.get_mut(addr..addr.checked_add(len)?)
+    }
+    fn copy_block(&mut self, src: usize, dst: usize, len: usize) -> (Cycles, Result<(), ()>) {
+        if self.slice(src, len).is_some() && self.slice(dst, len).is_some() {
+            self.ram.copy_within(src..src+len, dst);
+            return (block_cycles(len), Ok(()));
+        }
+        copy_block_buffered(self, src, dst, len)
+    }
 }
 
 const UVAX31_ROM_BEGIN: usize = 0x2004_0000;
@@ -166,9 +181,46 @@ impl Bus<()> for MicroVAX3100Bus {
             }
         }
     }
+    fn slice(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        match MicroVAX3100Bus::block_addr(addr, len)? {
+            MicroVAXAddress::RAM(v) => self.ram.get(v..v+len),
+            MicroVAXAddress::BootROM(v) => self.boot_rom.get(v..v+len),
+            _ => None,
+        }
+    }
+    fn slice_mut(&mut self, addr: usize, len: usize) -> Option<&mut [u8]> {
+        match MicroVAX3100Bus::block_addr(addr, len)? {
+            MicroVAXAddress::RAM(v) => self.ram.get_mut(v..v+len),
+            _ => None,
+        }
+    }
+    fn copy_block(&mut self, src: usize, dst: usize, len: usize) -> (Cycles, Result<(), ()>) {
+        if let (Some(MicroVAXAddress::RAM(s)), Some(MicroVAXAddress::RAM(d)))
+            = (MicroVAX3100Bus::block_addr(src, len), MicroVAX3100Bus::block_addr(dst, len))
+        {
+            if s + len <= self.ram.len() && d + len <= self.ram.len() {
+                self.ram.copy_within(s..s+len, d);
+                return (block_cycles(len), Ok(()));
+            }
+        }
+        copy_block_buffered(self, src, dst, len)
+    }
 }
 
 impl MicroVAX3100Bus {
+    /// Work out which memory a block access lands in, if it's all in one.
+    fn block_addr(addr: usize, len: usize) -> Option<MicroVAXAddress> {
+        let last = addr.checked_add(len.max(1) - 1)?;
+        let within = |begin: usize, end: usize| (begin..=end).contains(&addr) && last <= end;
+        if within(UVAX31_RAM_BEGIN, UVAX31_RAM_END) {
+            Some(MicroVAXAddress::RAM(addr - UVAX31_RAM_BEGIN))
+        } else if within(UVAX31_ROM_BEGIN, UVAX31_ROM_END) {
+            Some(MicroVAXAddress::BootROM(addr - UVAX31_ROM_BEGIN))
+        } else {
+            None
+        }
+    }
+
     pub fn new(boot_rom: &'static [u8], ram_size: RAMSize) -> MicroVAX3100Bus {
         MicroVAX3100Bus {
             boot_rom,
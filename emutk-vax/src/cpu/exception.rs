@@ -0,0 +1,352 @@
+use crate::cpu::{VAXCPU, PSL};
+use crate::bus::VAXBus;
+use crate::{Error, ErrorKind, ExceptionClass};
+use crate::cpu::instrs::{
+    MultiInstruction,
+    suspend_multi_instruction,
+};
+
+impl<B: VAXBus> VAXCPU<'_, B> {
+    /// Take an exception raised by the current instruction, vectoring through the SCB.
+    ///
+    /// Errors that aren't architectural exceptions are handed back, as is the error
+    /// that halted the processor if the exception couldn't be taken.
+    pub fn take_exception(&mut self, e: Error) -> Result<(), Error> {
+        let vector = match e.scb_vector() {
+            Some(v) => v,
+            None => return Err(e),
+        };
+        if e.kind() == ErrorKind::ChangeMode {
+            return self.change_mode(e, vector);
+        }
+
+        match e.kind().class() {
+            ExceptionClass::Fault | ExceptionClass::Abort => {
+                if self.multi_instr_active != MultiInstruction::None {
+                    let start_pc = self.instr_start.get_pc();
+                    suspend_multi_instruction(self, start_pc);
+                } else {
+                    let snap = self.instr_start;
+                    self.regfile.restore(&snap);
+                }
+            },
+            ExceptionClass::Trap => {},
+            ExceptionClass::Halt => return Err(e),
+        }
+
+        self.dispatch(vector, e.frame_params(), None, false)
+    }
+
+    /// Take the highest priority pending interrupt if it's above the current IPL.
+    /// Returns true if one was taken.
+    ///
+    /// Only called between instructions, or between the parts of a multi-part
+    /// instruction, which is suspended so it can resume after the interrupt.
+    pub(crate) fn take_interrupt(&mut self) -> Result<bool, Error> {
+        let ipl = self.regfile.get_psl().get_ipl();
+        let hw_ipl = self.interrupts.highest_pending();
+        let sw_ipl = self.regfile.highest_software_interrupt();
+        if hw_ipl.max(sw_ipl) <= ipl {
+            return Ok(false);
+        }
+
+        let (level, vector) = if hw_ipl > sw_ipl {
+            match self.interrupts.acknowledge(hw_ipl) {
+                Some(v) => (hw_ipl, v),
+                None => return Ok(false), // Withdrawn before we got to it.
+            }
+        } else {
+            let sisr = self.regfile.get_sisr();
+            self.regfile.set_sisr(sisr & !(1 << sw_ipl));
+            (sw_ipl, 0x80 + 4 * sw_ipl as u32)
+        };
+
+        if self.multi_instr_active != MultiInstruction::None {
+            let start_pc = self.instr_start.get_pc();
+            suspend_multi_instruction(self, start_pc);
+        }
+        self.dispatch(vector, &[], Some(level), false)?;
+        Ok(true)
+    }
+
+    /// Push a PC/PSL frame followed by `params` and jump to the handler at `vector`.
+    ///
+    /// `int_ipl` is the new IPL when dispatching an interrupt, exceptions keep the
+    /// current IPL unless serviced on the interrupt stack. `severe` forces the
+    /// interrupt stack, as for kernel stack not valid.
+    pub(crate) fn dispatch(&mut self, vector: u32, params: &[u32], int_ipl: Option<u8>, severe: bool)
+        -> Result<(), Error>
+    {
+        let old_psl = *self.regfile.get_psl();
+        let old_pc = self.regfile.get_pc();
+
+        let handler = match self.read_phys::<u32>(self.regfile.get_scbb().wrapping_add(vector)) {
+            Ok(v) => v,
+            Err(e) => return Err(self.console_halt(e)),
+        };
+        // Codes 2 and 3 ask for writable control store or a halt, neither of which exist.
+        if handler & 0x2 != 0 {
+            return Err(self.console_halt(Error::new_machine_check()));
+        }
+
+        let to_is = severe || handler & 0x1 != 0;
+        let mut psl = PSL(0);
+        psl.set_is(old_psl.get_is() || to_is);
+        match int_ipl {
+            Some(ipl) => psl.set_ipl(ipl),
+            None => {
+                psl.set_ipl(if to_is { 0x1F } else { old_psl.get_ipl() });
+                psl.set_prv_mod(old_psl.get_cur_mod());
+            },
+        }
+        self.regfile.set_psl(psl);
+
+        let sp = self.regfile.get_sp().wrapping_sub(4 * (params.len() as u32 + 2));
+        let frame = params.iter().copied().chain([old_pc, old_psl.0]);
+        for (i, v) in frame.enumerate() {
+            if self.write_val(sp.wrapping_add(4 * i as u32), v).is_err() {
+                self.regfile.set_psl(old_psl);
+                return if psl.get_is() {
+                    Err(self.console_halt(Error::new_interrupt_stack_not_valid()))
+                } else {
+                    let ksnv = Error::new_kernel_stack_not_valid();
+                    self.dispatch(ksnv.scb_vector().unwrap(), ksnv.frame_params(), None, true)
+                };
+            }
+        }
+
+        self.regfile.set_sp(sp);
+        self.regfile.set_pc(handler & !0x3);
+        Ok(())
+    }
+
+    /// Enter the mode requested by a CHMx, on that mode's stack.
+    fn change_mode(&mut self, e: Error, vector: u32) -> Result<(), Error> {
+        let old_psl = *self.regfile.get_psl();
+        let old_pc = self.regfile.get_pc();
+        if old_psl.get_is() {
+            return Err(self.console_halt(e));
+        }
+
+        let handler = match self.read_phys::<u32>(self.regfile.get_scbb().wrapping_add(vector)) {
+            Ok(v) => v,
+            Err(e) => return Err(self.console_halt(e)),
+        };
+        if handler & 0x3 != 0 {
+            return Err(self.console_halt(Error::new_machine_check()));
+        }
+
+        let mut psl = PSL(0);
+        // The low byte is the mode to enter, the one above it the opcode's.
+        psl.set_cur_mod(e.data()[1] as u8);
+        psl.set_prv_mod(old_psl.get_cur_mod());
+        psl.set_ipl(old_psl.get_ipl());
+        self.regfile.set_psl(psl);
+
+        let sp = self.regfile.get_sp().wrapping_sub(12);
+        let frame = [e.data()[0], old_pc, old_psl.0];
+        for (i, v) in frame.iter().enumerate() {
+            if let Err(fault) = self.write_val(sp.wrapping_add(4 * i as u32), *v) {
+                // The new stack is unusable, so the CHMx itself faults.
+                self.regfile.set_psl(old_psl);
+                return self.take_exception(fault);
+            }
+        }
+
+        self.regfile.set_sp(sp);
+        self.regfile.set_pc(handler);
+        Ok(())
+    }
+
+    /// Halt the processor, leaving PC and PSL where the console can find them.
+    fn console_halt(&mut self, e: Error) -> Error {
+        let pc = self.regfile.get_pc();
+        let psl = self.regfile.get_psl().0;
+        self.regfile.set_conpc(pc);
+        self.regfile.set_conpsl(psl);
+        self.halt();
+        e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::exec::simple_test_cpu;
+    use crate::cpu::PSL;
+    use crate::{Error, ErrorKind};
+
+    const SCBB: u32 = 0x200;
+    const KSP: u32 = 0x1000;
+
+    fn setup_scb(bus: &mut crate::bus::RAMBus) {
+        let vectors: &[(u32, u32)] = &[
+            (0x08, 0x3001), // KSNV, interrupt stack
+            (0x18, 0x3100), // Reserved operand
+            (0x34, 0x3200), // Arithmetic
+        ];
+        for (vec, handler) in vectors {
+            let addr = (SCBB + vec) as usize;
+            bus.ram_mut()[addr..addr+4].copy_from_slice(&handler.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn fault_restarts_instruction() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        setup_scb(&mut bus);
+        // MTPR (R1)+, #62 ; no such register
+        bus.ram_mut()[0x800..0x803].copy_from_slice(&[0xDA, 0x81, 0x3E]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_ksp(KSP);
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r1(0x900);
+        cpu.regfile.set_psl(PSL(0x0004_0004));
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3100);
+        assert_eq!(cpu.regfile.get_r1(), 0x900);
+        assert_eq!(cpu.regfile.get_sp(), KSP - 8);
+        assert_eq!(cpu.read_phys::<u32>(KSP - 8).unwrap(), 0x800);
+        assert_eq!(cpu.read_phys::<u32>(KSP - 4).unwrap(), 0x0004_0004);
+        assert_eq!(cpu.regfile.get_psl().get_ipl(), 4);
+        assert!(!cpu.regfile.get_psl().get_z());
+    }
+
+    #[test]
+    fn trap_frame() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        setup_scb(&mut bus);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_ksp(KSP);
+        cpu.regfile.set_usp(0x1800);
+        cpu.regfile.set_psl(PSL(0x0300_0000));
+        cpu.regfile.set_pc(0x804);
+
+        cpu.take_exception(Error::new_integer_overflow_trap()).unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3200);
+        assert_eq!(cpu.regfile.get_psl().get_cur_mod(), 0);
+        assert_eq!(cpu.regfile.get_psl().get_prv_mod(), 3);
+        assert_eq!(cpu.regfile.get_usp(), 0x1800);
+        assert_eq!(cpu.regfile.get_sp(), KSP - 12);
+        assert_eq!(cpu.read_phys::<u32>(KSP - 12).unwrap(), 1);
+        assert_eq!(cpu.read_phys::<u32>(KSP - 8).unwrap(), 0x804);
+        assert_eq!(cpu.read_phys::<u32>(KSP - 4).unwrap(), 0x0300_0000);
+    }
+
+    #[test]
+    fn double_faults() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        setup_scb(&mut bus);
+        // S0 page 0 maps physical page 0, everything else is invalid.
+        bus.ram_mut()[0x1C00..0x1C04].copy_from_slice(&(0x8000_0000_u32 | (0b0010 << 27)).to_le_bytes());
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_sbr(0x1C00);
+        cpu.regfile.set_slr(4);
+        cpu.regfile.set_mapen(true);
+        cpu.regfile.set_ksp(0x8000_0400);
+        cpu.regfile.set_isp(0x8000_0100);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x8000_0010);
+
+        cpu.take_exception(Error::new_integer_overflow_trap()).unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3000);
+        assert!(cpu.regfile.get_psl().get_is());
+        assert_eq!(cpu.regfile.get_psl().get_ipl(), 0x1F);
+        assert_eq!(cpu.regfile.get_isp(), 0x8000_00F8);
+        assert_eq!(cpu.read_phys::<u32>(0xF8).unwrap(), 0x8000_0010);
+
+        cpu.regfile.set_isp(0x8000_0600);
+        let e = cpu.take_exception(Error::new_integer_overflow_trap()).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InterruptStackNotValid);
+        assert!(cpu.halted());
+        assert_eq!(cpu.regfile.get_conpc(), 0x3000);
+    }
+
+    #[test]
+    fn fault_suspends_string_instruction() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        setup_scb(&mut bus);
+        bus.ram_mut()[0x220..0x224].copy_from_slice(&0x3300_u32.to_le_bytes()); // TNV
+        // S0 page 0 maps physical page 0, page 1 is physical page 2 but not yet valid.
+        bus.ram_mut()[0x1C00..0x1C04].copy_from_slice(&(0x8000_0000_u32 | (0b0010 << 27)).to_le_bytes());
+        bus.ram_mut()[0x1C04..0x1C08].copy_from_slice(&((0b0010_u32 << 27) | 2).to_le_bytes());
+        // MOVC3 #8, (R1), (R2)
+        bus.ram_mut()[0x10..0x14].copy_from_slice(&[0x28, 0x08, 0x61, 0x62]);
+        bus.ram_mut()[0x100..0x108].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_sbr(0x1C00);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_mapen(true);
+        cpu.regfile.set_ksp(0x8000_00F0);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x8000_0010);
+        cpu.regfile.set_r1(0x8000_0100);
+        cpu.regfile.set_r2(0x8000_01FC);
+
+        while cpu.regfile.get_pc() != 0x3300 {
+            cpu.run_tick().unwrap();
+        }
+        assert_eq!(cpu.read_phys::<u32>(0xE0).unwrap(), 4); // Write
+        assert_eq!(cpu.read_phys::<u32>(0xE4).unwrap(), 0x8000_0200);
+        assert_eq!(cpu.read_phys::<u32>(0xE8).unwrap(), 0x8000_0010);
+        let psl = cpu.read_phys::<u32>(0xEC).unwrap();
+        assert!(PSL(psl).get_fpd());
+        assert_eq!(cpu.regfile.get_r0(), 0x0004_0004);
+        assert_eq!(cpu.regfile.get_r1(), 0x8000_0104);
+        assert_eq!(cpu.regfile.get_r3(), 0x8000_0200);
+
+        // Page the destination in and return to the instruction.
+        cpu.write_phys::<u32>(0x1C04, 0x8000_0000 | (0b0010 << 27) | 2).unwrap();
+        cpu.regfile.set_psl(PSL(psl));
+        cpu.regfile.set_pc(0x8000_0010);
+        while cpu.regfile.get_pc() != 0x8000_0014 || cpu.regfile.get_r0() != 0 {
+            cpu.run_tick().unwrap();
+        }
+        assert!(!cpu.regfile.get_psl().get_fpd());
+        assert_eq!(cpu.read_phys::<u32>(0x1FC).unwrap(), 0x0403_0201);
+        assert_eq!(cpu.read_phys::<u32>(0x400).unwrap(), 0x0807_0605);
+    }
+
+    #[test]
+    fn interrupts() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        setup_scb(&mut bus);
+        bus.ram_mut()[0x284..0x288].copy_from_slice(&0x3401_u32.to_le_bytes()); // Software IPL 1
+        bus.ram_mut()[0x3A0..0x3A4].copy_from_slice(&0x3501_u32.to_le_bytes()); // Device
+        // NOP ; NOP
+        bus.ram_mut()[0x800..0x802].copy_from_slice(&[0x01, 0x01]);
+        let line = cpu.interrupt_controller().line(0x15, 0x1A0);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_ksp(KSP);
+        cpu.regfile.set_isp(0x1400);
+        cpu.regfile.set_psl(PSL(0x0316_0000));
+        cpu.regfile.set_pc(0x800);
+
+        // Masked by IPL.
+        line.assert();
+        cpu.write_msr(20, 1).unwrap();
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x801);
+
+        cpu.regfile.get_psl_mut().set_ipl(0);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3500);
+        assert!(!line.is_asserted());
+        let psl = *cpu.regfile.get_psl();
+        assert!(psl.get_is());
+        assert_eq!((psl.get_ipl(), psl.get_cur_mod(), psl.get_prv_mod()), (0x15, 0, 0));
+        assert_eq!(cpu.read_phys::<u32>(0x13F8).unwrap(), 0x801);
+        assert_eq!(cpu.read_phys::<u32>(0x13FC).unwrap(), 0x0300_0000);
+
+        cpu.regfile.set_psl(PSL(0x0300_0000));
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3400);
+        assert_eq!(cpu.regfile.get_psl().get_ipl(), 1);
+        assert_eq!(cpu.regfile.get_sisr(), 0);
+    }
+}
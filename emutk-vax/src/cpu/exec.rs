@@ -5,10 +5,10 @@ use crate::bus::VAXBus;
 use crate::bus::RAMBus;
 use crate::Error;
 
-use crate::cpu::instrs::InstructionType;
 use crate::cpu::instrs::execute_instr;
 use crate::cpu::instrs::MultiInstruction;
 use crate::cpu::instrs::exec_multi_instructions;
+use crate::cpu::instrs::resume_multi_instruction;
 
 use emutk_core::cycles::Cycles;
 
@@ -18,17 +18,43 @@ impl<B: VAXBus> VAXCPU<'_, B> {
             return Ok(());
         }
 
+        if self.take_interrupt()? {
+            return Ok(());
+        }
+
+        match self.step() {
+            Ok(()) => Ok(()),
+            Err(e) => self.take_exception(e),
+        }
+    }
+
+    fn step(&mut self) -> Result<(), Error> {
+        let pc = if self.multi_instr_active != MultiInstruction::None {
+            self.instr_start.get_pc()
+        } else {
+            self.instr_start = self.regfile.snapshot();
+            self.regfile.get_pc()
+        };
+        #[cfg(feature = "trace")]
+        self.trace_begin(pc);
+        let res = self.step_instr(pc);
+        #[cfg(feature = "trace")]
+        self.trace_end(&res);
+        res
+    }
+
+    fn step_instr(&mut self, pc: u32) -> Result<(), Error> {
         if self.multi_instr_active != MultiInstruction::None {
             let mut cyc = Cycles(0);
             exec_multi_instructions(self, &mut cyc)?;
             self.cur_cycle += cyc;
             Ok(())
         } else {
-            let pc = self.regfile.get_pc();
             let instr = self.read_val(pc)?;
 
-            //println!("{:?}", InstructionType::from_instrid(instr));
-            //println!("{:01$x}", pc, 8);
+            if self.regfile.get_psl().get_fpd() && resume_multi_instruction(self, instr) {
+                return Ok(());
+            }
 
             let mut cyc = Cycles(0);
             execute_instr(instr, self, &mut cyc)?;
@@ -80,6 +106,7 @@ pub fn run_sample_to_done<'a>(dat: &'_ [u8]) -> (VAXCPU<'a, RAMBus>, RAMBus) {
 mod tests {
     use super::*;
     use crate::bus::RAMBus;
+    use crate::asm::{assemble, Syntax};
     use test::Bencher;
 
     #[test]
@@ -135,13 +162,11 @@ mod tests {
         let mut cpu = VAXCPU::new();
         let mut bus = RAMBus::new(8192);
         let rom = bus.ram_mut();
-        let bytes = &[
-            // MOVL $5, R0
-            0xD0, 0x05, 0x50,
-            // MULL2 R0, R0
-            0xC4, 0x50, 0x50,
-        ];
-        rom[0..bytes.len()].copy_from_slice(bytes);
+        let bytes = assemble("
+            MOVL #5, R0
+            MULL2 R0, R0
+        ", 0, Syntax::Macro32).unwrap();
+        rom[0..bytes.len()].copy_from_slice(&bytes);
         //(*rom)[8..15].copy_from_slice(bytes);
 
         cpu.give_bus(&mut bus);
@@ -162,23 +187,16 @@ mod tests {
         let mut cpu = VAXCPU::new();
         let mut bus = RAMBus::new(8192);
         let rom = bus.ram_mut();
-        let bytes = &[
-                // MOVL $5, R0
-                0xD0, 0x05, 0x50,
-                // MULL2 R0, R0
-                0xC4, 0x50, 0x50,
-                // NOP
-                0x01,
-                // MOVO $9, R1:R5
-                0xFD, 0x7D, 0x09, 0x51,
-                // ADDW2 1024, R0
-                0xA0, 0x8F, 0x00, 0x0F, 0x50,
-                // MNEGL R1, (R0)+
-                0xCE, 0x51, 0x80,
-                // CLRL -(R0)
-                0xD4, 0x70,
-            ];
-        rom[..(bytes.len())*32].chunks_mut(bytes.len()).for_each(|ch| ch.copy_from_slice(bytes));
+        let bytes = assemble("
+            MOVL #5, R0
+            MULL2 R0, R0
+            NOP
+            MOVO #9, R1
+            ADDW2 #^XF00, R0
+            MNEGL R1, (R0)+
+            CLRL -(R0)
+        ", 0, Syntax::Macro32).unwrap();
+        rom[..(bytes.len())*32].chunks_mut(bytes.len()).for_each(|ch| ch.copy_from_slice(&bytes));
         cpu.give_bus(&mut bus);
 
         b.iter(|| {
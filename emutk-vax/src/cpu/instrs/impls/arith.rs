@@ -70,6 +70,24 @@ pub fn instr_adwc
     })
 }
 
+pub fn instr_sbwc
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    rm_instr_wrap(cpu, |x: T, y: T, cpu: &mut VAXCPU<B>| -> Result<T, Error> {
+        let c: u32 = cpu.regfile.get_psl().get_c() as u32;
+
+        // Subtract the borrow separately, so it can't wrap the subtrahend.
+        let (f1, v) = y.flagged_sub(x);
+        let (mut flags, v) = v.flagged_sub(T::primitive_from(c));
+        flags.set_c(f1.get_c() || flags.get_c());
+        flags.set_v(f1.get_v() != flags.get_v());
+        cpu.commit_flags(flags);
+        Ok(v)
+    })
+}
+
 pub fn instr_sub3
     <B: VAXBus, T: VAXNum>
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
@@ -171,6 +189,44 @@ pub fn instr_ash
     })
 }
 
+pub fn instr_rotl
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    rrw_instr_wrap(cpu, |cnt: u8, src: u32, cpu: &mut VAXCPU<B>| -> Result<u32, Error> {
+        // Negative counts rotate right.
+        let out = src.rotate_left((cnt as i8).rem_euclid(32) as u32);
+        let mut flags = out.calc_nz();
+        flags.set_c(cpu.regfile.get_psl().get_c());
+        cpu.commit_flags(flags);
+        Ok(out)
+    })
+}
+
+/// INDEX computes `(indexin + subscript) * size` for array addressing, trapping
+/// once the result is stored if the subscript is outside `low..=high`.
+pub fn instr_index
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let subscript = parse_read_operand::<B,u32>(cpu)?.read(cpu)? as i32;
+    let low = parse_read_operand::<B,u32>(cpu)?.read(cpu)? as i32;
+    let high = parse_read_operand::<B,u32>(cpu)?.read(cpu)? as i32;
+    let size = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let indexin = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let opw = parse_write_operand::<B,u32>(cpu)?;
+
+    let out = indexin.wrapping_add(subscript as u32).wrapping_mul(size);
+    opw.write(cpu, out)?;
+    cpu.commit_flags(out.calc_nz());
+    if subscript < low || subscript > high {
+        return Err(Error::new_subscript_range_trap());
+    }
+    Ok(())
+}
+
 pub fn instr_cmp
     <B: VAXBus, T: VAXNum>
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
@@ -1,7 +1,7 @@
 use super::util::*;
 use crate::cpu::VAXCPU;
 use crate::bus::VAXBus;
-use crate::Error;
+use crate::{Error, CVZN};
 use crate::VAXNum;
 use emutk_core::{
     cycles::Cycles,
@@ -14,14 +14,16 @@ pub enum BitfieldOperand {
 }
 
 fn check_bitfield
-    <T: VAXBus>
     (
         pos: u32,
         field_len: u8,
-        mut base: UnresolvedOperand<u32>,
-        sign_extend: bool,
+        base: UnresolvedOperand<u8>,
     ) -> Result<BitfieldOperand, Error>
 {
+    if field_len > 32 {
+        return Err(Error::new_reserved_operand_fault());
+    }
+
     if field_len == 0 {
         return Ok(BitfieldOperand::Register {
             pos,
@@ -30,20 +32,19 @@ fn check_bitfield
         });
     }
 
-    if field_len > 32 {
-        return Err(Error::new_reserved_operand_fault());
-    }
-
-
     match base {
-        UnresolvedOperand::Value(_, reg) => {
+        UnresolvedOperand::Register(_, reg) => {
+            // Register fields can't start past the end of the register.
+            if pos > 31 {
+                return Err(Error::new_reserved_operand_fault());
+            }
             Ok(BitfieldOperand::Register {
                 pos,
                 field_len,
                 reg,
             })
         },
-        mut v => {
+        v => {
             Ok(BitfieldOperand::Memory {
                 pos,
                 field_len,
@@ -52,4 +53,354 @@ fn check_bitfield
         }
     }
 
-}
\ No newline at end of file
+}
+
+/// Parse the position, size and base operands that every bit field instruction has.
+fn parse_bitfield
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<BitfieldOperand, Error>
+{
+    let pos = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let size = parse_read_operand::<B,u8>(cpu)?.read(cpu)?;
+    parse_bitfield_base(cpu, pos, size)
+}
+
+/// Parse the base operand of a field at `pos` that's `size` bits long.
+fn parse_bitfield_base
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, pos: u32, size: u8)
+    -> Result<BitfieldOperand, Error>
+{
+    let mut base = parse_operand::<B,u8>(cpu, AccessType::VariableBitField)?;
+    base.resolve(cpu)?;
+    check_bitfield(pos, size, base)
+}
+
+/// Mask covering the low `len` bits.
+fn field_mask(len: u8) -> u64 {
+    (1_u64 << len) - 1
+}
+
+impl BitfieldOperand {
+    fn field_len(&self) -> u8 {
+        match *self {
+            BitfieldOperand::Register { field_len, .. } => field_len,
+            BitfieldOperand::Memory { field_len, .. } => field_len,
+        }
+    }
+
+    /// Where the memory a field lives in starts, the field's offset in it, and
+    /// how many bytes it covers.
+    fn memory_span(pos: u32, field_len: u8, base: u32) -> (u32, u32, usize) {
+        let pos = pos as i32;
+        let addr = base.wrapping_add((pos >> 3) as u32);
+        let shift = (pos & 7) as u32;
+        (addr, shift, (shift as usize + field_len as usize).div_ceil(8))
+    }
+
+    /// Registers a field covers, which can run over into the next one.
+    fn register_pair(reg: u8, pos: u32, field_len: u8) -> Result<Option<u8>, Error> {
+        if pos + field_len as u32 <= 32 {
+            return Ok(None);
+        }
+        if reg >= 15 {
+            return Err(Error::new_address_mode_fault());
+        }
+        Ok(Some(reg + 1))
+    }
+
+    /// Read the field, zero extended.
+    fn read<B: VAXBus>(&self, cpu: &mut VAXCPU<B>) -> Result<u32, Error> {
+        match *self {
+            BitfieldOperand::Register { field_len: 0, .. }
+            | BitfieldOperand::Memory { field_len: 0, .. } => Ok(0),
+            BitfieldOperand::Register { pos, field_len, reg } => {
+                let mut v = cpu.regfile.read_gpr(reg) as u64;
+                if let Some(next) = Self::register_pair(reg, pos, field_len)? {
+                    v |= (cpu.regfile.read_gpr(next) as u64) << 32;
+                }
+                Ok(((v >> pos) & field_mask(field_len)) as u32)
+            },
+            BitfieldOperand::Memory { pos, field_len, base } => {
+                let (addr, shift, len) = Self::memory_span(pos, field_len, base);
+                let mut buf = [0; 8];
+                cpu.read_block(addr, &mut buf[..len])?;
+                let v = u64::from_le_bytes(buf);
+                Ok(((v >> shift) & field_mask(field_len)) as u32)
+            },
+        }
+    }
+
+    /// Replace the field with the low bits of `val`.
+    fn write<B: VAXBus>(&self, cpu: &mut VAXCPU<B>, val: u32) -> Result<(), Error> {
+        match *self {
+            BitfieldOperand::Register { field_len: 0, .. }
+            | BitfieldOperand::Memory { field_len: 0, .. } => Ok(()),
+            BitfieldOperand::Register { pos, field_len, reg } => {
+                let next = Self::register_pair(reg, pos, field_len)?;
+                let mut v = cpu.regfile.read_gpr(reg) as u64;
+                if let Some(next) = next {
+                    v |= (cpu.regfile.read_gpr(next) as u64) << 32;
+                }
+                let mask = field_mask(field_len) << pos;
+                v = (v & !mask) | ((val as u64) << pos & mask);
+                cpu.regfile.write_gpr(reg, v as u32);
+                if let Some(next) = next {
+                    cpu.regfile.write_gpr(next, (v >> 32) as u32);
+                }
+                Ok(())
+            },
+            BitfieldOperand::Memory { pos, field_len, base } => {
+                let (addr, shift, len) = Self::memory_span(pos, field_len, base);
+                let mut buf = [0; 8];
+                cpu.read_block(addr, &mut buf[..len])?;
+                let mut v = u64::from_le_bytes(buf);
+                let mask = field_mask(field_len) << shift;
+                v = (v & !mask) | ((val as u64) << shift & mask);
+                cpu.write_block(addr, &v.to_le_bytes()[..len])
+            },
+        }
+    }
+}
+
+fn sign_extend(val: u32, len: u8) -> u32 {
+    if len == 0 || len == 32 {
+        return val;
+    }
+    let shift = 32 - len as u32;
+    (((val << shift) as i32) >> shift) as u32
+}
+
+fn instr_ext
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, signed: bool)
+    -> Result<(), Error>
+{
+    let field = parse_bitfield(cpu)?;
+    let dst = parse_write_operand::<B,u32>(cpu)?;
+    let mut v = field.read(cpu)?;
+    if signed {
+        v = sign_extend(v, field.field_len());
+    }
+    dst.write(cpu, v)?;
+
+    let mut flags = v.calc_nz();
+    flags.set_c(cpu.regfile.get_psl().get_c());
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_extv
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_ext(cpu, true)
+}
+
+pub fn instr_extzv
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_ext(cpu, false)
+}
+
+fn instr_cmp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, signed: bool)
+    -> Result<(), Error>
+{
+    let field = parse_bitfield(cpu)?;
+    let src = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let mut v = field.read(cpu)?;
+    if signed {
+        v = sign_extend(v, field.field_len());
+    }
+
+    let mut flags = CVZN::blank();
+    flags.set_n((v as i32) < (src as i32));
+    flags.set_z(v == src);
+    flags.set_c(v < src);
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_cmpv
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_cmp(cpu, true)
+}
+
+pub fn instr_cmpzv
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_cmp(cpu, false)
+}
+
+pub fn instr_insv
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let src = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let field = parse_bitfield(cpu)?;
+    field.write(cpu, src)
+}
+
+fn instr_find_first
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, set: bool)
+    -> Result<(), Error>
+{
+    let field = parse_bitfield(cpu)?;
+    let start = match field {
+        BitfieldOperand::Register { pos, .. } | BitfieldOperand::Memory { pos, .. } => pos,
+    };
+    let dst = parse_write_operand::<B,u32>(cpu)?;
+    let len = field.field_len() as u32;
+    let mut v = field.read(cpu)?;
+    if !set {
+        v = !v & field_mask(len as u8) as u32;
+    }
+
+    // Not finding anything leaves the position just past the field.
+    let found = if v != 0 { v.trailing_zeros() } else { len };
+    dst.write(cpu, start.wrapping_add(found))?;
+
+    let mut flags = CVZN::blank();
+    flags.set_z(v == 0);
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_ffs
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_find_first(cpu, true)
+}
+
+pub fn instr_ffc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_find_first(cpu, false)
+}
+
+/// What a branch on bit instruction does to the bit after testing it.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum BitUpdate {
+    Leave,
+    Set,
+    Clear,
+}
+
+fn instr_branch_bit
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, branch_if: bool, update: BitUpdate, interlocked: bool)
+    -> Result<(), Error>
+{
+    let pos = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let field = parse_bitfield_base(cpu, pos, 1)?;
+    let displ = read_data::<u8, B>(cpu)?;
+
+    let new_bit = |old: bool| match update {
+        BitUpdate::Leave => old,
+        BitUpdate::Set => true,
+        BitUpdate::Clear => false,
+    };
+    let bit = match field {
+        BitfieldOperand::Memory { pos, base, .. } if interlocked => {
+            // Only memory is visible to anyone else, so registers needn't bother.
+            let (addr, shift, _) = BitfieldOperand::memory_span(pos, 1, base);
+            let old = cpu.interlocked_update::<u8, _>(addr, |b| {
+                (b & !(1 << shift)) | ((new_bit(b >> shift & 1 != 0) as u8) << shift)
+            })?;
+            old >> shift & 1 != 0
+        },
+        _ => {
+            let bit = field.read(cpu)? != 0;
+            if update != BitUpdate::Leave {
+                field.write(cpu, new_bit(bit) as u32)?;
+            }
+            bit
+        },
+    };
+
+    if bit == branch_if {
+        jump_with_byte_displacement(cpu, displ);
+    }
+    Ok(())
+}
+
+pub fn instr_bbs
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, true, BitUpdate::Leave, false)
+}
+
+pub fn instr_bbc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, false, BitUpdate::Leave, false)
+}
+
+pub fn instr_bbss
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, true, BitUpdate::Set, false)
+}
+
+pub fn instr_bbcs
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, false, BitUpdate::Set, false)
+}
+
+pub fn instr_bbsc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, true, BitUpdate::Clear, false)
+}
+
+pub fn instr_bbcc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, false, BitUpdate::Clear, false)
+}
+
+pub fn instr_bbssi
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, true, BitUpdate::Set, true)
+}
+
+pub fn instr_bbcci
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_branch_bit(cpu, false, BitUpdate::Clear, true)
+}
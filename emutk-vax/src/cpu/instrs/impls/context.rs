@@ -0,0 +1,229 @@
+use super::util::*;
+use crate::cpu::VAXCPU;
+use crate::bus::VAXBus;
+use crate::{Error, CVZN};
+use crate::mmu::MemoryAccessType;
+use crate::cpu::{PSL, PrivilegeMode};
+use num_traits::FromPrimitive;
+use emutk_core::{
+    cycles::Cycles,
+};
+
+/// PSL bits that must be zero: VM, bit 28, bit 21 and <15:8>.
+const PSL_MBZ: u32 = 0x3020_FF00;
+
+// Process Control Block layout, as offsets from PCBB.
+const PCB_KSP: u32 = 0;
+const PCB_ESP: u32 = 4;
+const PCB_SSP: u32 = 8;
+const PCB_USP: u32 = 12;
+const PCB_R0: u32 = 16;
+const PCB_PC: u32 = 72;
+const PCB_PSL: u32 = 76;
+const PCB_P0BR: u32 = 80;
+/// P0LR<21:0>, with ASTLVL in <26:24>.
+const PCB_P0LR: u32 = 84;
+const PCB_P1BR: u32 = 88;
+/// P1LR<21:0>, with the performance monitor enable in <31>.
+const PCB_P1LR: u32 = 92;
+
+const PCB_LR_MASK: u32 = 0x3F_FFFF;
+
+/// Check that REI may load `new` while running with `old`.
+fn rei_psl_valid(old: PSL, new: PSL) -> bool {
+    let cur = new.get_cur_mod();
+    let ipl = new.get_ipl();
+    new.0 & PSL_MBZ == 0
+        && !new.get_cm()
+        && cur >= old.get_cur_mod()
+        && new.get_prv_mod() >= cur
+        && ipl <= old.get_ipl()
+        && (cur == 0 || ipl == 0)
+        && (!new.get_is() || (old.get_is() && cur == 0 && ipl != 0))
+}
+
+pub fn instr_rei
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let sp = cpu.regfile.get_sp();
+    let new_pc: u32 = cpu.read_val(sp)?;
+    let mut new_psl = PSL(cpu.read_val(sp.wrapping_add(4))?);
+    let old_psl = *cpu.regfile.get_psl();
+
+    if !rei_psl_valid(old_psl, new_psl) {
+        return Err(Error::new_reserved_operand_fault());
+    }
+
+    // Leave the old stack before the new PSL picks the new one.
+    cpu.regfile.set_sp(sp.wrapping_add(8));
+    if old_psl.get_tp() {
+        new_psl.set_tp(true);
+    }
+    cpu.regfile.set_psl(new_psl);
+    cpu.regfile.set_pc(new_pc);
+
+    // Deliver any AST the new mode is eligible for.
+    if new_psl.get_cur_mod() as u32 >= cpu.regfile.get_astlvl() && new_psl.get_ipl() < 2 {
+        cpu.regfile.request_software_interrupt(2);
+    }
+    Ok(())
+}
+
+pub fn instr_ldpctx
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    assert_kernel_mode(cpu)?;
+    let pcb = cpu.regfile.get_pcbb();
+
+    let ksp = cpu.read_phys(pcb + PCB_KSP)?;
+    cpu.regfile.set_ksp(ksp);
+    let esp = cpu.read_phys(pcb + PCB_ESP)?;
+    cpu.regfile.set_esp(esp);
+    let ssp = cpu.read_phys(pcb + PCB_SSP)?;
+    cpu.regfile.set_ssp(ssp);
+    let usp = cpu.read_phys(pcb + PCB_USP)?;
+    cpu.regfile.set_usp(usp);
+    for i in 0..14 {
+        let v = cpu.read_phys(pcb + PCB_R0 + 4 * i as u32)?;
+        cpu.regfile.write_gpr(i, v);
+    }
+
+    let p0br = cpu.read_phys(pcb + PCB_P0BR)?;
+    cpu.regfile.set_p0br(p0br);
+    let p0lr: u32 = cpu.read_phys(pcb + PCB_P0LR)?;
+    cpu.regfile.set_p0lr(p0lr & PCB_LR_MASK);
+    cpu.regfile.set_astlvl((p0lr >> 24) & 0x7);
+    let p1br = cpu.read_phys(pcb + PCB_P1BR)?;
+    cpu.regfile.set_p1br(p1br);
+    let p1lr: u32 = cpu.read_phys(pcb + PCB_P1LR)?;
+    cpu.regfile.set_p1lr(p1lr & PCB_LR_MASK);
+
+    // The old process' translations are meaningless now.
+    cpu.tb.invalidate_process();
+
+    // Switch to the kernel stack, leaving the new PC and PSL on it for the REI
+    // that follows.
+    let pc: u32 = cpu.read_phys(pcb + PCB_PC)?;
+    let psl: u32 = cpu.read_phys(pcb + PCB_PSL)?;
+    cpu.regfile.get_psl_mut().set_is(false);
+    push(cpu, psl)?;
+    push(cpu, pc)?;
+    Ok(())
+}
+
+pub fn instr_svpctx
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    assert_kernel_mode(cpu)?;
+    let pc = pop::<B, u32>(cpu)?;
+    let psl = pop::<B, u32>(cpu)?;
+    let pcb = cpu.regfile.get_pcbb();
+
+    cpu.write_phys(pcb + PCB_KSP, cpu.regfile.get_ksp())?;
+    cpu.write_phys(pcb + PCB_ESP, cpu.regfile.get_esp())?;
+    cpu.write_phys(pcb + PCB_SSP, cpu.regfile.get_ssp())?;
+    cpu.write_phys(pcb + PCB_USP, cpu.regfile.get_usp())?;
+    for i in 0..14 {
+        cpu.write_phys(pcb + PCB_R0 + 4 * i as u32, cpu.regfile.read_gpr(i))?;
+    }
+    cpu.write_phys(pcb + PCB_PC, pc)?;
+    cpu.write_phys(pcb + PCB_PSL, psl)?;
+
+    let cur = cpu.regfile.get_psl_mut();
+    if !cur.get_is() {
+        cur.set_is(true);
+        if cur.get_ipl() == 0 {
+            cur.set_ipl(1);
+        }
+    }
+    Ok(())
+}
+
+/// Change to `target` mode, or stay in the current one if it's more privileged.
+fn change_mode
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, target: PrivilegeMode)
+    -> Result<(), Error>
+{
+    let code = parse_read_operand::<B,u16>(cpu)?.read(cpu)?;
+    let mode = (target as u8).min(cpu.regfile.get_psl().get_cur_mod());
+    Err(Error::new_change_mode_trap(code, target as u8, mode))
+}
+
+pub fn instr_chmk
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    change_mode(cpu, PrivilegeMode::Kernel)
+}
+
+pub fn instr_chme
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    change_mode(cpu, PrivilegeMode::Executive)
+}
+
+pub fn instr_chms
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    change_mode(cpu, PrivilegeMode::Supervisor)
+}
+
+pub fn instr_chmu
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    change_mode(cpu, PrivilegeMode::User)
+}
+
+/// Check whether the first and last bytes of a structure could be accessed in
+/// the less privileged of the operand mode and the previous mode, setting Z if
+/// they can't. Only the protection codes are checked, not the valid bits.
+fn probe
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, access: MemoryAccessType)
+    -> Result<(), Error>
+{
+    let mode = parse_read_operand::<B,u8>(cpu)?.read(cpu)? & 0x3;
+    let len = parse_read_operand::<B,u16>(cpu)?.read(cpu)? as u32;
+    let base = parse_address_operand::<B,u8>(cpu)?;
+
+    let mode = mode.max(cpu.regfile.get_psl().get_prv_mod());
+    let mode = PrivilegeMode::from_u8(mode).unwrap();
+    let last = base.wrapping_add(len.saturating_sub(1));
+    let accessible = cpu.probe(base, mode, access)? && cpu.probe(last, mode, access)?;
+
+    let mut flags = CVZN::blank();
+    flags.set_z(!accessible);
+    flags.set_c(cpu.regfile.get_psl().get_c());
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_prober
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    probe(cpu, MemoryAccessType::Read)
+}
+
+pub fn instr_probew
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    probe(cpu, MemoryAccessType::Write)
+}
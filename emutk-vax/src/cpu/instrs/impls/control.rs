@@ -2,6 +2,7 @@ use super::util::*;
 use crate::cpu::VAXCPU;
 use crate::bus::VAXBus;
 use crate::{VAXNum, Error};
+use crate::cpu::instrs::operands::UnresolvedOperand;
 use crate::cpu::PSL;
 use emutk_core::{
     cycles::Cycles,
@@ -244,6 +245,109 @@ pub fn instr_branch_subroutine_word
     Ok(())
 }
 
+/// Add `step` to a loop's resolved index operand, setting NZV and leaving C alone.
+/// Returns the new index, sign extended for comparing against the limit.
+fn step_loop_index
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, index: &mut UnresolvedOperand<T>, step: T)
+    -> Result<i128, Error>
+{
+    let (mut flags, res) = index.read(cpu)?.flagged_add(step);
+    index.write(cpu, res)?;
+    flags.set_c(cpu.regfile.get_psl().get_c());
+    cpu.commit_flags(flags);
+    Ok(int_value(res))
+}
+
+pub fn instr_acb
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let limit = parse_read_operand::<B, T>(cpu)?.read(cpu)?;
+    let add = parse_read_operand::<B, T>(cpu)?.read(cpu)?;
+    let mut index = parse_modify_operand::<B, T>(cpu)?;
+    index.resolve(cpu)?;
+    let displ = read_data::<u16, B>(cpu)?;
+
+    let index = step_loop_index(cpu, &mut index, add)?;
+    let limit = int_value(limit);
+    let taken = if int_value(add) < 0 { index >= limit } else { index <= limit };
+    if taken {
+        jump_with_word_displacement(cpu, displ);
+    }
+    // Overflow traps once the branch is done, like any other trap.
+    cpu.check_integer_overflow()
+}
+
+/// AOBLSS and AOBLEQ, `or_equal` picks the latter.
+pub fn instr_aob
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles, or_equal: bool)
+    -> Result<(), Error>
+{
+    let limit = parse_read_operand::<B, u32>(cpu)?.read(cpu)?;
+    let mut index = parse_modify_operand::<B, u32>(cpu)?;
+    index.resolve(cpu)?;
+    let displ = read_data::<u8, B>(cpu)?;
+
+    let index = step_loop_index(cpu, &mut index, 1)?;
+    let limit = int_value(limit);
+    if index < limit || (or_equal && index == limit) {
+        jump_with_byte_displacement(cpu, displ);
+    }
+    cpu.check_integer_overflow()
+}
+
+pub fn instr_aoblss
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_aob(cpu, cycle_count, false)
+}
+
+pub fn instr_aobleq
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_aob(cpu, cycle_count, true)
+}
+
+/// SOBGEQ and SOBGTR, `or_equal` picks the former.
+pub fn instr_sob
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles, or_equal: bool)
+    -> Result<(), Error>
+{
+    let mut index = parse_modify_operand::<B, u32>(cpu)?;
+    index.resolve(cpu)?;
+    let displ = read_data::<u8, B>(cpu)?;
+
+    let index = step_loop_index(cpu, &mut index, u32::MAX)?;
+    if index > 0 || (or_equal && index == 0) {
+        jump_with_byte_displacement(cpu, displ);
+    }
+    cpu.check_integer_overflow()
+}
+
+pub fn instr_sobgeq
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_sob(cpu, cycle_count, true)
+}
+
+pub fn instr_sobgtr
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    instr_sob(cpu, cycle_count, false)
+}
+
 pub fn instr_case
     <T: VAXNum, B: VAXBus>
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
@@ -276,9 +380,7 @@ pub fn instr_jmp
     (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let mut selector = parse_read_operand::<T, u32>(cpu)?;
-    selector.validate(cpu)?;
-    let addr = selector.address()?;
+    let addr = parse_address_operand::<T, u8>(cpu)?;
     cpu.regfile.set_pc(addr);
     Ok(())
 }
@@ -288,9 +390,7 @@ pub fn instr_jsb
     (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let mut selector = parse_read_operand::<T, u32>(cpu)?;
-    selector.validate(cpu)?;
-    let addr = selector.address()?;
+    let addr = parse_address_operand::<T, u8>(cpu)?;
     push(cpu, cpu.regfile.get_pc())?;
     cpu.regfile.set_pc(addr);
     Ok(())
@@ -306,95 +406,85 @@ pub fn instr_rsb
     Ok(())
 }
 
-pub fn instr_callg
-    <T: VAXBus>
-    (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
+/// Entry mask bits 13:12 are reserved.
+const ENTRY_MASK_MBZ: u16 = 0x3000;
+/// Saved PSW bits 15:8 are reserved.
+const SAVED_PSW_MBZ: u32 = 0xFF00;
+/// Saved status bit marking a frame built by CALLS.
+const FRAME_CALLS: u32 = 1 << 29;
+
+/// The common part of CALLG and CALLS: build a call frame under `sp` and jump
+/// past the entry mask at `dest`. Registers aren't touched until the whole
+/// frame has been written, so a fault can just restart the instruction.
+fn call_procedure
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, dest: u32, ap: u32, sp: u32, calls: bool)
     -> Result<(), Error>
 {
-    let mut arglist = parse_read_operand::<T, u8>(cpu)?;
-    arglist.validate(cpu)?;
-    let mut dest = parse_read_operand::<T, u8>(cpu)?;
-    dest.validate(cpu)?;
-
-    let header = cpu.read_val::<u16>(dest.address()?)?;
-
+    let mask: u16 = cpu.read_val(dest)?;
+    if mask & ENTRY_MASK_MBZ != 0 {
+        return Err(Error::new_reserved_operand_fault());
+    }
 
-    let old_sp = cpu.regfile.get_sp(); 
-    let sp = old_sp & !0x3; // zero lower 2 bits for alignment
-    cpu.regfile.set_sp(sp);
-    // read mask and push registers as needed
-    for i in 11..0 {
-        if header & (1 << i) != 0 {
-            let reg = cpu.regfile.read_gpr(i);
-            push(cpu, reg)?;
+    // Longwords in the order they get pushed.
+    let mut frame = vec![];
+    for i in (0..12).rev() {
+        if mask & (1 << i) != 0 {
+            frame.push(cpu.regfile.read_gpr(i));
         }
     }
-    push(cpu, cpu.regfile.get_pc())?;
-    push(cpu, cpu.regfile.read_gpr(13))?; // FP
-    push(cpu, cpu.regfile.read_gpr(12))?; // AP
+    frame.push(cpu.regfile.get_pc());
+    frame.push(cpu.regfile.read_gpr(13)); // FP
+    frame.push(cpu.regfile.read_gpr(12)); // AP
+    frame.push(
+        (sp & 0x3) << 30
+        | (calls as u32) << 29
+        | (mask as u32 & 0xFFF) << 16
+        | (cpu.regfile.get_psl().0 & 0xFFE0) // NZVC and T are saved clear.
+    );
+    frame.push(0); // Condition handler.
+
+    let bytes: Vec<u8> = frame.iter().rev().flat_map(|v| v.to_le_bytes()).collect();
+    let new_sp = (sp & !0x3).wrapping_sub(bytes.len() as u32);
+    cpu.write_block(new_sp, &bytes)?;
+
+    cpu.regfile.set_sp(new_sp);
+    cpu.regfile.write_gpr(13, new_sp);
+    cpu.regfile.write_gpr(12, ap);
+    cpu.regfile.set_pc(dest.wrapping_add(2));
     let psl = cpu.regfile.get_psl_mut();
     psl.0 &= !0xF; // zero NZVC
-    let masks = ((old_sp & 0x3) << 30) 
-              | ((header as u32 & 0b111_1111_1111) << 15)
-              | (psl.0 & 0xFFFF);
-    push(cpu, masks)?;
-    push(cpu, 0_u32)?;
-    cpu.regfile.write_gpr(13, sp);
-    cpu.regfile.write_gpr(12, arglist.address()?);
-
-    let psl = cpu.regfile.get_psl_mut(); // rustc pls
-    psl.set_dv((header & (1 << 15)) != 0);
-    psl.set_iv((header & (1 << 14)) != 0);
+    psl.set_dv(mask & (1 << 15) != 0);
+    psl.set_iv(mask & (1 << 14) != 0);
     psl.set_fu(false);
-
     Ok(())
 }
 
-pub fn instr_calls
+pub fn instr_callg
     <T: VAXBus>
     (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let mut argnum = parse_read_operand::<T, u32>(cpu)?.read(cpu)?;
-    let mut dest = parse_read_operand::<T, u8>(cpu)?;
-    dest.validate(cpu)?;
-
-    push(cpu, argnum)?;
-    let tmp1 = cpu.regfile.get_sp();
+    let arglist = parse_address_operand::<T, u8>(cpu)?;
+    let dest = parse_address_operand::<T, u8>(cpu)?;
 
-    let header = cpu.read_val::<u16>(dest.address()?)?;
-
-
-    let old_sp = cpu.regfile.get_sp(); 
-    let sp = old_sp & !0x3; // zero lower 2 bits for alignment
-    cpu.regfile.set_sp(sp);
-    // read mask and push registers as needed
-    for i in 11..0 {
-        if header & (1 << i) != 0 {
-            let reg = cpu.regfile.read_gpr(i);
-            push(cpu, reg)?;
-        }
-    }
-    push(cpu, cpu.regfile.get_pc())?;
-    push(cpu, cpu.regfile.read_gpr(13))?; // FP
-    push(cpu, cpu.regfile.read_gpr(12))?; // AP
-    let psl = cpu.regfile.get_psl_mut();
-    psl.0 &= !0xF; // zero NZVC
-    let masks = ((old_sp & 0x3) << 30) 
-              | ((header as u32 & 0b111_1111_1111) << 15)
-              | (psl.0 & 0xFFFF)
-              | 1 << 29;
-    push(cpu, masks)?;
-    push(cpu, 0_u32)?;
-    cpu.regfile.write_gpr(13, sp);
-    cpu.regfile.write_gpr(12, tmp1);
-
-    let psl = cpu.regfile.get_psl_mut(); // rustc pls
-    psl.set_dv((header & (1 << 15)) != 0);
-    psl.set_iv((header & (1 << 14)) != 0);
-    psl.set_fu(false);
+    let sp = cpu.regfile.get_sp();
+    call_procedure(cpu, dest, arglist, sp, false)
+}
 
-    Ok(())
+pub fn instr_calls
+    <T: VAXBus>
+    (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let argnum = parse_read_operand::<T, u32>(cpu)?.read(cpu)?;
+    let dest = parse_address_operand::<T, u8>(cpu)?;
+
+    // The argument count goes on the stack first, and the argument list is
+    // whatever was pushed before it.
+    let sp = cpu.regfile.get_sp().wrapping_sub(4);
+    cpu.write_val(sp, argnum)?;
+    call_procedure(cpu, dest, sp, sp, true)
 }
 
 pub fn instr_ret
@@ -402,35 +492,43 @@ pub fn instr_ret
     (cpu: &mut VAXCPU<T>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    {
-        let sp = cpu.regfile.get_sp();
-        cpu.regfile.set_sp(sp + 4);
+    // Read the whole frame before changing anything, skipping the handler.
+    let mut sp = cpu.regfile.read_gpr(13).wrapping_add(4);
+    let mut pop_frame = |cpu: &mut VAXCPU<T>| -> Result<u32, Error> {
+        let v = cpu.read_val(sp)?;
+        sp = sp.wrapping_add(4);
+        Ok(v)
+    };
+    let status = pop_frame(cpu)?;
+    if status & SAVED_PSW_MBZ != 0 {
+        return Err(Error::new_reserved_operand_fault());
     }
-    let tmp1: u32 = pop(cpu)?;
-    let ap = pop(cpu)?;
-    cpu.regfile.write_gpr(12, ap);
-    let fp = pop(cpu)?;
-    cpu.regfile.write_gpr(13, fp);
-    let pc = pop(cpu)?;
-    cpu.regfile.set_pc(pc);
-    let tmp2 = (tmp1 >> 16) & 0b111_1111_1111;
-    for i in 0..11 {
-        if tmp2 & (1 << i) != 0 {
-            let rval = pop(cpu)?;
-            cpu.regfile.write_gpr(i, rval);
+    let ap = pop_frame(cpu)?;
+    let fp = pop_frame(cpu)?;
+    let pc = pop_frame(cpu)?;
+    let mut regs = [None; 12];
+    for (i, reg) in regs.iter_mut().enumerate() {
+        if status & (1 << (16 + i)) != 0 {
+            *reg = Some(pop_frame(cpu)?);
         }
     }
-    let offs = (tmp1 >> 30) & 0x3;
-    let sp = cpu.regfile.get_sp();
-    cpu.regfile.set_sp(sp + offs);
+    let mut sp = sp.wrapping_add(status >> 30);
+    if status & FRAME_CALLS != 0 {
+        // Pop the argument list CALLS pushed too.
+        let argnum: u32 = cpu.read_val(sp)?;
+        sp = sp.wrapping_add(4 + 4 * (argnum & 0xFF));
+    }
 
-    if ((tmp1 >> 29) & 0x1) != 0 {
-        let v: u32 = pop(cpu)?;
-        let tmp4 = 4 * (v & 0xFF);
-        cpu.regfile.set_sp(sp + offs + tmp4);
+    for (i, reg) in regs.iter().enumerate() {
+        if let Some(v) = reg {
+            cpu.regfile.write_gpr(i as u8, *v);
+        }
     }
+    cpu.regfile.write_gpr(12, ap);
+    cpu.regfile.write_gpr(13, fp);
+    cpu.regfile.set_sp(sp);
+    cpu.regfile.set_pc(pc);
     let psl = cpu.regfile.get_psl_mut();
-    psl.0 &= tmp1 & 0xFFFF;
-
+    psl.0 = (psl.0 & !0xFFFF) | (status & 0xFFFF);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,554 @@
+use super::util::*;
+use super::string::MultiInstruction;
+use crate::cpu::VAXCPU;
+use crate::bus::VAXBus;
+use crate::{Error, CVZN};
+use crate::{Decimal, MAX_DECIMAL_DIGITS, packed_size};
+use emutk_core::{
+    cycles::Cycles,
+};
+
+// The packed decimal instructions decode their operands into R0-R5 and start a
+// multi-part instruction, which does the arithmetic on the next step. Nothing is
+// written until every source has been read and the destination checked, so if
+// anything faults the instruction can be restarted from the registers with FPD.
+//
+// The conversions work the same way, but do everything in one go since there's
+// no room for all their operands in the registers. A fault just restarts them.
+
+/// Read a decimal string length operand.
+pub(super) fn read_decimal_len
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u16, Error>
+{
+    let len = parse_read_operand::<B,u16>(cpu)?.read(cpu)?;
+    if len > MAX_DECIMAL_DIGITS {
+        return Err(Error::new_reserved_operand_fault());
+    }
+    Ok(len)
+}
+
+pub(super) fn read_address
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u32, Error>
+{
+    parse_address_operand::<B,u8>(cpu)
+}
+
+/// Read a length and address operand pair.
+fn read_decimal_operand
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<(u32, u32), Error>
+{
+    let len = read_decimal_len(cpu)?;
+    Ok((len as u32, read_address(cpu)?))
+}
+
+fn read_bytes
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, addr: u32, len: u32)
+    -> Result<Vec<u8>, Error>
+{
+    let mut buf = vec![0; len as usize];
+    cpu.read_block(addr, &mut buf)?;
+    Ok(buf)
+}
+
+fn read_packed
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, len: u32, addr: u32)
+    -> Result<Decimal, Error>
+{
+    let bytes = read_bytes(cpu, addr, packed_size(len as u16))?;
+    Decimal::from_packed(&bytes, len as u16)
+}
+
+/// Write `bytes` out. The whole destination is checked before anything's written.
+fn write_bytes
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, addr: u32, bytes: &[u8])
+    -> Result<(), Error>
+{
+    cpu.write_block(addr, bytes)
+}
+
+/// What `val` turns into when stored in `len` digits, and whether that overflowed.
+fn truncate_decimal(val: &Decimal, len: u32) -> (Decimal, bool) {
+    let (bytes, overflow) = val.to_packed(len as u16);
+    (Decimal::from_packed(&bytes, len as u16).unwrap(), overflow)
+}
+
+fn set_decimal_flags
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, stored: &Decimal, overflow: bool)
+{
+    let mut flags = CVZN::blank();
+    flags.set_n(stored.is_negative());
+    flags.set_z(stored.is_zero());
+    flags.set_v(overflow);
+    cpu.commit_flags(flags);
+}
+
+/// Write a result and set the condition codes from it, returning whether it
+/// overflowed.
+fn write_packed
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, val: &Decimal, len: u32, addr: u32)
+    -> Result<bool, Error>
+{
+    let (bytes, overflow) = val.to_packed(len as u16);
+    write_bytes(cpu, addr, &bytes)?;
+
+    let stored = Decimal::from_packed(&bytes, len as u16)?;
+    set_decimal_flags(cpu, &stored, overflow);
+    Ok(overflow)
+}
+
+/// Trap if the instruction overflowed with PSL<DV> set.
+pub(super) fn check_decimal_overflow
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, overflow: bool)
+    -> Result<(), Error>
+{
+    if overflow && cpu.regfile.get_psl().get_dv() {
+        return Err(Error::new_decimal_overflow_trap());
+    }
+    Ok(())
+}
+
+/// Finish the active instruction, then check for overflow.
+fn finish_decimal
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, overflow: bool)
+    -> Result<(), Error>
+{
+    cpu.multi_instr_active = MultiInstruction::None;
+    check_decimal_overflow(cpu, overflow)
+}
+
+fn start_decimal4
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, instr: MultiInstruction)
+    -> Result<(), Error>
+{
+    let (len1, addr1) = read_decimal_operand(cpu)?;
+    let (len2, addr2) = read_decimal_operand(cpu)?;
+    cpu.regfile.set_r0(len1);
+    cpu.regfile.set_r1(addr1);
+    cpu.regfile.set_r2(len2);
+    cpu.regfile.set_r3(addr2);
+    cpu.multi_instr_active = instr;
+    Ok(())
+}
+
+fn start_decimal6
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, instr: MultiInstruction)
+    -> Result<(), Error>
+{
+    let (len1, addr1) = read_decimal_operand(cpu)?;
+    let (len2, addr2) = read_decimal_operand(cpu)?;
+    let (len3, addr3) = read_decimal_operand(cpu)?;
+    cpu.regfile.set_r0(len1);
+    cpu.regfile.set_r1(addr1);
+    cpu.regfile.set_r2(len2);
+    cpu.regfile.set_r3(addr2);
+    cpu.regfile.set_r4(len3);
+    cpu.regfile.set_r5(addr3);
+    cpu.multi_instr_active = instr;
+    Ok(())
+}
+
+pub fn instr_addp4
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal4(cpu, MultiInstruction::ADDP4 {})
+}
+
+pub fn instr_addp6
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal6(cpu, MultiInstruction::ADDP6 {})
+}
+
+pub fn instr_subp4
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal4(cpu, MultiInstruction::SUBP4 {})
+}
+
+pub fn instr_subp6
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal6(cpu, MultiInstruction::SUBP6 {})
+}
+
+pub fn instr_mulp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal6(cpu, MultiInstruction::MULP {})
+}
+
+pub fn instr_divp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal6(cpu, MultiInstruction::DIVP {})
+}
+
+pub fn instr_cmpp3
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let len = read_decimal_len(cpu)? as u32;
+    let addr1 = read_address(cpu)?;
+    let addr2 = read_address(cpu)?;
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(addr1);
+    cpu.regfile.set_r2(len);
+    cpu.regfile.set_r3(addr2);
+    cpu.multi_instr_active = MultiInstruction::CMPP3 {};
+    Ok(())
+}
+
+pub fn instr_cmpp4
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    start_decimal4(cpu, MultiInstruction::CMPP4 {})
+}
+
+pub fn instr_movp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let len = read_decimal_len(cpu)? as u32;
+    let src = read_address(cpu)?;
+    let dst = read_address(cpu)?;
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(src);
+    cpu.regfile.set_r2(len);
+    cpu.regfile.set_r3(dst);
+    cpu.multi_instr_active = MultiInstruction::MOVP {};
+    Ok(())
+}
+
+pub fn instr_ashp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let cnt = parse_read_operand::<B,u8>(cpu)?.read(cpu)?;
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let round = parse_read_operand::<B,u8>(cpu)?.read(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+    cpu.regfile.set_r0(srclen);
+    cpu.regfile.set_r1(srcaddr);
+    // The rounding digit rides along in R2<19:16> until the instruction's done.
+    cpu.regfile.set_r2(dstlen | ((round as u32 & 0xF) << 16));
+    cpu.regfile.set_r3(dstaddr);
+    cpu.multi_instr_active = MultiInstruction::ASHP { cnt };
+    Ok(())
+}
+
+/// Zero the length registers, leaving the addresses behind.
+fn clear_lengths
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+{
+    cpu.regfile.set_r0(0);
+    cpu.regfile.set_r2(0);
+}
+
+/// ADDP4, SUBP4 and CMPP4: `op` gets the first and second strings.
+fn exec_decimal4
+    <B: VAXBus, F>
+    (cpu: &mut VAXCPU<B>, op: F)
+    -> Result<(), Error>
+    where F: Fn(&Decimal, &Decimal) -> Decimal
+{
+    let (len1, addr1) = (cpu.regfile.get_r0(), cpu.regfile.get_r1());
+    let (len2, addr2) = (cpu.regfile.get_r2(), cpu.regfile.get_r3());
+    let a = read_packed(cpu, len1, addr1)?;
+    let b = read_packed(cpu, len2, addr2)?;
+
+    let overflow = write_packed(cpu, &op(&a, &b), len2, addr2)?;
+    clear_lengths(cpu);
+    finish_decimal(cpu, overflow)
+}
+
+/// ADDP6, SUBP6, MULP and DIVP: `op` gets the first two strings, and may fail
+/// before anything is written.
+fn exec_decimal6
+    <B: VAXBus, F>
+    (cpu: &mut VAXCPU<B>, op: F)
+    -> Result<(), Error>
+    where F: Fn(&Decimal, &Decimal) -> Result<Decimal, Error>
+{
+    let (len1, addr1) = (cpu.regfile.get_r0(), cpu.regfile.get_r1());
+    let (len2, addr2) = (cpu.regfile.get_r2(), cpu.regfile.get_r3());
+    let (len3, addr3) = (cpu.regfile.get_r4(), cpu.regfile.get_r5());
+    let a = read_packed(cpu, len1, addr1)?;
+    let b = read_packed(cpu, len2, addr2)?;
+
+    let res = match op(&a, &b) {
+        Ok(v) => v,
+        Err(e) => {
+            // Division by zero traps, so the instruction is over.
+            cpu.multi_instr_active = MultiInstruction::None;
+            return Err(e);
+        }
+    };
+    let overflow = write_packed(cpu, &res, len3, addr3)?;
+    clear_lengths(cpu);
+    cpu.regfile.set_r4(0);
+    finish_decimal(cpu, overflow)
+}
+
+fn exec_cmpp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<(), Error>
+{
+    let (len1, addr1) = (cpu.regfile.get_r0(), cpu.regfile.get_r1());
+    let (len2, addr2) = (cpu.regfile.get_r2(), cpu.regfile.get_r3());
+    let a = read_packed(cpu, len1, addr1)?;
+    let b = read_packed(cpu, len2, addr2)?;
+
+    let mut flags = CVZN::blank();
+    flags.set_n(a < b);
+    flags.set_z(a == b);
+    cpu.commit_flags(flags);
+    clear_lengths(cpu);
+    finish_decimal(cpu, false)
+}
+
+fn exec_movp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<(), Error>
+{
+    let (len, src) = (cpu.regfile.get_r0(), cpu.regfile.get_r1());
+    let dst = cpu.regfile.get_r3();
+    let val = read_packed(cpu, len, src)?;
+
+    let c = cpu.regfile.get_psl().get_c();
+    write_packed(cpu, &val, len, dst)?;
+    cpu.regfile.get_psl_mut().set_c(c);
+    clear_lengths(cpu);
+    finish_decimal(cpu, false)
+}
+
+fn exec_ashp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, cnt: u8)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = (cpu.regfile.get_r0(), cpu.regfile.get_r1());
+    let r2 = cpu.regfile.get_r2();
+    let (dstlen, round) = (r2 & 0xFFFF, (r2 >> 16) as u8 & 0xF);
+    let dstaddr = cpu.regfile.get_r3();
+    let val = read_packed(cpu, srclen, srcaddr)?;
+
+    let overflow = write_packed(cpu, &val.shift(cnt as i8, round), dstlen, dstaddr)?;
+    clear_lengths(cpu);
+    finish_decimal(cpu, overflow)
+}
+
+/// Run the packed decimal multi-part instruction `instr`. Anything else is a
+/// machine check, as the decimal state was not what the caller claimed.
+pub(super) fn exec_decimal
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, instr: MultiInstruction)
+    -> Result<(), Error>
+{
+    use MultiInstruction::*;
+    match instr {
+        ADDP4 {} => exec_decimal4(cpu, |add, sum| sum.add(add)),
+        SUBP4 {} => exec_decimal4(cpu, |sub, dif| dif.sub(sub)),
+        CMPP3 {} | CMPP4 {} => exec_cmpp(cpu),
+        ADDP6 {} => exec_decimal6(cpu, |add1, add2| Ok(add1.add(add2))),
+        SUBP6 {} => exec_decimal6(cpu, |sub, min| Ok(min.sub(sub))),
+        MULP {} => exec_decimal6(cpu, |mulr, muld| Ok(muld.mul(mulr))),
+        DIVP {} => exec_decimal6(cpu, |divr, divd| {
+            if divr.is_zero() {
+                return Err(Error::new_decimal_div_zero_trap());
+            }
+            Ok(divd.div(divr))
+        }),
+        MOVP {} => exec_movp(cpu),
+        ASHP { cnt } => exec_ashp(cpu, cnt),
+        _ => Err(Error::new_machine_check()),
+    }
+}
+
+/// Set the registers conversions leave behind.
+fn set_conversion_regs
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, src: u32, dst: u32)
+{
+    cpu.regfile.set_r0(0);
+    cpu.regfile.set_r1(src);
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(dst);
+}
+
+pub fn instr_cvtlp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let src = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+
+    let val = Decimal::from_int(src as i32 as i128);
+    let overflow = write_packed(cpu, &val, dstlen, dstaddr)?;
+    set_conversion_regs(cpu, 0, dstaddr);
+    check_decimal_overflow(cpu, overflow)
+}
+
+pub fn instr_cvtpl
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let opw = parse_write_operand::<B,u32>(cpu)?;
+    let val = read_packed(cpu, srclen, srcaddr)?.to_int();
+
+    let overflow = val < i32::MIN as i128 || val > i32::MAX as i128;
+    set_conversion_regs(cpu, srcaddr, 0);
+    opw.write(cpu, val as u32)?;
+
+    let mut flags = CVZN::blank();
+    flags.set_n((val as i32) < 0);
+    flags.set_z(val as i32 == 0);
+    flags.set_v(overflow);
+    cpu.commit_flags(flags);
+    cpu.check_integer_overflow()
+}
+
+pub fn instr_cvtps
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+    let val = read_packed(cpu, srclen, srcaddr)?;
+
+    // Leading separate numeric: a sign character, then the digits.
+    let (stored, overflow) = truncate_decimal(&val, dstlen);
+    let mut bytes = vec![if stored.neg { b'-' } else { b'+' }];
+    bytes.extend(stored.to_digits(dstlen as u16).0.iter().map(|d| b'0' + d));
+    write_bytes(cpu, dstaddr, &bytes)?;
+
+    set_decimal_flags(cpu, &stored, overflow);
+    set_conversion_regs(cpu, srcaddr, dstaddr);
+    check_decimal_overflow(cpu, overflow)
+}
+
+pub fn instr_cvtsp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+    let bytes = read_bytes(cpu, srcaddr, srclen + 1)?;
+
+    let neg = match bytes[0] {
+        b'+' | b' ' => false,
+        b'-' => true,
+        _ => return Err(Error::new_reserved_operand_fault()),
+    };
+    let mut digits = Vec::with_capacity(srclen as usize);
+    for &c in &bytes[1..] {
+        if !c.is_ascii_digit() {
+            return Err(Error::new_reserved_operand_fault());
+        }
+        digits.push(c - b'0');
+    }
+
+    let overflow = write_packed(cpu, &Decimal::from_digits(neg, &digits), dstlen, dstaddr)?;
+    set_conversion_regs(cpu, srcaddr, dstaddr);
+    check_decimal_overflow(cpu, overflow)
+}
+
+pub fn instr_cvtpt
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let tbladdr = read_address(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+    let val = read_packed(cpu, srclen, srcaddr)?;
+
+    // Trailing numeric: the digits, with the last one and the sign turned into a
+    // single character by the table, indexed by the packed byte holding both.
+    let (packed, overflow) = val.to_packed(dstlen as u16);
+    let stored = Decimal::from_packed(&packed, dstlen as u16)?;
+    let mut bytes: Vec<u8> = stored.to_digits(dstlen as u16).0.iter()
+        .map(|d| b'0' + d)
+        .collect();
+    if let Some(last) = bytes.last_mut() {
+        *last = cpu.read_val(tbladdr.wrapping_add(packed[packed.len() - 1] as u32))?;
+    }
+    write_bytes(cpu, dstaddr, &bytes)?;
+
+    set_decimal_flags(cpu, &stored, overflow);
+    set_conversion_regs(cpu, srcaddr, dstaddr);
+    check_decimal_overflow(cpu, overflow)
+}
+
+pub fn instr_cvttp
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (srclen, srcaddr) = read_decimal_operand(cpu)?;
+    let tbladdr = read_address(cpu)?;
+    let (dstlen, dstaddr) = read_decimal_operand(cpu)?;
+    let bytes = read_bytes(cpu, srcaddr, srclen)?;
+
+    let mut digits = Vec::with_capacity(srclen as usize);
+    let mut neg = false;
+    for (i, &c) in bytes.iter().enumerate() {
+        if i + 1 == bytes.len() {
+            // The table gives back the packed byte for the last digit and sign.
+            let t: u8 = cpu.read_val(tbladdr.wrapping_add(c as u32))?;
+            let sign = t & 0xF;
+            if t >> 4 > 9 || sign < 0xA {
+                return Err(Error::new_reserved_operand_fault());
+            }
+            neg = sign == 0xB || sign == 0xD;
+            digits.push(t >> 4);
+        } else if c.is_ascii_digit() {
+            digits.push(c - b'0');
+        } else {
+            return Err(Error::new_reserved_operand_fault());
+        }
+    }
+
+    let overflow = write_packed(cpu, &Decimal::from_digits(neg, &digits), dstlen, dstaddr)?;
+    set_conversion_regs(cpu, srcaddr, dstaddr);
+    check_decimal_overflow(cpu, overflow)
+}
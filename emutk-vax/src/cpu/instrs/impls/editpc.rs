@@ -0,0 +1,309 @@
+use super::decimal::{read_decimal_len, read_address, check_decimal_overflow};
+use super::string::MultiInstruction;
+use crate::cpu::VAXCPU;
+use crate::bus::VAXBus;
+use crate::{Error, CVZN};
+use crate::{MAX_DECIMAL_DIGITS, packed_size};
+use emutk_core::{
+    cycles::Cycles,
+};
+
+// EDITPC formats a packed decimal string into characters by running a pattern of
+// editing operators over it. While it runs, its state lives in the registers:
+//
+//   R0<15:0>  source length     R1  source address
+//   R2<7:0>   fill character    R2<15:8>  sign character
+//   R3        next pattern operator
+//   R4<15:0>  source digits left, R4<31:16> leading zeros to supply first
+//   R5        next destination byte
+//
+// with the condition codes tracking the sign (N), whether only zero digits have
+// been seen (Z), whether nonzero digits were dropped (V) and significance (C).
+// Each operator only updates the registers once it's done, so a fault partway
+// through one restarts it with FPD.
+
+const EO_END: u8 = 0x00;
+const EO_END_FLOAT: u8 = 0x01;
+const EO_CLEAR_SIGNIF: u8 = 0x02;
+const EO_SET_SIGNIF: u8 = 0x03;
+const EO_STORE_SIGN: u8 = 0x04;
+const EO_LOAD_FILL: u8 = 0x40;
+const EO_LOAD_SIGN: u8 = 0x41;
+const EO_LOAD_PLUS: u8 = 0x42;
+const EO_LOAD_MINUS: u8 = 0x43;
+const EO_INSERT: u8 = 0x44;
+const EO_BLANK_ZERO: u8 = 0x45;
+const EO_REPLACE_SIGN: u8 = 0x46;
+const EO_ADJUST_INPUT: u8 = 0x47;
+/// Repeated operators have the repeat count in the low nibble.
+const EO_FILL: u8 = 0x80;
+const EO_MOVE: u8 = 0x90;
+const EO_FLOAT: u8 = 0xA0;
+
+/// How many pattern operators to run before giving interrupts a chance.
+const EDITPC_CHUNK: usize = 16;
+
+pub fn instr_editpc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let srclen = read_decimal_len(cpu)?;
+    let srcaddr = read_address(cpu)?;
+    let pattern = read_address(cpu)?;
+    let dstaddr = read_address(cpu)?;
+
+    let sign: u8 = cpu.read_val(srcaddr.wrapping_add(packed_size(srclen)).wrapping_sub(1))?;
+    let sign = sign & 0xF;
+    if sign < 0xA {
+        return Err(Error::new_reserved_operand_fault());
+    }
+    let neg = sign == 0xB || sign == 0xD;
+
+    cpu.regfile.set_r0(srclen as u32);
+    cpu.regfile.set_r1(srcaddr);
+    cpu.regfile.set_r2(b' ' as u32 | (if neg { b'-' } else { b' ' } as u32) << 8);
+    cpu.regfile.set_r3(pattern);
+    cpu.regfile.set_r4(srclen as u32);
+    cpu.regfile.set_r5(dstaddr);
+    let mut flags = CVZN::blank();
+    flags.set_n(neg);
+    flags.set_z(true);
+    cpu.commit_flags(flags);
+    cpu.multi_instr_active = MultiInstruction::EDITPC {};
+    Ok(())
+}
+
+/// Working copy of the registers for the operator being run.
+struct EditState {
+    srclen: u32,
+    srcaddr: u32,
+    fill: u8,
+    sign: u8,
+    pattern: u32,
+    left: u32,
+    zeros: u32,
+    dst: u32,
+    flags: CVZN,
+}
+
+impl EditState {
+    fn load<B: VAXBus>(cpu: &VAXCPU<B>) -> Self {
+        let r2 = cpu.regfile.get_r2();
+        let r4 = cpu.regfile.get_r4();
+        let psl = cpu.regfile.get_psl();
+        let mut flags = CVZN::blank();
+        flags.set_n(psl.get_n());
+        flags.set_z(psl.get_z());
+        flags.set_v(psl.get_v());
+        flags.set_c(psl.get_c());
+        EditState {
+            srclen: cpu.regfile.get_r0() & 0xFFFF,
+            srcaddr: cpu.regfile.get_r1(),
+            fill: r2 as u8,
+            sign: (r2 >> 8) as u8,
+            pattern: cpu.regfile.get_r3(),
+            left: r4 & 0xFFFF,
+            zeros: r4 >> 16,
+            dst: cpu.regfile.get_r5(),
+            flags,
+        }
+    }
+
+    fn store<B: VAXBus>(&self, cpu: &mut VAXCPU<B>) {
+        cpu.regfile.set_r0(self.srclen);
+        cpu.regfile.set_r1(self.srcaddr);
+        cpu.regfile.set_r2(self.fill as u32 | (self.sign as u32) << 8);
+        cpu.regfile.set_r3(self.pattern);
+        cpu.regfile.set_r4(self.left | self.zeros << 16);
+        cpu.regfile.set_r5(self.dst);
+        cpu.commit_flags(self.flags);
+    }
+
+    fn next_pattern<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<u8, Error> {
+        let v = cpu.read_val(self.pattern)?;
+        self.pattern = self.pattern.wrapping_add(1);
+        Ok(v)
+    }
+
+    fn put<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>, c: u8) -> Result<(), Error> {
+        cpu.write_val(self.dst, c)?;
+        self.dst = self.dst.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Take the next source digit, running out of supplied zeros first.
+    fn next_digit<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<u8, Error> {
+        if self.zeros > 0 {
+            self.zeros -= 1;
+            return Ok(0);
+        }
+        if self.left == 0 {
+            return Err(Error::new_reserved_operand_fault());
+        }
+
+        // Even length strings start with a pad nibble.
+        let nibble = self.srclen - self.left + self.srclen.is_multiple_of(2) as u32;
+        let byte: u8 = cpu.read_val(self.srcaddr.wrapping_add(nibble / 2))?;
+        let d = if nibble.is_multiple_of(2) { byte >> 4 } else { byte & 0xF };
+        if d > 9 {
+            return Err(Error::new_reserved_operand_fault());
+        }
+        self.left -= 1;
+        if d != 0 {
+            self.flags.set_z(false);
+        }
+        Ok(d)
+    }
+
+    /// Store a digit, or the fill character if nothing significant's been seen.
+    fn put_digit<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>, d: u8) -> Result<(), Error> {
+        if self.flags.get_c() {
+            self.put(cpu, b'0' + d)
+        } else {
+            self.put(cpu, self.fill)
+        }
+    }
+
+    /// Length operand for the operators that take one, which can't be zero.
+    fn next_len<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<u32, Error> {
+        let len = self.next_pattern(cpu)?;
+        if len == 0 {
+            return Err(Error::new_reserved_operand_fault());
+        }
+        Ok(len as u32)
+    }
+
+    /// Run one pattern operator, returning true if it was EO$END.
+    fn step<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<bool, Error> {
+        let op = self.next_pattern(cpu)?;
+        let neg = self.flags.get_n();
+        match op {
+            EO_END => {
+                if self.left != 0 || self.zeros != 0 {
+                    return Err(Error::new_reserved_operand_fault());
+                }
+                self.pattern = self.pattern.wrapping_sub(1);
+                return Ok(true);
+            }
+            EO_END_FLOAT => {
+                if !self.flags.get_c() {
+                    self.put(cpu, self.sign)?;
+                    self.flags.set_c(true);
+                }
+            }
+            EO_CLEAR_SIGNIF => self.flags.set_c(false),
+            EO_SET_SIGNIF => self.flags.set_c(true),
+            EO_STORE_SIGN => self.put(cpu, self.sign)?,
+            EO_LOAD_FILL => self.fill = self.next_pattern(cpu)?,
+            EO_LOAD_SIGN => self.sign = self.next_pattern(cpu)?,
+            EO_LOAD_PLUS => {
+                let c = self.next_pattern(cpu)?;
+                if !neg {
+                    self.sign = c;
+                }
+            }
+            EO_LOAD_MINUS => {
+                let c = self.next_pattern(cpu)?;
+                if neg {
+                    self.sign = c;
+                }
+            }
+            EO_INSERT => {
+                let c = self.next_pattern(cpu)?;
+                let c = if self.flags.get_c() { c } else { self.fill };
+                self.put(cpu, c)?;
+            }
+            EO_BLANK_ZERO => {
+                let len = self.next_len(cpu)?;
+                if self.flags.get_z() {
+                    for i in 0..len {
+                        cpu.write_val(self.dst.wrapping_sub(len - i), self.fill)?;
+                    }
+                }
+            }
+            EO_REPLACE_SIGN => {
+                let len = self.next_len(cpu)?;
+                if self.flags.get_z() {
+                    cpu.write_val(self.dst.wrapping_sub(len), self.fill)?;
+                }
+            }
+            EO_ADJUST_INPUT => {
+                let len = self.next_len(cpu)?;
+                if len > MAX_DECIMAL_DIGITS as u32 {
+                    return Err(Error::new_reserved_operand_fault());
+                }
+                if self.left > len {
+                    // Drop the excess high digits, noting any that mattered.
+                    for _ in 0..self.left - len {
+                        if self.next_digit(cpu)? != 0 {
+                            self.flags.set_v(true);
+                        }
+                    }
+                } else {
+                    self.zeros = len - self.left;
+                }
+            }
+            _ => {
+                let count = op & 0xF;
+                if count == 0 {
+                    return Err(Error::new_reserved_operand_fault());
+                }
+                match op & 0xF0 {
+                    EO_FILL => {
+                        for _ in 0..count {
+                            self.put(cpu, self.fill)?;
+                        }
+                    }
+                    EO_MOVE => {
+                        for _ in 0..count {
+                            let d = self.next_digit(cpu)?;
+                            if d != 0 {
+                                self.flags.set_c(true);
+                            }
+                            self.put_digit(cpu, d)?;
+                        }
+                    }
+                    EO_FLOAT => {
+                        for _ in 0..count {
+                            let d = self.next_digit(cpu)?;
+                            if d != 0 && !self.flags.get_c() {
+                                self.put(cpu, self.sign)?;
+                                self.flags.set_c(true);
+                            }
+                            self.put_digit(cpu, d)?;
+                        }
+                    }
+                    _ => return Err(Error::new_reserved_operand_fault()),
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+pub(super) fn exec_editpc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<(), Error>
+{
+    for _ in 0..EDITPC_CHUNK {
+        let mut state = EditState::load(cpu);
+        let done = state.step(cpu)?;
+        if done {
+            // A negative zero doesn't count as negative.
+            if state.flags.get_z() {
+                state.flags.set_n(false);
+            }
+            state.fill = 0;
+            state.sign = 0;
+            state.left = 0;
+            state.store(cpu);
+            cpu.regfile.set_r4(0);
+            cpu.multi_instr_active = MultiInstruction::None;
+            return check_decimal_overflow(cpu, state.flags.get_v());
+        }
+        state.store(cpu);
+    }
+    Ok(())
+}
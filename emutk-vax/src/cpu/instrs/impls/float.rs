@@ -0,0 +1,422 @@
+use super::util::*;
+use crate::cpu::VAXCPU;
+use crate::bus::VAXBus;
+use crate::{Error, CVZN, VAXNum};
+use crate::{VAXFloat, UnpackedFloat, FFloat, HFloat};
+use emutk_core::{
+    cycles::Cycles,
+    ByteRepr,
+};
+
+/// Read a floating point operand. Short literals are expanded into the float
+/// format instead of being zero extended like integer literals.
+pub fn read_float_operand
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<F, Error>
+{
+    let pc = cpu.regfile.get_pc();
+    let head: u8 = cpu.read_val(pc)?;
+    if head & 0xC0 == 0 {
+        cpu.regfile.set_pc(pc + 1);
+        return Ok(F::from_short_literal(head));
+    }
+    let raw = parse_read_operand::<B, F::Raw>(cpu)?.read(cpu)?;
+    Ok(F::from_raw(raw))
+}
+
+fn read_unpacked
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<UnpackedFloat, Error>
+{
+    read_float_operand::<B, F>(cpu)?.unpack()
+}
+
+/// Round a result into `F`, faulting on underflow if PSL<FU> is set.
+fn pack_result
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, v: UnpackedFloat)
+    -> Result<F, Error>
+{
+    F::pack(v, true, cpu.regfile.get_psl().get_fu())
+}
+
+fn float_flags(v: UnpackedFloat) -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_z(v.is_zero());
+    flags.set_n(v.sign && !v.is_zero());
+    flags
+}
+
+/// Two operand arithmetic, `op` gets the first operand and the one it modifies.
+fn float_op2
+    <B: VAXBus, F: VAXFloat, O>
+    (cpu: &mut VAXCPU<B>, op: O)
+    -> Result<(), Error>
+    where O: Fn(UnpackedFloat, UnpackedFloat) -> Result<UnpackedFloat, Error>
+{
+    let a = read_unpacked::<B, F>(cpu)?;
+    let mut opm = parse_modify_operand::<B, F::Raw>(cpu)?;
+    opm.resolve(cpu)?;
+    let b = F::from_raw(opm.read(cpu)?).unpack()?;
+
+    let v = op(a, b)?;
+    let res = pack_result::<B, F>(cpu, v)?;
+    opm.write(cpu, res.to_raw())?;
+    cpu.commit_flags(float_flags(res.unpack()?));
+    Ok(())
+}
+
+/// Three operand arithmetic, `op` gets the two source operands in order.
+fn float_op3
+    <B: VAXBus, F: VAXFloat, O>
+    (cpu: &mut VAXCPU<B>, op: O)
+    -> Result<(), Error>
+    where O: Fn(UnpackedFloat, UnpackedFloat) -> Result<UnpackedFloat, Error>
+{
+    let a = read_unpacked::<B, F>(cpu)?;
+    let b = read_unpacked::<B, F>(cpu)?;
+    let opw = parse_write_operand::<B, F::Raw>(cpu)?;
+
+    let v = op(a, b)?;
+    let res = pack_result::<B, F>(cpu, v)?;
+    opw.write(cpu, res.to_raw())?;
+    cpu.commit_flags(float_flags(res.unpack()?));
+    Ok(())
+}
+
+fn checked_div(divr: UnpackedFloat, divd: UnpackedFloat) -> Result<UnpackedFloat, Error> {
+    if divr.is_zero() {
+        return Err(Error::new_floating_div_zero_fault());
+    }
+    Ok(divd / divr)
+}
+
+pub fn instr_add2
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op2::<B, F, _>(cpu, |add, sum| Ok(sum + add))
+}
+
+pub fn instr_add3
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op3::<B, F, _>(cpu, |add1, add2| Ok(add1 + add2))
+}
+
+pub fn instr_sub2
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op2::<B, F, _>(cpu, |sub, dif| Ok(dif - sub))
+}
+
+pub fn instr_sub3
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op3::<B, F, _>(cpu, |sub, min| Ok(min - sub))
+}
+
+pub fn instr_mul2
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op2::<B, F, _>(cpu, |mulr, prod| Ok(prod * mulr))
+}
+
+pub fn instr_mul3
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op3::<B, F, _>(cpu, |mulr, muld| Ok(muld * mulr))
+}
+
+pub fn instr_div2
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op2::<B, F, _>(cpu, checked_div)
+}
+
+pub fn instr_div3
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_op3::<B, F, _>(cpu, checked_div)
+}
+
+pub fn instr_mov
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let v = read_unpacked::<B, F>(cpu)?;
+    let opw = parse_write_operand::<B, F::Raw>(cpu)?;
+    // Exact, so this only cleans up dirty zeroes.
+    opw.write(cpu, F::pack(v, false, false)?.to_raw())?;
+
+    let mut flags = float_flags(v);
+    flags.set_c(cpu.regfile.get_psl().get_c());
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_mneg
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let v = -read_unpacked::<B, F>(cpu)?;
+    let opw = parse_write_operand::<B, F::Raw>(cpu)?;
+    opw.write(cpu, F::pack(v, false, false)?.to_raw())?;
+    cpu.commit_flags(float_flags(v));
+    Ok(())
+}
+
+pub fn instr_cmp
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let a = read_unpacked::<B, F>(cpu)?;
+    let b = read_unpacked::<B, F>(cpu)?;
+    let mut flags = CVZN::blank();
+    flags.set_n(a < b);
+    flags.set_z(a == b);
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+pub fn instr_tst
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let v = read_unpacked::<B, F>(cpu)?;
+    cpu.commit_flags(float_flags(v));
+    Ok(())
+}
+
+pub fn instr_acb
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let limit = read_unpacked::<B, F>(cpu)?;
+    let add = read_unpacked::<B, F>(cpu)?;
+    let mut opm = parse_modify_operand::<B, F::Raw>(cpu)?;
+    opm.resolve(cpu)?;
+    let index = F::from_raw(opm.read(cpu)?).unpack()?;
+    let displ = read_data::<u16, B>(cpu)?;
+
+    let res = pack_result::<B, F>(cpu, index + add)?;
+    opm.write(cpu, res.to_raw())?;
+    let index = res.unpack()?;
+
+    let mut flags = float_flags(index);
+    flags.set_c(cpu.regfile.get_psl().get_c());
+    cpu.commit_flags(flags);
+
+    let taken = if add.sign { index >= limit } else { index <= limit };
+    if taken {
+        jump_with_word_displacement(cpu, displ);
+    }
+    Ok(())
+}
+
+fn float_to_int
+    <B: VAXBus, F: VAXFloat, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, round: bool)
+    -> Result<(), Error>
+{
+    let v = read_unpacked::<B, F>(cpu)?;
+    let opw = parse_write_operand::<B, T>(cpu)?;
+
+    let (i, overflow) = v.to_int(round);
+    let bits = T::BYTE_LEN as u32 * 8;
+    let min = -(1_i128 << (bits - 1));
+    let max = (1_i128 << (bits - 1)) - 1;
+    let overflow = overflow || i < min || i > max;
+    let res = T::primitive_from(i as u32);
+    opw.write(cpu, res)?;
+
+    let res = int_value(res);
+    let mut flags = CVZN::blank();
+    flags.set_n(res < 0);
+    flags.set_z(res == 0);
+    flags.set_v(overflow);
+    cpu.commit_flags(flags);
+    cpu.check_integer_overflow()
+}
+
+/// Convert to an integer, truncating.
+pub fn instr_cvt_to_int
+    <B: VAXBus, F: VAXFloat, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_to_int::<B, F, T>(cpu, false)
+}
+
+/// Convert to an integer, rounding. Used by CVTRxL.
+pub fn instr_cvt_round_to_int
+    <B: VAXBus, F: VAXFloat, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    float_to_int::<B, F, T>(cpu, true)
+}
+
+pub fn instr_cvt_from_int
+    <B: VAXBus, T: VAXNum, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let i = parse_read_operand::<B, T>(cpu)?.read(cpu)?;
+    let opw = parse_write_operand::<B, F::Raw>(cpu)?;
+    let v = UnpackedFloat::from_int(int_value(i));
+    let res = pack_result::<B, F>(cpu, v)?;
+    opw.write(cpu, res.to_raw())?;
+    cpu.commit_flags(float_flags(v));
+    Ok(())
+}
+
+/// Convert between float formats, rounding if the destination is narrower.
+pub fn instr_cvt_float
+    <B: VAXBus, S: VAXFloat, D: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let v = read_unpacked::<B, S>(cpu)?;
+    let opw = parse_write_operand::<B, D::Raw>(cpu)?;
+    let res = pack_result::<B, D>(cpu, v)?;
+    opw.write(cpu, res.to_raw())?;
+    cpu.commit_flags(float_flags(res.unpack()?));
+    Ok(())
+}
+
+/// Fraction bits kept by EMOD and POLY: the datatype's fraction extended to
+/// fill its size, so eight more for F and D, eleven for G and fifteen for H.
+fn extended_bits<F: VAXFloat>() -> u32 {
+    F::Raw::BYTE_LEN as u32 * 8
+}
+
+pub fn instr_emod
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mut mulr = read_unpacked::<B, F>(cpu)?;
+    // F and D take a byte of extension, G and H the high bits of a word.
+    let ext_bits = extended_bits::<F>() - F::FORMAT.frac_bits() - 1;
+    let mulrx = if ext_bits > 8 {
+        (parse_read_operand::<B, u16>(cpu)?.read(cpu)? >> (16 - ext_bits)) as u128
+    } else {
+        parse_read_operand::<B, u8>(cpu)?.read(cpu)? as u128
+    };
+    let muld = read_unpacked::<B, F>(cpu)?;
+    let opw_int = parse_write_operand::<B, u32>(cpu)?;
+    let opw_fract = parse_write_operand::<B, F::Raw>(cpu)?;
+
+    // The extension supplies eight more fraction bits below the multiplier's.
+    if !mulr.is_zero() {
+        mulr.frac |= mulrx << (128 - extended_bits::<F>());
+    }
+    let prod = (mulr * muld).truncated(extended_bits::<F>());
+    let (int, fract) = prod.split_int();
+    let (int, overflow) = int.to_int(false);
+    let overflow = overflow || int < i32::MIN as i128 || int > i32::MAX as i128;
+    let fract = pack_result::<B, F>(cpu, fract)?;
+
+    opw_int.write(cpu, int as u32)?;
+    opw_fract.write(cpu, fract.to_raw())?;
+    let mut flags = float_flags(fract.unpack()?);
+    flags.set_v(overflow);
+    cpu.commit_flags(flags);
+    cpu.check_integer_overflow()
+}
+
+/// Highest degree POLY accepts.
+const POLY_MAX_DEGREE: u16 = 31;
+
+/// Evaluate a polynomial with Horner's method. The coefficient table starts with
+/// the highest order one. Returns the result and the address past the table.
+fn poly
+    <B: VAXBus, F: VAXFloat>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<(F, u32), Error>
+{
+    let arg = read_unpacked::<B, F>(cpu)?;
+    let degree = parse_read_operand::<B, u16>(cpu)?.read(cpu)?;
+    let mut tbl = parse_address_operand::<B, u8>(cpu)?;
+    if degree > POLY_MAX_DEGREE {
+        return Err(Error::new_reserved_operand_fault());
+    }
+
+    let mut res = F::from_raw(cpu.read_val(tbl)?);
+    res.unpack()?;
+    tbl = tbl.wrapping_add(F::Raw::BYTE_LEN as u32);
+    for _ in 0..degree {
+        let coef = F::from_raw(cpu.read_val(tbl)?).unpack()?;
+        tbl = tbl.wrapping_add(F::Raw::BYTE_LEN as u32);
+        // Each step is done to the extended precision, then rounded.
+        let prod = (res.unpack()? * arg).truncated(extended_bits::<F>());
+        let sum = (prod + coef).truncated(extended_bits::<F>());
+        res = pack_result::<B, F>(cpu, sum)?;
+    }
+
+    cpu.commit_flags(float_flags(res.unpack()?));
+    Ok((res, tbl))
+}
+
+pub fn instr_polyf
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (res, tbl) = poly::<B, FFloat>(cpu)?;
+    cpu.regfile.set_r0(res.to_raw());
+    cpu.regfile.set_r1(0);
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(tbl);
+    Ok(())
+}
+
+/// POLYD and POLYG, which leave their result in R0 and R1.
+pub fn instr_polyq
+    <B: VAXBus, F: VAXFloat<Raw = u64>>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (res, tbl) = poly::<B, F>(cpu)?;
+    cpu.regfile.write_gpr_ext(0, res.to_raw());
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(tbl);
+    cpu.regfile.set_r4(0);
+    cpu.regfile.set_r5(0);
+    Ok(())
+}
+
+pub fn instr_polyh
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let (res, tbl) = poly::<B, HFloat>(cpu)?;
+    cpu.regfile.write_gpr_ext(0, res.to_raw());
+    cpu.regfile.set_r4(0);
+    cpu.regfile.set_r5(tbl);
+    Ok(())
+}
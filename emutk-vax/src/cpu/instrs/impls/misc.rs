@@ -118,10 +118,9 @@ pub fn instr_mova
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let mut opr_a = parse_read_operand::<B,T>(cpu)?;
-    opr_a.validate(cpu)?;
+    let addr = parse_address_operand::<B,T>(cpu)?;
     let opw_o = parse_write_operand::<B,u32>(cpu)?;
-    opw_o.write(cpu, opr_a.address()?)?;
+    opw_o.write(cpu, addr)?;
     Ok(())
 }
 
@@ -130,9 +129,8 @@ pub fn instr_pusha
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let mut opr_a = parse_read_operand::<B,T>(cpu)?;
-    opr_a.validate(cpu)?;
-    push(cpu, opr_a.address()?)?;
+    let addr = parse_address_operand::<B,T>(cpu)?;
+    push(cpu, addr)?;
     Ok(())
 }
 
@@ -143,7 +141,7 @@ pub fn instr_mtpr
     -> Result<(), Error>
 {
     rr_instr_wrap(cpu, |x: u32, y: u32, cpu: &mut VAXCPU<B>| -> Result<(), Error> {
-        cpu.regfile.write_msr(y as u16, x)
+        cpu.write_msr(y as u16, x)
     })
 }
 
@@ -153,7 +151,7 @@ pub fn instr_mfpr
     -> Result<(), Error>
 {
     rw_instr_wrap::<B, u32, u32, _>(cpu, |r: u32, cpu: &mut VAXCPU<B>| -> Result<u32, Error> {
-        cpu.regfile.read_msr(r as u16)
+        cpu.read_msr(r as u16)
     })
 }
 
@@ -5,6 +5,11 @@ mod util;
 mod bitfield;
 mod convert;
 mod string;
+mod context;
+mod float;
+mod decimal;
+mod editpc;
+mod queue;
 #[cfg(test)]
 mod tests;
 
@@ -13,12 +18,14 @@ use crate::cpu::instrs::InstructionType;
 use tablegen_proc::gen_instr_table;
 use crate::bus::VAXBus;
 use crate::Error;
+use crate::{FFloat, DFloat, GFloat, HFloat};
 use control::BranchCondition;
 use emutk_core::{
     cycles::Cycles,
 };
 pub use string::MultiInstruction;
 pub use string::exec_multi_instructions;
+pub use string::{suspend_multi_instruction, resume_multi_instruction};
 /*
 // Instruction form:
 fn instr_name(&mut VAXCPU, &mut Cycles)
@@ -33,20 +40,20 @@ impl<T: VAXBus> VAXCPU<'_, T> {
             VAX_INSTR_MAP_TABLE; 1280; Option<fn(&mut VAXCPU<'_, T> , &mut Cycles) -> Result<(), Error>>; None => {
                 0x00 => HALT, Some(misc::instr_halt);
                 0x01 => NOP, Some(misc::instr_nop);
-                0x02 => REI, Some(misc::instr_noimpl);
+                0x02 => REI, Some(context::instr_rei);
                 0x03 => BPT, Some(misc::instr_bpt);
-                0x04 => RET, Some(misc::instr_noimpl);
+                0x04 => RET, Some(control::instr_ret);
                 0x05 => RSB, Some(control::instr_rsb);
-                0x06 => LDPCTX, Some(misc::instr_noimpl);
-                0x07 => SVPCTX, Some(misc::instr_noimpl);
-                0x08 => CVTPS, Some(misc::instr_noimpl);
-                0x09 => CVTSP, Some(misc::instr_noimpl);
-                0x0A => INDEX, Some(misc::instr_noimpl);
-                0x0B => CRC, Some(misc::instr_noimpl);
-                0x0C => PROBER, Some(misc::instr_noimpl);
-                0x0D => PROBEW, Some(misc::instr_noimpl);
-                0x0E => INSQUE, Some(misc::instr_noimpl);
-                0x0F => REMQUE, Some(misc::instr_noimpl);
+                0x06 => LDPCTX, Some(context::instr_ldpctx);
+                0x07 => SVPCTX, Some(context::instr_svpctx);
+                0x08 => CVTPS, Some(decimal::instr_cvtps);
+                0x09 => CVTSP, Some(decimal::instr_cvtsp);
+                0x0A => INDEX, Some(arith::instr_index);
+                0x0B => CRC, Some(string::instr_crc);
+                0x0C => PROBER, Some(context::instr_prober);
+                0x0D => PROBEW, Some(context::instr_probew);
+                0x0E => INSQUE, Some(queue::instr_insque);
+                0x0F => REMQUE, Some(queue::instr_remque);
                 0x10 => BSBB, Some(control::instr_branch_subroutine_byte);
                 0x11 => BRB, Some(control::instr_branch_byte);
                 0x12 => BNEQ, Some(control::instr_branch_cond_zus);
@@ -63,93 +70,93 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0x1D => BVS, Some(control::instr_branch_cond_vs);
                 0x1E => BGEQU, Some(control::instr_branch_cond_cus);
                 0x1F => BLSSU, Some(control::instr_branch_cond_cs);
-                0x20 => ADDP4, Some(misc::instr_noimpl);
-                0x21 => ADDP6, Some(misc::instr_noimpl);
-                0x22 => SUBP4, Some(misc::instr_noimpl);
-                0x23 => SUBP6, Some(misc::instr_noimpl);
-                0x24 => CVTPT, Some(misc::instr_noimpl);
-                0x25 => MULP, Some(misc::instr_noimpl);
-                0x26 => CVTTP, Some(misc::instr_noimpl);
-                0x27 => DIVP, Some(misc::instr_noimpl);
+                0x20 => ADDP4, Some(decimal::instr_addp4);
+                0x21 => ADDP6, Some(decimal::instr_addp6);
+                0x22 => SUBP4, Some(decimal::instr_subp4);
+                0x23 => SUBP6, Some(decimal::instr_subp6);
+                0x24 => CVTPT, Some(decimal::instr_cvtpt);
+                0x25 => MULP, Some(decimal::instr_mulp);
+                0x26 => CVTTP, Some(decimal::instr_cvttp);
+                0x27 => DIVP, Some(decimal::instr_divp);
                 0x28 => MOVC3, Some(string::instr_movc3);
-                0x29 => CMPC3, Some(misc::instr_noimpl);
-                0x2A => SCANC, Some(misc::instr_noimpl);
-                0x2B => SPANC, Some(misc::instr_noimpl);
-                0x2C => MOVC5, Some(misc::instr_noimpl);
-                0x2D => CMPC5, Some(misc::instr_noimpl);
-                0x2E => MOVTC, Some(misc::instr_noimpl);
-                0x2F => MOVTUC, Some(misc::instr_noimpl);
+                0x29 => CMPC3, Some(string::instr_cmpc3);
+                0x2A => SCANC, Some(string::instr_scanc);
+                0x2B => SPANC, Some(string::instr_spanc);
+                0x2C => MOVC5, Some(string::instr_movc5);
+                0x2D => CMPC5, Some(string::instr_cmpc5);
+                0x2E => MOVTC, Some(string::instr_movtc);
+                0x2F => MOVTUC, Some(string::instr_movtuc);
                 0x30 => BSBW, Some(control::instr_branch_subroutine_word);
                 0x31 => BRW, Some(control::instr_branch_word);
                 0x32 => CVTWL, Some(convert::instr_cvtwl);
                 0x33 => CVTWB, Some(convert::instr_cvtwb);
-                0x34 => MOVP, Some(misc::instr_noimpl);
-                0x35 => CMPP3, Some(misc::instr_noimpl);
-                0x36 => CVTPL, Some(misc::instr_noimpl);
-                0x37 => CMPP4, Some(misc::instr_noimpl);
-                0x38 => EDITPC, Some(misc::instr_noimpl);
-                0x39 => MATCHC, Some(misc::instr_noimpl);
-                0x3A => LOCC, Some(misc::instr_noimpl);
-                0x3B => SKPC, Some(misc::instr_noimpl);
+                0x34 => MOVP, Some(decimal::instr_movp);
+                0x35 => CMPP3, Some(decimal::instr_cmpp3);
+                0x36 => CVTPL, Some(decimal::instr_cvtpl);
+                0x37 => CMPP4, Some(decimal::instr_cmpp4);
+                0x38 => EDITPC, Some(editpc::instr_editpc);
+                0x39 => MATCHC, Some(string::instr_matchc);
+                0x3A => LOCC, Some(string::instr_locc);
+                0x3B => SKPC, Some(string::instr_skpc);
                 0x3C => MOVZWL, Some(convert::instr_movzwl);
-                0x3D => ACBW, Some(misc::instr_noimpl);
+                0x3D => ACBW, Some(control::instr_acb::<_, u16>);
                 0x3E => MOVAW, Some(misc::instr_mova::<_, u16>);
                 0x3F => PUSHAW, Some(misc::instr_pusha::<_, u16>);
-                0x40 => ADDF2, Some(misc::instr_noimpl);
-                0x41 => ADDF3, Some(misc::instr_noimpl);
-                0x42 => SUBF2, Some(misc::instr_noimpl);
-                0x43 => SUBF3, Some(misc::instr_noimpl);
-                0x44 => MULF2, Some(misc::instr_noimpl);
-                0x45 => MULF3, Some(misc::instr_noimpl);
-                0x46 => DIVF2, Some(misc::instr_noimpl);
-                0x47 => DIVF3, Some(misc::instr_noimpl);
-                0x48 => CVTFB, Some(misc::instr_noimpl);
-                0x49 => CVTFW, Some(misc::instr_noimpl);
-                0x4A => CVTFL, Some(misc::instr_noimpl);
-                0x4B => CVTRFL, Some(misc::instr_noimpl);
-                0x4C => CVTBF, Some(misc::instr_noimpl);
-                0x4D => CVTWF, Some(misc::instr_noimpl);
-                0x4E => CVTLF, Some(misc::instr_noimpl);
-                0x4F => ACBF, Some(misc::instr_noimpl);
-                0x50 => MOVF, Some(misc::instr_noimpl);
-                0x51 => CMPF, Some(misc::instr_noimpl);
-                0x52 => MNEGF, Some(misc::instr_noimpl);
-                0x53 => TSTF, Some(misc::instr_noimpl);
-                0x54 => EMODF, Some(misc::instr_noimpl);
-                0x55 => POLYF, Some(misc::instr_noimpl);
-                0x56 => CVTFD, Some(misc::instr_noimpl);
+                0x40 => ADDF2, Some(float::instr_add2::<_, FFloat>);
+                0x41 => ADDF3, Some(float::instr_add3::<_, FFloat>);
+                0x42 => SUBF2, Some(float::instr_sub2::<_, FFloat>);
+                0x43 => SUBF3, Some(float::instr_sub3::<_, FFloat>);
+                0x44 => MULF2, Some(float::instr_mul2::<_, FFloat>);
+                0x45 => MULF3, Some(float::instr_mul3::<_, FFloat>);
+                0x46 => DIVF2, Some(float::instr_div2::<_, FFloat>);
+                0x47 => DIVF3, Some(float::instr_div3::<_, FFloat>);
+                0x48 => CVTFB, Some(float::instr_cvt_to_int::<_, FFloat, u8>);
+                0x49 => CVTFW, Some(float::instr_cvt_to_int::<_, FFloat, u16>);
+                0x4A => CVTFL, Some(float::instr_cvt_to_int::<_, FFloat, u32>);
+                0x4B => CVTRFL, Some(float::instr_cvt_round_to_int::<_, FFloat, u32>);
+                0x4C => CVTBF, Some(float::instr_cvt_from_int::<_, u8, FFloat>);
+                0x4D => CVTWF, Some(float::instr_cvt_from_int::<_, u16, FFloat>);
+                0x4E => CVTLF, Some(float::instr_cvt_from_int::<_, u32, FFloat>);
+                0x4F => ACBF, Some(float::instr_acb::<_, FFloat>);
+                0x50 => MOVF, Some(float::instr_mov::<_, FFloat>);
+                0x51 => CMPF, Some(float::instr_cmp::<_, FFloat>);
+                0x52 => MNEGF, Some(float::instr_mneg::<_, FFloat>);
+                0x53 => TSTF, Some(float::instr_tst::<_, FFloat>);
+                0x54 => EMODF, Some(float::instr_emod::<_, FFloat>);
+                0x55 => POLYF, Some(float::instr_polyf);
+                0x56 => CVTFD, Some(float::instr_cvt_float::<_, FFloat, DFloat>);
                 //0x57
                 0x58 => ADAWI, Some(arith::instr_add2::<_, u32>);
                 //0x59
                 //0x5A
                 //0x5B
-                0x5C => INSQHI, Some(misc::instr_noimpl);
-                0x5D => INSQTI, Some(misc::instr_noimpl);
-                0x5E => REMQHI, Some(misc::instr_noimpl);
-                0x5F => REMQTI, Some(misc::instr_noimpl);
-                0x60 => ADDD2, Some(misc::instr_noimpl);
-                0x61 => ADDD3, Some(misc::instr_noimpl);
-                0x62 => SUBD2, Some(misc::instr_noimpl);
-                0x63 => SUBD3, Some(misc::instr_noimpl);
-                0x64 => MULD2, Some(misc::instr_noimpl);
-                0x65 => MULD3, Some(misc::instr_noimpl);
-                0x66 => DIVD2, Some(misc::instr_noimpl);
-                0x67 => DIVD3, Some(misc::instr_noimpl);
-                0x68 => CVTDB, Some(misc::instr_noimpl);
-                0x69 => CVTDW, Some(misc::instr_noimpl);
-                0x6A => CVTDL, Some(misc::instr_noimpl);
-                0x6B => CVTRDL, Some(misc::instr_noimpl);
-                0x6C => CVTBD, Some(misc::instr_noimpl);
-                0x6D => CVTWD, Some(misc::instr_noimpl);
-                0x6E => CVTLD, Some(misc::instr_noimpl);
-                0x6F => ACBD, Some(misc::instr_noimpl);
-                0x70 => MOVD, Some(misc::instr_noimpl);
-                0x71 => CMPD, Some(misc::instr_noimpl);
-                0x72 => MNEGD, Some(misc::instr_noimpl);
-                0x73 => TSTD, Some(misc::instr_noimpl);
-                0x74 => EMODD, Some(misc::instr_noimpl);
-                0x75 => POLYD, Some(misc::instr_noimpl);
-                0x76 => CVTDF, Some(misc::instr_noimpl);
+                0x5C => INSQHI, Some(queue::instr_insqhi);
+                0x5D => INSQTI, Some(queue::instr_insqti);
+                0x5E => REMQHI, Some(queue::instr_remqhi);
+                0x5F => REMQTI, Some(queue::instr_remqti);
+                0x60 => ADDD2, Some(float::instr_add2::<_, DFloat>);
+                0x61 => ADDD3, Some(float::instr_add3::<_, DFloat>);
+                0x62 => SUBD2, Some(float::instr_sub2::<_, DFloat>);
+                0x63 => SUBD3, Some(float::instr_sub3::<_, DFloat>);
+                0x64 => MULD2, Some(float::instr_mul2::<_, DFloat>);
+                0x65 => MULD3, Some(float::instr_mul3::<_, DFloat>);
+                0x66 => DIVD2, Some(float::instr_div2::<_, DFloat>);
+                0x67 => DIVD3, Some(float::instr_div3::<_, DFloat>);
+                0x68 => CVTDB, Some(float::instr_cvt_to_int::<_, DFloat, u8>);
+                0x69 => CVTDW, Some(float::instr_cvt_to_int::<_, DFloat, u16>);
+                0x6A => CVTDL, Some(float::instr_cvt_to_int::<_, DFloat, u32>);
+                0x6B => CVTRDL, Some(float::instr_cvt_round_to_int::<_, DFloat, u32>);
+                0x6C => CVTBD, Some(float::instr_cvt_from_int::<_, u8, DFloat>);
+                0x6D => CVTWD, Some(float::instr_cvt_from_int::<_, u16, DFloat>);
+                0x6E => CVTLD, Some(float::instr_cvt_from_int::<_, u32, DFloat>);
+                0x6F => ACBD, Some(float::instr_acb::<_, DFloat>);
+                0x70 => MOVD, Some(float::instr_mov::<_, DFloat>);
+                0x71 => CMPD, Some(float::instr_cmp::<_, DFloat>);
+                0x72 => MNEGD, Some(float::instr_mneg::<_, DFloat>);
+                0x73 => TSTD, Some(float::instr_tst::<_, DFloat>);
+                0x74 => EMODD, Some(float::instr_emod::<_, DFloat>);
+                0x75 => POLYD, Some(float::instr_polyq::<_, DFloat>);
+                0x76 => CVTDF, Some(float::instr_cvt_float::<_, DFloat, FFloat>);
                 // 0x77
                 0x78 => ASHL, Some(arith::instr_ash::<_, u32>);
                 0x79 => ASHQ, Some(arith::instr_ash::<_, u64>);
@@ -187,8 +194,8 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0x99 => CVTBW, Some(convert::instr_cvtbw);
                 0x9A => MOVZBL, Some(convert::instr_movzbl);
                 0x9B => MOVZBW, Some(convert::instr_movzbw);
-                0x9C => ROTL, Some(misc::instr_noimpl);
-                0x9D => ACBB, Some(misc::instr_noimpl);
+                0x9C => ROTL, Some(arith::instr_rotl);
+                0x9D => ACBB, Some(control::instr_acb::<_, u8>);
                 0x9E => MOVAB, Some(misc::instr_mova::<_, u8>);
                 0x9F => PUSHAB, Some(misc::instr_pusha::<_, u8>);
                 0xA0 => ADDW2, Some(arith::instr_add2::<_, u16>);
@@ -219,10 +226,10 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0xB9 => BICPSW, Some(misc::instr_bicpsw);
                 0xBA => POPR, Some(misc::instr_popr);
                 0xBB => PUSHR, Some(misc::instr_pushr);
-                0xBC => CHMK, Some(misc::instr_noimpl);
-                0xBD => CHME, Some(misc::instr_noimpl);
-                0xBE => CHMS, Some(misc::instr_noimpl);
-                0xBF => CHMU, Some(misc::instr_noimpl);
+                0xBC => CHMK, Some(context::instr_chmk);
+                0xBD => CHME, Some(context::instr_chme);
+                0xBE => CHMS, Some(context::instr_chms);
+                0xBF => CHMU, Some(context::instr_chmu);
                 0xC0 => ADDL2, Some(arith::instr_add2::<_, u32>);
                 0xC1 => ADDL3, Some(arith::instr_add3::<_, u32>);
                 0xC2 => SUBL2, Some(arith::instr_sub2::<_, u32>);
@@ -248,41 +255,41 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0xD6 => INCL, Some(arith::instr_inc::<_, u32>);
                 0xD7 => DECL, Some(arith::instr_dec::<_, u32>);
                 0xD8 => ADWC, Some(arith::instr_adwc::<_, u16>);
-                0xD9 => SBWC, Some(misc::instr_noimpl);
+                0xD9 => SBWC, Some(arith::instr_sbwc::<_, u32>);
                 0xDA => MTPR, Some(misc::instr_mtpr);
                 0xDB => MFPR, Some(misc::instr_mfpr);
                 0xDC => MOVPSL, Some(misc::instr_movpsl);
                 0xDD => PUSHL, Some(misc::instr_pushl);
                 0xDE => MOVAL, Some(misc::instr_mova::<_, u32>);
                 0xDF => PUSHAL, Some(misc::instr_pusha::<_, u32>);
-                0xE0 => BBS, Some(misc::instr_noimpl);
-                0xE1 => BBC, Some(misc::instr_noimpl);
-                0xE2 => BBSS, Some(misc::instr_noimpl);
-                0xE3 => BBCS, Some(misc::instr_noimpl);
-                0xE4 => BBSC, Some(misc::instr_noimpl);
-                0xE5 => BBCC, Some(misc::instr_noimpl);
-                0xE6 => BBSSI, Some(misc::instr_noimpl);
-                0xE7 => BBCCI, Some(misc::instr_noimpl);
+                0xE0 => BBS, Some(bitfield::instr_bbs);
+                0xE1 => BBC, Some(bitfield::instr_bbc);
+                0xE2 => BBSS, Some(bitfield::instr_bbss);
+                0xE3 => BBCS, Some(bitfield::instr_bbcs);
+                0xE4 => BBSC, Some(bitfield::instr_bbsc);
+                0xE5 => BBCC, Some(bitfield::instr_bbcc);
+                0xE6 => BBSSI, Some(bitfield::instr_bbssi);
+                0xE7 => BBCCI, Some(bitfield::instr_bbcci);
                 0xE8 => BLBS, Some(control::instr_branch_low_bit_true);
                 0xE9 => BLBC, Some(control::instr_branch_low_bit_false);
-                0xEA => FFS, Some(misc::instr_noimpl);
-                0xEB => FFC, Some(misc::instr_noimpl);
-                0xEC => CMPV, Some(misc::instr_noimpl);
-                0xED => CMPZV, Some(misc::instr_noimpl);
-                0xEE => EXTV, Some(misc::instr_noimpl);
-                0xEF => EXTZV, Some(misc::instr_noimpl);
-                0xF0 => INSV, Some(misc::instr_noimpl);
-                0xF1 => ACBL, Some(misc::instr_noimpl);
-                0xF2 => AOBLSS, Some(misc::instr_noimpl);
-                0xF3 => AOBLEQ, Some(misc::instr_noimpl);
-                0xF4 => SOBGEQ, Some(misc::instr_noimpl);
-                0xF5 => SOBGTR, Some(misc::instr_noimpl);
+                0xEA => FFS, Some(bitfield::instr_ffs);
+                0xEB => FFC, Some(bitfield::instr_ffc);
+                0xEC => CMPV, Some(bitfield::instr_cmpv);
+                0xED => CMPZV, Some(bitfield::instr_cmpzv);
+                0xEE => EXTV, Some(bitfield::instr_extv);
+                0xEF => EXTZV, Some(bitfield::instr_extzv);
+                0xF0 => INSV, Some(bitfield::instr_insv);
+                0xF1 => ACBL, Some(control::instr_acb::<_, u32>);
+                0xF2 => AOBLSS, Some(control::instr_aoblss);
+                0xF3 => AOBLEQ, Some(control::instr_aobleq);
+                0xF4 => SOBGEQ, Some(control::instr_sobgeq);
+                0xF5 => SOBGTR, Some(control::instr_sobgtr);
                 0xF6 => CVTLB, Some(convert::instr_cvtlb);
                 0xF7 => CVTLW, Some(convert::instr_cvtlw);
-                0xF8 => ASHP, Some(misc::instr_noimpl);
-                0xF9 => CVTLP, Some(misc::instr_noimpl);
-                0xFA => CALLG, Some(misc::instr_noimpl);
-                0xFB => CALLS, Some(misc::instr_noimpl);
+                0xF8 => ASHP, Some(decimal::instr_ashp);
+                0xF9 => CVTLP, Some(decimal::instr_cvtlp);
+                0xFA => CALLG, Some(control::instr_callg);
+                0xFB => CALLS, Some(control::instr_calls);
                 // 0xFC PREFIX
                 // 0xFD PREFIX
                 // 0xFE PREFIX
@@ -291,60 +298,60 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0x202 => WAIT, Some(misc::instr_noimpl);
 
                 0x231 => MFVP, Some(misc::instr_noimpl);
-                0x232 => CVTDH, Some(misc::instr_noimpl);
-                0x233 => CVTGH, Some(misc::instr_noimpl);
+                0x232 => CVTDH, Some(float::instr_cvt_float::<_, DFloat, HFloat>);
+                0x233 => CVTGF, Some(float::instr_cvt_float::<_, GFloat, FFloat>);
 
                 0x235 => VGATHL, Some(misc::instr_noimpl);
 
                 0x237 => VGATHQ, Some(misc::instr_noimpl);
 
-                0x240 => ADDG2, Some(misc::instr_noimpl);
-                0x241 => ADDG3, Some(misc::instr_noimpl);
-                0x242 => SUBG2, Some(misc::instr_noimpl);
-                0x243 => SUBG3, Some(misc::instr_noimpl);
-                0x244 => MULG2, Some(misc::instr_noimpl);
-                0x245 => MULG3, Some(misc::instr_noimpl);
-                0x246 => DIVG2, Some(misc::instr_noimpl);
-                0x247 => DIVG3, Some(misc::instr_noimpl);
-                0x248 => CVTGB, Some(misc::instr_noimpl);
-                0x249 => CVTGW, Some(misc::instr_noimpl);
-                0x24A => CVTGL, Some(misc::instr_noimpl);
-                0x24B => CVTRGL, Some(misc::instr_noimpl);
-                0x24C => CVTBG, Some(misc::instr_noimpl);
-                0x24D => CVTWG, Some(misc::instr_noimpl);
-                0x24E => CVTLG, Some(misc::instr_noimpl);
-                0x24F => ACBG, Some(misc::instr_noimpl);
-                0x250 => MOVG, Some(misc::instr_noimpl);
-                0x251 => CMPG, Some(misc::instr_noimpl);
-                0x252 => MNEGG, Some(misc::instr_noimpl);
-                0x253 => TSTG, Some(misc::instr_noimpl);
-                0x254 => EMODG, Some(misc::instr_noimpl);
-                0x255 => POLYG, Some(misc::instr_noimpl);
-                0x256 => CVTGH, Some(misc::instr_noimpl);
+                0x240 => ADDG2, Some(float::instr_add2::<_, GFloat>);
+                0x241 => ADDG3, Some(float::instr_add3::<_, GFloat>);
+                0x242 => SUBG2, Some(float::instr_sub2::<_, GFloat>);
+                0x243 => SUBG3, Some(float::instr_sub3::<_, GFloat>);
+                0x244 => MULG2, Some(float::instr_mul2::<_, GFloat>);
+                0x245 => MULG3, Some(float::instr_mul3::<_, GFloat>);
+                0x246 => DIVG2, Some(float::instr_div2::<_, GFloat>);
+                0x247 => DIVG3, Some(float::instr_div3::<_, GFloat>);
+                0x248 => CVTGB, Some(float::instr_cvt_to_int::<_, GFloat, u8>);
+                0x249 => CVTGW, Some(float::instr_cvt_to_int::<_, GFloat, u16>);
+                0x24A => CVTGL, Some(float::instr_cvt_to_int::<_, GFloat, u32>);
+                0x24B => CVTRGL, Some(float::instr_cvt_round_to_int::<_, GFloat, u32>);
+                0x24C => CVTBG, Some(float::instr_cvt_from_int::<_, u8, GFloat>);
+                0x24D => CVTWG, Some(float::instr_cvt_from_int::<_, u16, GFloat>);
+                0x24E => CVTLG, Some(float::instr_cvt_from_int::<_, u32, GFloat>);
+                0x24F => ACBG, Some(float::instr_acb::<_, GFloat>);
+                0x250 => MOVG, Some(float::instr_mov::<_, GFloat>);
+                0x251 => CMPG, Some(float::instr_cmp::<_, GFloat>);
+                0x252 => MNEGG, Some(float::instr_mneg::<_, GFloat>);
+                0x253 => TSTG, Some(float::instr_tst::<_, GFloat>);
+                0x254 => EMODG, Some(float::instr_emod::<_, GFloat>);
+                0x255 => POLYG, Some(float::instr_polyq::<_, GFloat>);
+                0x256 => CVTGH, Some(float::instr_cvt_float::<_, GFloat, HFloat>);
 
-                0x260 => ADDH2, Some(misc::instr_noimpl);
-                0x261 => ADDH3, Some(misc::instr_noimpl);
-                0x262 => SUBH2, Some(misc::instr_noimpl);
-                0x263 => SUBH3, Some(misc::instr_noimpl);
-                0x264 => MULH2, Some(misc::instr_noimpl);
-                0x265 => MULH3, Some(misc::instr_noimpl);
-                0x266 => DIVH2, Some(misc::instr_noimpl);
-                0x267 => DIVH3, Some(misc::instr_noimpl);
-                0x268 => CVTHB, Some(misc::instr_noimpl);
-                0x269 => CVTHW, Some(misc::instr_noimpl);
-                0x26A => CVTHL, Some(misc::instr_noimpl);
-                0x26B => CVTRHL, Some(misc::instr_noimpl);
-                0x26C => CVTBH, Some(misc::instr_noimpl);
-                0x26D => CVTWH, Some(misc::instr_noimpl);
-                0x26E => CVTLH, Some(misc::instr_noimpl);
-                0x26F => ACBH, Some(misc::instr_noimpl);
-                0x270 => MOVH, Some(misc::instr_noimpl);
-                0x271 => CMPH, Some(misc::instr_noimpl);
-                0x272 => MNEGH, Some(misc::instr_noimpl);
-                0x273 => TSTH, Some(misc::instr_noimpl);
-                0x274 => EMODH, Some(misc::instr_noimpl);
-                0x275 => POLYH, Some(misc::instr_noimpl);
-                0x276 => CVTHG, Some(misc::instr_noimpl);
+                0x260 => ADDH2, Some(float::instr_add2::<_, HFloat>);
+                0x261 => ADDH3, Some(float::instr_add3::<_, HFloat>);
+                0x262 => SUBH2, Some(float::instr_sub2::<_, HFloat>);
+                0x263 => SUBH3, Some(float::instr_sub3::<_, HFloat>);
+                0x264 => MULH2, Some(float::instr_mul2::<_, HFloat>);
+                0x265 => MULH3, Some(float::instr_mul3::<_, HFloat>);
+                0x266 => DIVH2, Some(float::instr_div2::<_, HFloat>);
+                0x267 => DIVH3, Some(float::instr_div3::<_, HFloat>);
+                0x268 => CVTHB, Some(float::instr_cvt_to_int::<_, HFloat, u8>);
+                0x269 => CVTHW, Some(float::instr_cvt_to_int::<_, HFloat, u16>);
+                0x26A => CVTHL, Some(float::instr_cvt_to_int::<_, HFloat, u32>);
+                0x26B => CVTRHL, Some(float::instr_cvt_round_to_int::<_, HFloat, u32>);
+                0x26C => CVTBH, Some(float::instr_cvt_from_int::<_, u8, HFloat>);
+                0x26D => CVTWH, Some(float::instr_cvt_from_int::<_, u16, HFloat>);
+                0x26E => CVTLH, Some(float::instr_cvt_from_int::<_, u32, HFloat>);
+                0x26F => ACBH, Some(float::instr_acb::<_, HFloat>);
+                0x270 => MOVH, Some(float::instr_mov::<_, HFloat>);
+                0x271 => CMPH, Some(float::instr_cmp::<_, HFloat>);
+                0x272 => MNEGH, Some(float::instr_mneg::<_, HFloat>);
+                0x273 => TSTH, Some(float::instr_tst::<_, HFloat>);
+                0x274 => EMODH, Some(float::instr_emod::<_, HFloat>);
+                0x275 => POLYH, Some(float::instr_polyh);
+                0x276 => CVTHG, Some(float::instr_cvt_float::<_, HFloat, GFloat>);
 
                 0x27D => CLRO, Some(misc::instr_clr::<_, u128>);
                 0x27D => MOVO, Some(arith::instr_mov::<_, u128>);
@@ -367,8 +374,8 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0x28E => VVSUBD, Some(misc::instr_noimpl);
                 0x28F => VSSUBD, Some(misc::instr_noimpl);
                 
-                0x298 => CVTFH, Some(misc::instr_noimpl);
-                0x299 => CVTFG, Some(misc::instr_noimpl);
+                0x298 => CVTFH, Some(float::instr_cvt_float::<_, FFloat, HFloat>);
+                0x299 => CVTFG, Some(float::instr_cvt_float::<_, FFloat, GFloat>);
                 0x29A => PROBEVMR, Some(misc::instr_noimpl);
                 0x29B => PROBEVMW, Some(misc::instr_noimpl);
                 0x29C => VSTL, Some(misc::instr_noimpl);
@@ -413,8 +420,8 @@ impl<T: VAXBus> VAXCPU<'_, T> {
                 0x2ED => IOTA, Some(misc::instr_noimpl);
                 0x2EE => VVMERGE, Some(misc::instr_noimpl);
                 0x2EF => VSMERGE, Some(misc::instr_noimpl);
-                0x2F6 => CVTHF, Some(misc::instr_noimpl);
-                0x2F7 => CVTHD, Some(misc::instr_noimpl);
+                0x2F6 => CVTHF, Some(float::instr_cvt_float::<_, HFloat, FFloat>);
+                0x2F7 => CVTHD, Some(float::instr_cvt_float::<_, HFloat, DFloat>);
             }
         };
         
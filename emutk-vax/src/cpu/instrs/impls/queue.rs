@@ -0,0 +1,291 @@
+use super::util::*;
+use crate::cpu::VAXCPU;
+use crate::bus::VAXBus;
+use crate::{Error, CVZN, VAXNum};
+use emutk_core::{
+    cycles::Cycles,
+};
+
+// Queues are doubly linked lists threaded through a forward link at +0 and a
+// backward link at +4 of each entry, with the header being an entry of its own.
+// The absolute instructions store addresses in the links. The interlocked ones
+// store offsets from the entry instead, so the queue can be mapped at different
+// addresses by different processes, and use bit 0 of the header's forward link
+// as a secondary interlock so processors sharing the queue don't trip over
+// each other. Everything a queue instruction writes is probed first, so a fault
+// leaves the queue as it was.
+
+/// Condition codes from comparing an entry's links after an absolute insert or
+/// remove. Z means the queue has (or had) a single entry.
+fn link_flags(flink: u32, blink: u32) -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_n((flink as i32) < (blink as i32));
+    flags.set_z(flink == blink);
+    flags.set_c(flink < blink);
+    flags
+}
+
+pub fn instr_insque
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let entry = parse_address_operand::<B,u8>(cpu)?;
+    let pred = parse_address_operand::<B,u8>(cpu)?;
+
+    let succ: u32 = cpu.read_val(pred)?;
+    cpu.can_write_val::<u64>(entry)?;
+    cpu.can_write_val::<u32>(succ.wrapping_add(4))?;
+    cpu.can_write_val::<u32>(pred)?;
+
+    cpu.write_val(entry, succ)?;
+    cpu.write_val(entry.wrapping_add(4), pred)?;
+    cpu.write_val(succ.wrapping_add(4), entry)?;
+    cpu.write_val(pred, entry)?;
+
+    cpu.commit_flags(link_flags(succ, pred));
+    Ok(())
+}
+
+pub fn instr_remque
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let entry = parse_address_operand::<B,u8>(cpu)?;
+    let mut opw = parse_write_operand::<B, u32>(cpu)?;
+    opw.validate(cpu)?;
+
+    let flink: u32 = cpu.read_val(entry)?;
+    let blink: u32 = cpu.read_val(entry.wrapping_add(4))?;
+    cpu.can_write_val::<u32>(blink)?;
+    cpu.can_write_val::<u32>(flink.wrapping_add(4))?;
+
+    cpu.write_val(blink, flink)?;
+    cpu.write_val(flink.wrapping_add(4), blink)?;
+    opw.write(cpu, entry)?;
+
+    let mut flags = link_flags(flink, blink);
+    // Removing the header of an empty queue doesn't remove anything.
+    flags.set_v(entry == blink);
+    cpu.commit_flags(flags);
+    Ok(())
+}
+
+/// Read an interlocked queue entry (`.ab`) or header (`.aq`) address, which
+/// must be quadword aligned. `T` is the operand's size, which autoincrement,
+/// autodecrement and index scaling go by.
+fn read_queue_address
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u32, Error>
+{
+    let addr = parse_address_operand::<B,T>(cpu)?;
+    check_aligned(addr)?;
+    Ok(addr)
+}
+
+fn check_aligned(addr: u32) -> Result<(), Error> {
+    if addr & 0x7 != 0 {
+        return Err(Error::new_reserved_operand_fault());
+    }
+    Ok(())
+}
+
+/// Run `op` holding the secondary interlock on the queue at `header`. `op` gets
+/// the header's forward link and returns the one to leave behind, which also
+/// releases the interlock. Returns None if another processor holds it.
+fn with_queue_locked
+    <B: VAXBus, R, F>
+    (cpu: &mut VAXCPU<B>, header: u32, op: F)
+    -> Result<Option<R>, Error>
+    where F: FnOnce(&mut VAXCPU<B>, u32) -> Result<(u32, R), Error>
+{
+    let flink = cpu.interlocked_update::<u32, _>(header, |v| v | 1)?;
+    if flink & 1 != 0 {
+        return Ok(None);
+    }
+    match op(cpu, flink) {
+        Ok((flink, res)) => {
+            cpu.interlocked_update::<u32, _>(header, |_| flink)?;
+            Ok(Some(res))
+        }
+        Err(e) => {
+            cpu.interlocked_update::<u32, _>(header, |_| flink)?;
+            Err(e)
+        }
+    }
+}
+
+/// Flags for an insert that couldn't get the secondary interlock.
+fn insert_busy_flags() -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_c(true);
+    flags
+}
+
+/// Flags for a remove that couldn't get the secondary interlock.
+fn remove_busy_flags() -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_v(true);
+    flags.set_c(true);
+    flags
+}
+
+pub fn instr_insqhi
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let entry = read_queue_address::<B,u8>(cpu)?;
+    let header = read_queue_address::<B,u64>(cpu)?;
+
+    let res = with_queue_locked(cpu, header, |cpu, flink| {
+        let first = header.wrapping_add(flink);
+        check_aligned(first)?;
+        cpu.can_write_val::<u64>(entry)?;
+        cpu.can_write_val::<u32>(first.wrapping_add(4))?;
+
+        cpu.write_val(entry, first.wrapping_sub(entry))?;
+        cpu.write_val(entry.wrapping_add(4), header.wrapping_sub(entry))?;
+        cpu.write_val(first.wrapping_add(4), entry.wrapping_sub(first))?;
+        Ok((entry.wrapping_sub(header), flink == 0))
+    })?;
+
+    cpu.commit_flags(match res {
+        Some(was_empty) => {
+            let mut flags = CVZN::blank();
+            flags.set_z(was_empty);
+            flags
+        }
+        None => insert_busy_flags(),
+    });
+    Ok(())
+}
+
+pub fn instr_insqti
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let entry = read_queue_address::<B,u8>(cpu)?;
+    let header = read_queue_address::<B,u64>(cpu)?;
+
+    let res = with_queue_locked(cpu, header, |cpu, flink| {
+        let blink: u32 = cpu.read_val(header.wrapping_add(4))?;
+        let last = header.wrapping_add(blink);
+        check_aligned(last)?;
+        cpu.can_write_val::<u64>(entry)?;
+        cpu.can_write_val::<u32>(last)?;
+        cpu.can_write_val::<u32>(header.wrapping_add(4))?;
+
+        cpu.write_val(entry, header.wrapping_sub(entry))?;
+        cpu.write_val(entry.wrapping_add(4), last.wrapping_sub(entry))?;
+        cpu.write_val(header.wrapping_add(4), entry.wrapping_sub(header))?;
+        // The last entry of an empty queue is the header, whose forward link
+        // gets written when the interlock is released.
+        if last == header {
+            Ok((entry.wrapping_sub(header), true))
+        } else {
+            cpu.write_val(last, entry.wrapping_sub(last))?;
+            Ok((flink, false))
+        }
+    })?;
+
+    cpu.commit_flags(match res {
+        Some(was_empty) => {
+            let mut flags = CVZN::blank();
+            flags.set_z(was_empty);
+            flags
+        }
+        None => insert_busy_flags(),
+    });
+    Ok(())
+}
+
+/// Flags for a remove that got the interlock. V means there was nothing to
+/// remove, Z that the queue is empty now.
+fn remove_flags(removed: bool, now_empty: bool) -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_z(now_empty);
+    flags.set_v(!removed);
+    flags
+}
+
+pub fn instr_remqhi
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let header = read_queue_address::<B,u64>(cpu)?;
+    let mut opw = parse_write_operand::<B, u32>(cpu)?;
+    opw.validate(cpu)?;
+
+    let res = with_queue_locked(cpu, header, |cpu, flink| {
+        if flink == 0 {
+            return Ok((0, (header, false, true)));
+        }
+        let first = header.wrapping_add(flink);
+        check_aligned(first)?;
+        let next_link: u32 = cpu.read_val(first)?;
+        let next = first.wrapping_add(next_link);
+        check_aligned(next)?;
+        cpu.can_write_val::<u32>(next.wrapping_add(4))?;
+
+        cpu.write_val(next.wrapping_add(4), header.wrapping_sub(next))?;
+        let flink = next.wrapping_sub(header);
+        Ok((flink, (first, true, flink == 0)))
+    })?;
+
+    match res {
+        Some((entry, removed, now_empty)) => {
+            opw.write(cpu, entry)?;
+            cpu.commit_flags(remove_flags(removed, now_empty));
+        }
+        None => cpu.commit_flags(remove_busy_flags()),
+    }
+    Ok(())
+}
+
+pub fn instr_remqti
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let header = read_queue_address::<B,u64>(cpu)?;
+    let mut opw = parse_write_operand::<B, u32>(cpu)?;
+    opw.validate(cpu)?;
+
+    let res = with_queue_locked(cpu, header, |cpu, flink| {
+        let blink: u32 = cpu.read_val(header.wrapping_add(4))?;
+        if blink == 0 {
+            return Ok((flink, (header, false, true)));
+        }
+        let last = header.wrapping_add(blink);
+        check_aligned(last)?;
+        let prev_link: u32 = cpu.read_val(last.wrapping_add(4))?;
+        let prev = last.wrapping_add(prev_link);
+        check_aligned(prev)?;
+        cpu.can_write_val::<u32>(prev)?;
+        cpu.can_write_val::<u32>(header.wrapping_add(4))?;
+
+        cpu.write_val(header.wrapping_add(4), prev.wrapping_sub(header))?;
+        // If that was the only entry, the header becomes the last entry and
+        // its forward link is the one being released.
+        if prev == header {
+            Ok((0, (last, true, true)))
+        } else {
+            cpu.write_val(prev, header.wrapping_sub(prev))?;
+            Ok((flink, (last, true, false)))
+        }
+    })?;
+
+    match res {
+        Some((entry, removed, now_empty)) => {
+            opw.write(cpu, entry)?;
+            cpu.commit_flags(remove_flags(removed, now_empty));
+        }
+        None => cpu.commit_flags(remove_busy_flags()),
+    }
+    Ok(())
+}
@@ -1,8 +1,11 @@
 use super::util::*;
 use crate::cpu::VAXCPU;
 use crate::bus::VAXBus;
-use crate::Error;
+use crate::{Error, CVZN};
 use crate::VAXNum;
+use super::decimal::read_address;
+use crate::mmu::{PAGE_SIZE, page_offset};
+use crate::cpu::instrs::InstructionType;
 use emutk_core::{
     cycles::Cycles,
 };
@@ -10,9 +13,7 @@ use emutk_core::{
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum MultiInstruction {
     None,
-    CMPC3 {
-        len: u16,
-    },
+    CMPC3 {},
     CMPC5 {
         fill: u8,
     },
@@ -39,6 +40,162 @@ pub enum MultiInstruction {
     SPANC {
         mask: u8,
     },
+    ADDP4 {},
+    ADDP6 {},
+    SUBP4 {},
+    SUBP6 {},
+    MULP {},
+    DIVP {},
+    CMPP3 {},
+    CMPP4 {},
+    MOVP {},
+    ASHP {
+        cnt: u8,
+    },
+    EDITPC {},
+}
+
+impl MultiInstruction {
+    /// The state that doesn't live in the general registers.
+    fn payload(self) -> u8 {
+        use MultiInstruction::*;
+        match self {
+            CMPC5 { fill } | MOVC5 { fill } | MOVTC { fill } => fill,
+            LOCC { char } | SKPC { char } => char,
+            MOVTUC { esc } => esc,
+            SCANC { mask } | SPANC { mask } => mask,
+            ASHP { cnt } => cnt,
+            None | CMPC3 {} | MATCHC {} | MOVC3 {} => 0,
+            ADDP4 {} | ADDP6 {} | SUBP4 {} | SUBP6 {} | MULP {} | DIVP {}
+                | CMPP3 {} | CMPP4 {} | MOVP {} | EDITPC {} => 0,
+        }
+    }
+
+    fn from_payload(instr: InstructionType, payload: u8) -> Option<Self> {
+        use MultiInstruction::*;
+        Some(match instr {
+            InstructionType::CMPC3 => CMPC3 {},
+            InstructionType::CMPC5 => CMPC5 { fill: payload },
+            InstructionType::LOCC => LOCC { char: payload },
+            InstructionType::MATCHC => MATCHC {},
+            InstructionType::MOVC3 => MOVC3 {},
+            InstructionType::MOVC5 => MOVC5 { fill: payload },
+            InstructionType::MOVTC => MOVTC { fill: payload },
+            InstructionType::MOVTUC => MOVTUC { esc: payload },
+            InstructionType::SCANC => SCANC { mask: payload },
+            InstructionType::SKPC => SKPC { char: payload },
+            InstructionType::SPANC => SPANC { mask: payload },
+            InstructionType::ADDP4 => ADDP4 {},
+            InstructionType::ADDP6 => ADDP6 {},
+            InstructionType::SUBP4 => SUBP4 {},
+            InstructionType::SUBP6 => SUBP6 {},
+            InstructionType::MULP => MULP {},
+            InstructionType::DIVP => DIVP {},
+            InstructionType::CMPP3 => CMPP3 {},
+            InstructionType::CMPP4 => CMPP4 {},
+            InstructionType::MOVP => MOVP {},
+            InstructionType::ASHP => ASHP { cnt: payload },
+            InstructionType::EDITPC => EDITPC {},
+            _ => return Option::None,
+        })
+    }
+}
+
+/// Interrupt the active multi-part instruction so an exception can be taken.
+///
+/// The instruction is left restartable with PSL<FPD> set and PC pointing at its
+/// opcode. Lengths never exceed 16 bits, so the distance to the next instruction
+/// goes in R0<23:16> and the payload in R0<31:24>.
+pub fn suspend_multi_instruction
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, start_pc: u32)
+{
+    let delta = cpu.regfile.get_pc().wrapping_sub(start_pc) & 0xFF;
+    let payload = cpu.multi_instr_active.payload() as u32;
+    let r0 = cpu.regfile.get_r0() & 0xFFFF;
+    cpu.regfile.set_r0(r0 | (delta << 16) | (payload << 24));
+    cpu.regfile.set_pc(start_pc);
+    cpu.regfile.get_psl_mut().set_fpd(true);
+    cpu.multi_instr_active = MultiInstruction::None;
+}
+
+/// Pick up a multi-part instruction left by `suspend_multi_instruction`.
+/// Returns false if `instr` isn't one.
+pub fn resume_multi_instruction
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, instr: [u8; 2]) -> bool
+{
+    let r0 = cpu.regfile.get_r0();
+    let active = match InstructionType::from_instrid(instr)
+        .and_then(|i| MultiInstruction::from_payload(i, (r0 >> 24) as u8))
+    {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let pc = cpu.regfile.get_pc();
+    cpu.regfile.set_pc(pc.wrapping_add((r0 >> 16) & 0xFF));
+    cpu.regfile.set_r0(r0 & 0xFFFF);
+    cpu.regfile.get_psl_mut().set_fpd(false);
+    cpu.multi_instr_active = active;
+    true
+}
+
+/// How many bytes a string instruction handles before giving interrupts a chance.
+const STRING_CHUNK: u32 = 0xFF;
+
+fn read_len
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u32, Error>
+{
+    Ok(parse_read_operand::<B,u16>(cpu)?.read(cpu)? as u32)
+}
+
+fn read_char
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u8, Error>
+{
+    parse_read_operand::<B,u8>(cpu)?.read(cpu)
+}
+
+/// Flags for the moves, which compare the source and destination lengths.
+fn length_flags(srclen: u32, dstlen: u32) -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_n((srclen as i16) < (dstlen as i16));
+    flags.set_z(srclen == dstlen);
+    flags.set_c(srclen < dstlen);
+    flags
+}
+
+/// Flags for the searches, which only care if anything was left over.
+fn remaining_flags(remaining: u32) -> CVZN {
+    let mut flags = CVZN::blank();
+    flags.set_z(remaining == 0);
+    flags
+}
+
+/// Set up the shared MOVC3/MOVC5 state:
+///
+///   R0 source bytes left, R1 source, R3 destination, R4 destination bytes left
+///
+/// If the destination overlaps the end of the source the copy has to go
+/// backwards, in which case R2 counts down the bytes left to copy and the
+/// other registers hold still until it's done.
+fn start_movc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, srclen: u32, src: u32, dstlen: u32, dst: u32)
+{
+    let len = srclen.min(dstlen);
+    let backwards = dst > src && (dst as u64) < src as u64 + len as u64;
+    cpu.regfile.set_r0(srclen);
+    cpu.regfile.set_r1(src);
+    cpu.regfile.set_r2(if backwards { len } else { 0 });
+    cpu.regfile.set_r3(dst);
+    cpu.regfile.set_r4(dstlen);
+    cpu.regfile.set_r5(0);
+    cpu.commit_flags(length_flags(srclen, dstlen));
 }
 
 pub fn instr_movc3
@@ -46,22 +203,236 @@ pub fn instr_movc3
     (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    let len = parse_read_operand::<B,u16>(cpu)?.read(cpu)?;
-    let mut srcaddr = parse_read_operand::<B,u8>(cpu)?;
-    srcaddr.validate(cpu)?;
-    let mut dstaddr = parse_read_operand::<B,u8>(cpu)?;
-    dstaddr.validate(cpu)?;
+    let len = read_len(cpu)?;
+    let src = read_address(cpu)?;
+    let dst = read_address(cpu)?;
 
-    cpu.regfile.set_r0(len as u32);
-    cpu.regfile.set_r1(srcaddr.address()?);
-    cpu.regfile.set_r2(0);
-    cpu.regfile.set_r3(dstaddr.address()?);
-    cpu.regfile.set_r4(0);
-    cpu.regfile.set_r5(0);
+    start_movc(cpu, len, src, len, dst);
     cpu.multi_instr_active = MultiInstruction::MOVC3{};
     Ok(())
 }
 
+pub fn instr_movc5
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let srclen = read_len(cpu)?;
+    let src = read_address(cpu)?;
+    let fill = read_char(cpu)?;
+    let dstlen = read_len(cpu)?;
+    let dst = read_address(cpu)?;
+
+    start_movc(cpu, srclen, src, dstlen, dst);
+    cpu.multi_instr_active = MultiInstruction::MOVC5{ fill };
+    Ok(())
+}
+
+pub fn instr_cmpc3
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let len = read_len(cpu)?;
+    let src1 = read_address(cpu)?;
+    let src2 = read_address(cpu)?;
+
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(src1);
+    cpu.regfile.set_r2(len);
+    cpu.regfile.set_r3(src2);
+    cpu.multi_instr_active = MultiInstruction::CMPC3{};
+    Ok(())
+}
+
+pub fn instr_cmpc5
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let len1 = read_len(cpu)?;
+    let src1 = read_address(cpu)?;
+    let fill = read_char(cpu)?;
+    let len2 = read_len(cpu)?;
+    let src2 = read_address(cpu)?;
+
+    cpu.regfile.set_r0(len1);
+    cpu.regfile.set_r1(src1);
+    cpu.regfile.set_r2(len2);
+    cpu.regfile.set_r3(src2);
+    cpu.multi_instr_active = MultiInstruction::CMPC5{ fill };
+    Ok(())
+}
+
+fn start_locc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u8, Error>
+{
+    let char = read_char(cpu)?;
+    let len = read_len(cpu)?;
+    let addr = read_address(cpu)?;
+
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(addr);
+    Ok(char)
+}
+
+pub fn instr_locc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let char = start_locc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::LOCC{ char };
+    Ok(())
+}
+
+pub fn instr_skpc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let char = start_locc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::SKPC{ char };
+    Ok(())
+}
+
+fn start_scanc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u8, Error>
+{
+    let len = read_len(cpu)?;
+    let addr = read_address(cpu)?;
+    let table = read_address(cpu)?;
+    let mask = read_char(cpu)?;
+
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(addr);
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(table);
+    Ok(mask)
+}
+
+pub fn instr_scanc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mask = start_scanc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::SCANC{ mask };
+    Ok(())
+}
+
+pub fn instr_spanc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mask = start_scanc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::SPANC{ mask };
+    Ok(())
+}
+
+pub fn instr_matchc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let objlen = read_len(cpu)?;
+    let obj = read_address(cpu)?;
+    let srclen = read_len(cpu)?;
+    let src = read_address(cpu)?;
+
+    cpu.regfile.set_r0(objlen);
+    cpu.regfile.set_r1(obj);
+    cpu.regfile.set_r2(srclen);
+    cpu.regfile.set_r3(src);
+    cpu.multi_instr_active = MultiInstruction::MATCHC{};
+    Ok(())
+}
+
+/// Set up the MOVTC/MOVTUC state:
+///
+///   R0 source bytes left, R1 source, R3 table, R4 destination bytes left,
+///   R5 destination
+fn start_movtc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u8, Error>
+{
+    let srclen = read_len(cpu)?;
+    let src = read_address(cpu)?;
+    let char = read_char(cpu)?;
+    let table = read_address(cpu)?;
+    let dstlen = read_len(cpu)?;
+    let dst = read_address(cpu)?;
+
+    cpu.regfile.set_r0(srclen);
+    cpu.regfile.set_r1(src);
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(table);
+    cpu.regfile.set_r4(dstlen);
+    cpu.regfile.set_r5(dst);
+    cpu.commit_flags(length_flags(srclen, dstlen));
+    Ok(char)
+}
+
+pub fn instr_movtc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let fill = start_movtc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::MOVTC{ fill };
+    Ok(())
+}
+
+pub fn instr_movtuc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let esc = start_movtc(cpu)?;
+    cpu.multi_instr_active = MultiInstruction::MOVTUC{ esc };
+    Ok(())
+}
+
+/// CRC runs the string through a 16 entry table one nibble at a time, so any
+/// polynomial can be used. Unlike the other string instructions it finishes in
+/// one go, leaving R0 the CRC, R1 and R2 zero, and R3 one past the string.
+pub fn instr_crc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let tbl = read_address(cpu)?;
+    let mut crc = parse_read_operand::<B,u32>(cpu)?.read(cpu)?;
+    let len = read_len(cpu)?;
+    let stream = read_address(cpu)?;
+
+    let mut table = [0; 64];
+    cpu.read_block(tbl, &mut table)?;
+    let entry = |i: u32| {
+        let i = i as usize * 4;
+        u32::from_le_bytes([table[i], table[i + 1], table[i + 2], table[i + 3]])
+    };
+    let mut data = vec![0; len as usize];
+    cpu.read_block(stream, &mut data)?;
+    for b in data {
+        crc ^= b as u32;
+        crc = (crc >> 4) ^ entry(crc & 0xF);
+        crc = (crc >> 4) ^ entry(crc & 0xF);
+    }
+
+    cpu.regfile.set_r0(crc);
+    cpu.regfile.set_r1(0);
+    cpu.regfile.set_r2(0);
+    cpu.regfile.set_r3(stream.wrapping_add(len));
+    cpu.commit_flags(crc.calc_nz());
+    Ok(())
+}
 
 pub fn exec_multi_instructions
     <B: VAXBus>
@@ -70,43 +441,396 @@ pub fn exec_multi_instructions
 {
     match cpu.multi_instr_active {
         MultiInstruction::MOVC3 {} => {
-            exec_movc3(cpu, cycle_count)?;
+            exec_movc(cpu, 0, cycle_count)?;
+        }
+        MultiInstruction::MOVC5 { fill } => {
+            exec_movc(cpu, fill, cycle_count)?;
+        }
+        MultiInstruction::CMPC3 {} => {
+            exec_cmpc(cpu, 0, cycle_count)?;
+        }
+        MultiInstruction::CMPC5 { fill } => {
+            exec_cmpc(cpu, fill, cycle_count)?;
+        }
+        MultiInstruction::LOCC { char } => {
+            exec_locc(cpu, char, cycle_count)?;
+        }
+        MultiInstruction::SKPC { char } => {
+            exec_skpc(cpu, char, cycle_count)?;
+        }
+        MultiInstruction::SCANC { mask } => {
+            exec_scanc(cpu, mask, cycle_count)?;
+        }
+        MultiInstruction::SPANC { mask } => {
+            exec_spanc(cpu, mask, cycle_count)?;
+        }
+        MultiInstruction::MATCHC {} => {
+            exec_matchc(cpu, cycle_count)?;
+        }
+        MultiInstruction::MOVTC { fill } => {
+            exec_movtc(cpu, fill, cycle_count)?;
+        }
+        MultiInstruction::MOVTUC { esc } => {
+            exec_movtuc(cpu, esc, cycle_count)?;
+        }
+        MultiInstruction::EDITPC {} => {
+            super::editpc::exec_editpc(cpu)?;
+        }
+        instr @ (MultiInstruction::ADDP4 {}
+            | MultiInstruction::ADDP6 {}
+            | MultiInstruction::SUBP4 {}
+            | MultiInstruction::SUBP6 {}
+            | MultiInstruction::MULP {}
+            | MultiInstruction::DIVP {}
+            | MultiInstruction::CMPP3 {}
+            | MultiInstruction::CMPP4 {}
+            | MultiInstruction::MOVP {}
+            | MultiInstruction::ASHP { .. }) => {
+            super::decimal::exec_decimal(cpu, instr)?;
+        }
+        // Only reachable if a caller resumes with nothing in flight.
+        MultiInstruction::None => {
+            return Err(Error::new_machine_check());
         }
-        _=> unimplemented!(),
     }
     Ok(())
 }
 
-pub fn exec_movc3
+/// How far `addr` is from the end of its page.
+fn to_page_end(addr: u32) -> u32 {
+    PAGE_SIZE - page_offset(addr)
+}
+
+pub fn exec_movc
     <B: VAXBus>
-    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    (cpu: &mut VAXCPU<B>, fill: u8, _cycle_count: &mut Cycles)
     -> Result<(), Error>
 {
-    // this is literally just memcpy :T
-    let mut full_len = cpu.regfile.get_r0();
+    let mut srclen = cpu.regfile.get_r0();
     let mut src = cpu.regfile.get_r1();
+    let mut back = cpu.regfile.get_r2();
     let mut dst = cpu.regfile.get_r3();
-    let len = full_len & 0xFF; // how many bytes to copy this go around.
-    
-    //OPTIMIZATION POTENTIAL:
-    // Add API to bus to get mut slice to a chunk of RAM? (would allow direct memcpy instead of this)
-    println!("MOVC3 | Processing: {} bytes | Remainder: {} bytes", len, full_len - len);
-    for _ in 0..len {
-        let sbyte: u8 = cpu.read_val(src)?;
-        println!("I: {} | O: {} | V: {:?}", src, dst, sbyte);
-
-        cpu.write_val(dst, sbyte)?;
-        full_len -= 1;
-        cpu.regfile.set_r0(full_len);
-        src += 1;
-        cpu.regfile.set_r1(src);
-        dst += 1;
+    let mut dstlen = cpu.regfile.get_r4();
+    let mut budget = STRING_CHUNK;
+
+    // Blocks never cross a page, so a fault leaves everything before it done.
+    while back != 0 && budget != 0 {
+        let top_src = src.wrapping_add(back - 1);
+        let top_dst = dst.wrapping_add(back - 1);
+        let n = back.min(budget)
+            .min(page_offset(top_src) + 1)
+            .min(page_offset(top_dst) + 1);
+        back -= n;
+        cpu.copy_block(src.wrapping_add(back), dst.wrapping_add(back), n)?;
+        cpu.regfile.set_r2(back);
+        budget -= n;
+
+        if back == 0 {
+            // Everything that overlapped is copied, so pick up after it.
+            let len = srclen.min(dstlen);
+            srclen -= len;
+            dstlen -= len;
+            src = src.wrapping_add(len);
+            dst = dst.wrapping_add(len);
+            cpu.regfile.set_r0(srclen);
+            cpu.regfile.set_r1(src);
+            cpu.regfile.set_r3(dst);
+            cpu.regfile.set_r4(dstlen);
+        }
+    }
+
+    while back == 0 && dstlen != 0 && budget != 0 {
+        let n = dstlen.min(budget).min(to_page_end(dst));
+        let n = if srclen != 0 {
+            let n = n.min(srclen).min(to_page_end(src));
+            cpu.copy_block(src, dst, n)?;
+            srclen -= n;
+            src = src.wrapping_add(n);
+            cpu.regfile.set_r0(srclen);
+            cpu.regfile.set_r1(src);
+            n
+        } else {
+            cpu.fill_block(dst, fill, n)?;
+            n
+        };
+        dstlen -= n;
+        dst = dst.wrapping_add(n);
         cpu.regfile.set_r3(dst);
+        cpu.regfile.set_r4(dstlen);
+        budget -= n;
     }
 
-    if full_len == 0 {
+    if back == 0 && dstlen == 0 {
         cpu.multi_instr_active = MultiInstruction::None; // Done!
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Read `len` bytes of a string, or `len` fill characters if it's run out.
+fn read_string
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, addr: u32, left: u32, fill: u8, len: u32)
+    -> Result<Vec<u8>, Error>
+{
+    let mut buf = vec![fill; len as usize];
+    if left != 0 {
+        cpu.read_block(addr, &mut buf)?;
+    }
+    Ok(buf)
+}
+
+pub fn exec_cmpc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, fill: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mut len1 = cpu.regfile.get_r0();
+    let mut src1 = cpu.regfile.get_r1();
+    let mut len2 = cpu.regfile.get_r2();
+    let mut src2 = cpu.regfile.get_r3();
+
+    if len1 == 0 && len2 == 0 {
+        cpu.commit_flags(remaining_flags(0));
+        cpu.multi_instr_active = MultiInstruction::None;
+        return Ok(());
+    }
+
+    // The shorter string is padded out with the fill character.
+    let n = match (len1, len2) {
+        (0, l) | (l, 0) => l,
+        (l1, l2) => l1.min(l2),
+    }.min(STRING_CHUNK);
+    let s1 = read_string(cpu, src1, len1, fill, n)?;
+    let s2 = read_string(cpu, src2, len2, fill, n)?;
+    let same = s1.iter().zip(s2.iter()).take_while(|(a, b)| a == b).count() as u32;
+
+    if len1 != 0 {
+        len1 -= same;
+        src1 = src1.wrapping_add(same);
+        cpu.regfile.set_r0(len1);
+        cpu.regfile.set_r1(src1);
+    }
+    if len2 != 0 {
+        len2 -= same;
+        src2 = src2.wrapping_add(same);
+        cpu.regfile.set_r2(len2);
+        cpu.regfile.set_r3(src2);
+    }
+
+    if same < n {
+        let (b1, b2) = (s1[same as usize], s2[same as usize]);
+        let mut flags = CVZN::blank();
+        flags.set_n((b1 as i8) < (b2 as i8));
+        flags.set_c(b1 < b2);
+        cpu.commit_flags(flags);
+        cpu.multi_instr_active = MultiInstruction::None;
+    } else if len1 == 0 && len2 == 0 {
+        cpu.commit_flags(remaining_flags(0));
+        cpu.multi_instr_active = MultiInstruction::None;
+    }
+    Ok(())
+}
+
+/// Step through the string in R0/R1 until `found` matches a byte, leaving them
+/// pointing at it.
+fn exec_search
+    <B: VAXBus, F: Fn(&mut VAXCPU<B>, u8) -> Result<bool, Error>>
+    (cpu: &mut VAXCPU<B>, found: F)
+    -> Result<(), Error>
+{
+    let mut len = cpu.regfile.get_r0();
+    let mut addr = cpu.regfile.get_r1();
+
+    let n = len.min(STRING_CHUNK);
+    let mut buf = vec![0; n as usize];
+    cpu.read_block(addr, &mut buf)?;
+
+    let mut hit = false;
+    for b in buf {
+        if found(cpu, b)? {
+            hit = true;
+            break;
+        }
+        len -= 1;
+        addr = addr.wrapping_add(1);
+    }
+    cpu.regfile.set_r0(len);
+    cpu.regfile.set_r1(addr);
+
+    if hit || len == 0 {
+        cpu.commit_flags(remaining_flags(len));
+        cpu.multi_instr_active = MultiInstruction::None;
+    }
+    Ok(())
+}
+
+pub fn exec_locc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, char: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    exec_search(cpu, |_, b| Ok(b == char))
+}
+
+pub fn exec_skpc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, char: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    exec_search(cpu, |_, b| Ok(b != char))
+}
+
+pub fn exec_scanc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, mask: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let table = cpu.regfile.get_r3();
+    exec_search(cpu, |cpu, b| {
+        let entry: u8 = cpu.read_val(table.wrapping_add(b as u32))?;
+        Ok(entry & mask != 0)
+    })
+}
+
+pub fn exec_spanc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, mask: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let table = cpu.regfile.get_r3();
+    exec_search(cpu, |cpu, b| {
+        let entry: u8 = cpu.read_val(table.wrapping_add(b as u32))?;
+        Ok(entry & mask == 0)
+    })
+}
+
+pub fn exec_matchc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let objlen = cpu.regfile.get_r0();
+    let obj = cpu.regfile.get_r1();
+    let mut srclen = cpu.regfile.get_r2();
+    let mut src = cpu.regfile.get_r3();
+
+    // Try the object at up to a chunk's worth of positions in the source per go.
+    let mut objbuf = vec![0; objlen as usize];
+    cpu.read_block(obj, &mut objbuf)?;
+    let mut window = vec![0; srclen.min(STRING_CHUNK + objlen) as usize];
+    cpu.read_block(src, &mut window)?;
+
+    for pos in 0..STRING_CHUNK as usize {
+        if srclen < objlen {
+            // Not found, so the whole source has been used up.
+            cpu.regfile.set_r2(0);
+            cpu.regfile.set_r3(src.wrapping_add(srclen));
+            cpu.commit_flags(remaining_flags(objlen));
+            cpu.multi_instr_active = MultiInstruction::None;
+            return Ok(());
+        }
+        if window[pos..pos + objlen as usize] == objbuf[..] {
+            cpu.regfile.set_r0(0);
+            cpu.regfile.set_r1(obj.wrapping_add(objlen));
+            cpu.regfile.set_r2(srclen - objlen);
+            cpu.regfile.set_r3(src.wrapping_add(objlen));
+            cpu.commit_flags(remaining_flags(0));
+            cpu.multi_instr_active = MultiInstruction::None;
+            return Ok(());
+        }
+        srclen -= 1;
+        src = src.wrapping_add(1);
+    }
+    cpu.regfile.set_r2(srclen);
+    cpu.regfile.set_r3(src);
+    Ok(())
+}
+
+/// Translate `len` bytes from `src` through the table at R3.
+fn translate_string
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, src: u32, len: u32)
+    -> Result<Vec<u8>, Error>
+{
+    let table = cpu.regfile.get_r3();
+    let mut buf = vec![0; len as usize];
+    cpu.read_block(src, &mut buf)?;
+    for b in buf.iter_mut() {
+        *b = cpu.read_val(table.wrapping_add(*b as u32))?;
+    }
+    Ok(buf)
+}
+
+pub fn exec_movtc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, fill: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mut srclen = cpu.regfile.get_r0();
+    let mut src = cpu.regfile.get_r1();
+    let mut dstlen = cpu.regfile.get_r4();
+    let mut dst = cpu.regfile.get_r5();
+
+    if srclen != 0 && dstlen != 0 {
+        let n = srclen.min(dstlen).min(STRING_CHUNK);
+        let buf = translate_string(cpu, src, n)?;
+        cpu.write_block(dst, &buf)?;
+        srclen -= n;
+        src = src.wrapping_add(n);
+        dstlen -= n;
+        dst = dst.wrapping_add(n);
+        cpu.regfile.set_r0(srclen);
+        cpu.regfile.set_r1(src);
+        cpu.regfile.set_r4(dstlen);
+        cpu.regfile.set_r5(dst);
+    } else if dstlen != 0 {
+        let n = dstlen.min(STRING_CHUNK);
+        cpu.fill_block(dst, fill, n)?;
+        dstlen -= n;
+        dst = dst.wrapping_add(n);
+        cpu.regfile.set_r4(dstlen);
+        cpu.regfile.set_r5(dst);
+    }
+
+    if dstlen == 0 {
+        cpu.multi_instr_active = MultiInstruction::None;
+    }
+    Ok(())
+}
+
+pub fn exec_movtuc
+    <B: VAXBus>
+    (cpu: &mut VAXCPU<B>, esc: u8, _cycle_count: &mut Cycles)
+    -> Result<(), Error>
+{
+    let mut srclen = cpu.regfile.get_r0();
+    let mut src = cpu.regfile.get_r1();
+    let mut dstlen = cpu.regfile.get_r4();
+    let mut dst = cpu.regfile.get_r5();
+
+    let n = srclen.min(dstlen).min(STRING_CHUNK);
+    let buf = translate_string(cpu, src, n)?;
+    // Stop on the escape, leaving R0/R1 pointing at it.
+    let escaped = buf.iter().position(|&b| b == esc);
+    let moved = escaped.unwrap_or(buf.len());
+    cpu.write_block(dst, &buf[..moved])?;
+
+    let moved = moved as u32;
+    srclen -= moved;
+    src = src.wrapping_add(moved);
+    dstlen -= moved;
+    dst = dst.wrapping_add(moved);
+    cpu.regfile.set_r0(srclen);
+    cpu.regfile.set_r1(src);
+    cpu.regfile.set_r4(dstlen);
+    cpu.regfile.set_r5(dst);
+
+    if escaped.is_some() {
+        cpu.regfile.get_psl_mut().set_v(true);
+    }
+    if escaped.is_some() || srclen == 0 || dstlen == 0 {
+        cpu.multi_instr_active = MultiInstruction::None;
+    }
+    Ok(())
+}
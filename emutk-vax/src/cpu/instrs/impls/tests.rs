@@ -1,8 +1,1310 @@
 use super::*;
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::cpu::PSL;
+    use crate::cpu::exec::simple_test_cpu;
+    use crate::ErrorKind;
+
     #[test]
     pub fn callg_test() {
-        
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0xDD, 0x07,       // PUSHL S^#7
+            0xDD, 0x09,       // PUSHL S^#9
+            0xFB, 0x02, 0x66, // CALLS S^#2, (R6)
+            0xFA, 0x67, 0x66, // CALLG (R7), (R6)
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        bus.ram_mut()[0x900..0x909].copy_from_slice(&[
+            0x0C, 0x40,       // .ENTRY ^M<IV, R2, R3>
+            0xD0, 0x0F, 0x52, // MOVL S^#15, R2
+            0xD0, 0x6C, 0x53, // MOVL (AP), R3
+            0x04,             // RET
+        ]);
+        bus.ram_mut()[0xA00] = 0x00; // Entry mask with bit 12 set.
+        bus.ram_mut()[0xA01] = 0x10;
+        bus.ram_mut()[0x1100] = 0x01;
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0x1));
+        cpu.regfile.set_r2(0x22);
+        cpu.regfile.set_r3(0x33);
+        cpu.regfile.set_r6(0x900);
+        cpu.regfile.set_r7(0x1100);
+        cpu.regfile.write_gpr(12, 0x5678);
+        cpu.regfile.write_gpr(13, 0x1234);
+        cpu.regfile.set_sp(0x1000);
+        cpu.regfile.set_pc(0x800);
+
+        for _ in 0..3 {
+            cpu.run_tick().unwrap();
+        }
+        assert_eq!(cpu.regfile.get_pc(), 0x902);
+        assert_eq!(cpu.regfile.get_sp(), 0xFD8);
+        assert_eq!(cpu.regfile.read_gpr(13), 0xFD8);
+        assert_eq!(cpu.regfile.read_gpr(12), 0xFF4);
+        assert!(cpu.regfile.get_psl().get_iv());
+        assert!(!cpu.regfile.get_psl().get_c());
+        let frame: Vec<u32> = (0..8).map(|i| cpu.read_val(0xFD8 + i * 4).unwrap()).collect();
+        assert_eq!(frame, [0, 0x200C_0000, 0x5678, 0x1234, 0x807, 0x22, 0x33, 2]);
+
+        for _ in 0..3 {
+            cpu.run_tick().unwrap();
+        }
+        assert_eq!(cpu.regfile.get_pc(), 0x807);
+        assert_eq!(cpu.regfile.get_sp(), 0x1000);
+        assert_eq!(cpu.regfile.get_r2(), 0x22);
+        assert_eq!(cpu.regfile.get_r3(), 0x33);
+        assert_eq!(cpu.regfile.read_gpr(12), 0x5678);
+        assert_eq!(cpu.regfile.read_gpr(13), 0x1234);
+        // The saved PSW had NZVC cleared, and IV comes back off.
+        assert_eq!(cpu.regfile.get_psl().0, 0);
+
+        // CALLG aligns the stack and RET puts it back, leaving the arguments.
+        cpu.regfile.set_sp(0xFFE);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_sp(), 0xFFC - 28);
+        assert_eq!(cpu.regfile.read_gpr(12), 0x1100);
+        assert_eq!(cpu.read_val::<u32>(0xFFC - 24).unwrap(), 0x800C_0000);
+        for _ in 0..3 {
+            cpu.run_tick().unwrap();
+        }
+        assert_eq!(cpu.regfile.get_pc(), 0x80A);
+        assert_eq!(cpu.regfile.get_sp(), 0xFFE);
+        assert_eq!(cpu.regfile.get_r3(), 0x33);
+
+        // Reserved entry mask bits and saved PSW bits fault without side effects.
+        cpu.regfile.set_r6(0xA00);
+        cpu.regfile.set_pc(0x808);
+        let e = control::instr_callg(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        assert_eq!(cpu.regfile.get_sp(), 0xFFE);
+        cpu.write_val::<u32>(0x1104, 0x0000_0100).unwrap();
+        cpu.regfile.write_gpr(13, 0x1100);
+        let e = control::instr_ret(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        assert_eq!(cpu.regfile.get_sp(), 0xFFE);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn rei_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        bus.ram_mut()[0x800] = 0x02; // REI
+        bus.ram_mut()[0xFF8..0xFFC].copy_from_slice(&0x900_u32.to_le_bytes());
+        bus.ram_mut()[0xFFC..0x1000].copy_from_slice(&0x03C0_0004_u32.to_le_bytes());
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0x0003_0000));
+        cpu.regfile.set_ksp(0xFF8);
+        cpu.regfile.set_usp(0x1800);
+        cpu.regfile.set_pc(0x800);
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x900);
+        assert_eq!(cpu.regfile.get_psl().0, 0x03C0_0004);
+        assert_eq!(cpu.regfile.get_ksp(), 0x1000);
+        assert_eq!(cpu.regfile.get_sp(), 0x1800);
+
+        // User mode can't REI back into kernel mode.
+        cpu.write_val::<u32>(0x17F8, 0x800).unwrap();
+        cpu.write_val::<u32>(0x17FC, 0).unwrap();
+        cpu.regfile.set_sp(0x17F8);
+        let e = context::instr_rei(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+
+        // Nor can anything REI onto the interrupt stack from elsewhere.
+        cpu.regfile.set_psl(PSL(0x001F_0000));
+        cpu.regfile.set_sp(0x17F8);
+        cpu.write_val::<u32>(0x17FC, 0x041F_0000).unwrap();
+        let e = context::instr_rei(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn context_switch_test() {
+        const PCBB: u32 = 0x1400;
+        let (mut cpu, mut bus) = simple_test_cpu();
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0x0003_0000));
+        cpu.regfile.set_pcbb(PCBB);
+        cpu.regfile.set_ksp(0xFF8);
+        cpu.regfile.set_usp(0x1800);
+        cpu.write_val::<u32>(0xFF8, 0x2000).unwrap();
+        cpu.write_val::<u32>(0xFFC, 0x03C0_0000).unwrap();
+        for i in 0..14 {
+            cpu.regfile.write_gpr(i, 0x11 * i as u32);
+        }
+
+        context::instr_svpctx(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.read_phys::<u32>(PCBB).unwrap(), 0x1000);
+        assert_eq!(cpu.read_phys::<u32>(PCBB + 12).unwrap(), 0x1800);
+        assert_eq!(cpu.read_phys::<u32>(PCBB + 16 + 4 * 13).unwrap(), 0x11 * 13);
+        assert_eq!(cpu.read_phys::<u32>(PCBB + 72).unwrap(), 0x2000);
+        assert_eq!(cpu.read_phys::<u32>(PCBB + 76).unwrap(), 0x03C0_0000);
+        assert!(cpu.regfile.get_psl().get_is());
+        assert_eq!(cpu.regfile.get_psl().get_ipl(), 3);
+
+        cpu.write_phys::<u32>(PCBB + 16 + 4 * 5, 0x1234).unwrap();
+        cpu.write_phys::<u32>(PCBB + 80, 0x8000_0600).unwrap();
+        cpu.write_phys::<u32>(PCBB + 84, (3 << 24) | 0x10).unwrap();
+        cpu.write_phys::<u32>(PCBB + 92, (1 << 31) | 0x1F_FF00).unwrap();
+        for i in 0..14 {
+            cpu.regfile.write_gpr(i, 0);
+        }
+        cpu.regfile.set_ksp(0);
+
+        context::instr_ldpctx(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r5(), 0x1234);
+        assert_eq!(cpu.regfile.get_r13(), 0x11 * 13);
+        assert_eq!(cpu.regfile.get_p0br(), 0x8000_0600);
+        assert_eq!(cpu.regfile.get_p0lr(), 0x10);
+        assert_eq!(cpu.regfile.get_p1lr(), 0x1F_FF00);
+        assert_eq!(cpu.regfile.get_astlvl(), 3);
+        assert!(!cpu.regfile.get_psl().get_is());
+        assert_eq!(cpu.regfile.get_ksp(), 0xFF8);
+
+        context::instr_rei(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x2000);
+        assert_eq!(cpu.regfile.get_psl().get_cur_mod(), 3);
+        assert_eq!(cpu.regfile.get_sp(), 0x1800);
+    }
+
+    #[test]
+    pub fn chm_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        const SCBB: u32 = 0x200;
+        bus.ram_mut()[0x240..0x244].copy_from_slice(&0x3000_u32.to_le_bytes()); // CHMK
+        bus.ram_mut()[0x24C..0x250].copy_from_slice(&0x3300_u32.to_le_bytes()); // CHMU
+        // CHMK #-2 ; CHMU #7
+        bus.ram_mut()[0x800..0x806].copy_from_slice(&[0xBC, 0x8F, 0xFE, 0xFF, 0xBF, 0x07]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_scbb(SCBB);
+        cpu.regfile.set_ksp(0x1000);
+        cpu.regfile.set_usp(0x1800);
+        cpu.regfile.set_psl(PSL(0x03C0_0000));
+        cpu.regfile.set_pc(0x800);
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3000);
+        let psl = *cpu.regfile.get_psl();
+        assert_eq!((psl.get_cur_mod(), psl.get_prv_mod()), (0, 3));
+        assert_eq!(cpu.regfile.get_sp(), 0x1000 - 12);
+        assert_eq!(cpu.read_phys::<u32>(0x1000 - 12).unwrap(), 0xFFFF_FFFE);
+        assert_eq!(cpu.read_phys::<u32>(0x1000 - 8).unwrap(), 0x804);
+        assert_eq!(cpu.read_phys::<u32>(0x1000 - 4).unwrap(), 0x03C0_0000);
+
+        // Return to user mode, then CHMU stays there and uses the user stack.
+        cpu.regfile.set_astlvl(3);
+        cpu.regfile.set_sp(0x1000 - 8); // Pop the code.
+        context::instr_rei(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_sisr(), 1 << 2);
+        cpu.regfile.set_sisr(0);
+        cpu.regfile.set_pc(0x804);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3300);
+        let psl = *cpu.regfile.get_psl();
+        assert_eq!((psl.get_cur_mod(), psl.get_prv_mod()), (3, 3));
+        assert_eq!(cpu.regfile.get_sp(), 0x1800 - 12);
+        assert_eq!(cpu.read_phys::<u32>(0x1800 - 12).unwrap(), 7);
+
+        // The vector is the opcode's even when the mode doesn't change: CHMU in
+        // kernel mode goes to the CHMU handler and stays in kernel mode.
+        cpu.write_phys(SCBB + 0x44, 0x3100_u32).unwrap(); // CHME
+        // CHMU #1 ; CHME #2
+        cpu.write_block(0x810, &[0xBF, 0x01, 0xBD, 0x02]).unwrap();
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_ksp(0x1000);
+        cpu.regfile.set_pc(0x810);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3300);
+        let psl = *cpu.regfile.get_psl();
+        assert_eq!((psl.get_cur_mod(), psl.get_prv_mod()), (0, 0));
+        assert_eq!(cpu.read_phys::<u32>(0x1000 - 12).unwrap(), 1);
+
+        // CHME from user mode enters executive mode on its stack.
+        cpu.regfile.set_esp(0x1400);
+        cpu.regfile.set_psl(PSL(0x03C0_0000));
+        cpu.regfile.set_pc(0x812);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_pc(), 0x3100);
+        let psl = *cpu.regfile.get_psl();
+        assert_eq!((psl.get_cur_mod(), psl.get_prv_mod()), (1, 3));
+        assert_eq!(cpu.regfile.get_sp(), 0x1400 - 12);
+        assert_eq!(cpu.read_phys::<u32>(0x1400 - 12).unwrap(), 2);
+
+        // Change mode on the interrupt stack halts.
+        cpu.regfile.set_psl(PSL(0x041F_0000));
+        cpu.regfile.set_pc(0x800);
+        let e = cpu.run_tick().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ChangeMode);
+        assert!(cpu.halted());
+    }
+
+    #[test]
+    pub fn float_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // ADDF3 S^#1.5, R1, R2 ; DIVF2 R3, R2
+        bus.ram_mut()[0x800..0x807].copy_from_slice(&[0x41, 0x0C, 0x51, 0x52, 0x46, 0x53, 0x52]);
+        // CVTRFL R4, R5 ; ACBF S^#4.0, S^#1.0, R6, 0x10
+        bus.ram_mut()[0x900..0x909].copy_from_slice(&[0x4B, 0x54, 0x55, 0x4F, 0x18, 0x08, 0x56, 0x10, 0x00]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x801);
+        cpu.regfile.set_r1(0x4100); // 2.0
+        cpu.regfile.set_r4(0xC120); // -2.5
+        cpu.regfile.set_r6(0x4140); // 3.0
+
+        float::instr_add3::<_, FFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r2(), 0x4160); // 3.5
+        cpu.regfile.set_pc(0x805);
+        let e = float::instr_div2::<_, FFloat>(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::FloatingDivZero);
+        assert_eq!(cpu.regfile.get_r2(), 0x4160);
+
+        cpu.regfile.set_pc(0x901);
+        float::instr_cvt_round_to_int::<_, FFloat, u32>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r5() as i32, -3);
+        assert!(cpu.regfile.get_psl().get_n());
+        cpu.regfile.set_pc(0x904);
+
+        float::instr_acb::<_, FFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r6(), 0x4180); // 4.0
+        assert_eq!(cpu.regfile.get_pc(), 0x909 + 0x10);
+
+        // Reserved operands fault, overflowing conversions trap if enabled.
+        cpu.regfile.set_r4(0x8000);
+        cpu.regfile.set_pc(0x901);
+        let e = float::instr_cvt_to_int::<_, FFloat, u32>(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        cpu.regfile.set_psl(PSL(0x20));
+        cpu.regfile.set_r4(0x5000); // 2^31
+        cpu.regfile.set_pc(0x901);
+        let e = float::instr_cvt_to_int::<_, FFloat, u32>(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::IntegerOverflow);
+        assert_eq!(cpu.regfile.get_r5(), 0x8000_0000);
+    }
+
+    #[test]
+    pub fn addressing_mode_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0xD0, 0xA1, 0x04, 0x53,                         // MOVL B^4(R1), R3
+            0xD0, 0xC1, 0x10, 0x00, 0x53,                   // MOVL W^16(R1), R3
+            0xD0, 0xE1, 0x20, 0x00, 0x00, 0x00, 0x53,       // MOVL L^32(R1), R3
+            0xD0, 0xB1, 0x08, 0x53,                         // MOVL @B^8(R1), R3
+            0xD0, 0x8F, 0x78, 0x56, 0x34, 0x12, 0x53,       // MOVL #^X12345678, R3
+            0xD0, 0x9F, 0x00, 0x11, 0x00, 0x00, 0x53,       // MOVL @#^X1100, R3
+            0xD0, 0xCF, 0xEA, 0x07, 0x53,                   // MOVL W^^X1010, R3 (PC relative)
+            0xD0, 0x42, 0x61, 0x53,                         // MOVL (R1)[R2], R3
+            0xD0, 0x42, 0xA1, 0x04, 0x53,                   // MOVL B^4(R1)[R2], R3
+            0xD0, 0x42, 0x81, 0x53,                         // MOVL (R1)+[R2], R3
+            0xD0, 0x42, 0x71, 0x53,                         // MOVL -(R1)[R2], R3
+            0xD0, 0x42, 0x91, 0x53,                         // MOVL @(R1)+[R2], R3
+            0xD0, 0x42, 0x9F, 0x00, 0x11, 0x00, 0x00, 0x53, // MOVL @#^X1100[R2], R3
+            0xD0, 0x53, 0x42, 0xB1, 0x04,                   // MOVL R3, @B^4(R1)[R2]
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        for i in 0..0x48_u32 {
+            let at = 0x1000 + i as usize * 4;
+            bus.ram_mut()[at..at + 4].copy_from_slice(&(0xA000 + i * 4).to_le_bytes());
+        }
+        bus.ram_mut()[0x1000..0x1004].copy_from_slice(&0x1100_u32.to_le_bytes());
+        bus.ram_mut()[0x1008..0x100C].copy_from_slice(&0x1100_u32.to_le_bytes());
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_r1(0x1000);
+        cpu.regfile.set_r2(2);
+        cpu.regfile.set_pc(0x800);
+
+        let step = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>| {
+            cpu.run_tick().unwrap();
+            cpu.regfile.get_r3()
+        };
+        assert_eq!(step(&mut cpu), 0xA004);
+        assert_eq!(step(&mut cpu), 0xA010);
+        assert_eq!(step(&mut cpu), 0xA020);
+        assert_eq!(step(&mut cpu), 0xA100);
+        assert_eq!(step(&mut cpu), 0x1234_5678);
+        assert_eq!(step(&mut cpu), 0xA100);
+        assert_eq!(step(&mut cpu), 0xA010);
+        assert_eq!(step(&mut cpu), 0x1100);
+        assert_eq!(step(&mut cpu), 0xA00C);
+        assert_eq!(step(&mut cpu), 0x1100);
+        assert_eq!(cpu.regfile.get_r1(), 0x1004);
+        assert_eq!(step(&mut cpu), 0x1100);
+        assert_eq!(cpu.regfile.get_r1(), 0x1000);
+        assert_eq!(step(&mut cpu), 0xA108);
+        assert_eq!(cpu.regfile.get_r1(), 0x1004);
+        assert_eq!(step(&mut cpu), 0xA108);
+        step(&mut cpu);
+        assert_eq!(cpu.read_val::<u32>(0x1108).unwrap(), 0xA108);
+        assert_eq!(cpu.regfile.get_pc(), 0x800 + prog.len() as u32);
+
+        // Reserved combinations: PC as the index, indexing an autoincrement
+        // by its own register, indexed registers and registers running into PC.
+        let bad: &[&[u8]] = &[
+            &[0x4F, 0x61, 0x53],
+            &[0x41, 0x81, 0x53],
+            &[0x42, 0x51, 0x53],
+            &[0x42, 0x42, 0x61],
+            &[0x5F, 0x53],
+            &[0x6F, 0x53],
+        ];
+        for ops in bad {
+            cpu.write_block(0x900, ops).unwrap();
+            cpu.regfile.set_pc(0x900);
+            let e = arith::instr_mov::<_, u32>(&mut cpu, &mut Cycles(0)).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::ReservedAddressingMode);
+        }
+        cpu.write_block(0x900, &[0x5E, 0x50]).unwrap();
+        cpu.regfile.set_pc(0x900);
+        let e = arith::instr_mov::<_, u64>(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedAddressingMode);
+    }
+
+    #[test]
+    pub fn access_type_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        type Instr = fn(&mut crate::cpu::VAXCPU<crate::bus::RAMBus>, &mut Cycles) -> Result<(), crate::Error>;
+        let cases: &[(Instr, &[u8])] = &[
+            (misc::instr_clr::<_, u32>, &[0x05]),              // CLRL S^#5
+            (arith::instr_inc::<_, u32>, &[0x01]),             // INCL S^#1
+            (misc::instr_mova::<_, u32>, &[0x51, 0x52]),       // MOVAL R1, R2
+            (control::instr_jmp, &[0x51]),                     // JMP R1
+            (bitfield::instr_extzv, &[0x00, 0x08, 0x03, 0x52]), // EXTZV S^#0, S^#8, S^#3, R2
+        ];
+        for (instr, ops) in cases {
+            cpu.write_block(0x900, ops).unwrap();
+            cpu.regfile.set_pc(0x900);
+            let e = instr(&mut cpu, &mut Cycles(0)).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::ReservedAddressingMode);
+        }
+
+        // Registers are fine as bit field bases, and addresses come from any
+        // memory mode.
+        cpu.regfile.set_r1(0xABCD);
+        cpu.write_block(0x900, &[0x04, 0x08, 0x51, 0x52]).unwrap();
+        cpu.regfile.set_pc(0x900);
+        bitfield::instr_extzv(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r2(), 0xBC);
+        cpu.write_block(0x900, &[0x41, 0xA1, 0x10, 0x52]).unwrap();
+        cpu.regfile.set_pc(0x900);
+        misc::instr_mova::<_, u32>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r2(), 0xABCD + 0x10 + 4 * 0xABCD);
+    }
+
+    #[test]
+    pub fn loop_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0xD6, 0x52,                         // INCL R2
+            0xF2, 0x05, 0x51, 0xFA,             // AOBLSS S^#5, R1, .-6
+            0xF5, 0x51, 0xFD,                   // SOBGTR R1, .-3
+            0x3D, 0x0A, 0x02, 0x53, 0xFA, 0xFF, // ACBW S^#10, S^#2, R3, .-6
+            0x9D, 0x00, 0x54, 0x55, 0xFA, 0xFF, // ACBB S^#0, R4, R5, .-6
+            0xF3, 0x00, 0x56, 0x10,             // AOBLEQ S^#0, R6, .+0x10
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_r4(0xFF);
+        cpu.regfile.set_r5(3);
+        cpu.regfile.set_pc(0x800);
+
+        let run_to = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>, pc: u32| {
+            let mut ticks = 0;
+            while cpu.regfile.get_pc() != pc {
+                cpu.run_tick().unwrap();
+                ticks += 1;
+            }
+            ticks
+        };
+        assert_eq!(run_to(&mut cpu, 0x80F), 10 + 5 + 6);
+        assert_eq!(cpu.regfile.get_r1(), 0);
+        assert_eq!(cpu.regfile.get_r2(), 5);
+        assert_eq!(cpu.regfile.get_r3(), 12);
+
+        // The loops leave C alone.
+        cpu.regfile.get_psl_mut().set_c(true);
+        assert_eq!(run_to(&mut cpu, 0x815), 4);
+        assert_eq!(cpu.regfile.get_r5(), 0xFF);
+        assert!(cpu.regfile.get_psl().get_c());
+        assert!(cpu.regfile.get_psl().get_n());
+
+        // Overflow still branches, then traps if enabled.
+        cpu.regfile.set_psl(PSL(0x20));
+        cpu.regfile.set_r6(0x7FFF_FFFF);
+        cpu.regfile.set_pc(0x816);
+        let e = control::instr_aobleq(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::IntegerOverflow);
+        assert_eq!(cpu.regfile.get_r6(), 0x8000_0000);
+        assert_eq!(cpu.regfile.get_pc(), 0x819 + 0x10);
+    }
+
+    #[test]
+    pub fn extended_float_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // MULG3 S^#1.5, R0, R2 ; CVTGH R2, R4 ; CVTHF R4, R8 ; CMPH R4, S^#3.0
+        bus.ram_mut()[0x800..0x811].copy_from_slice(&[
+            0xFD, 0x45, 0x0C, 0x50, 0x52,
+            0xFD, 0x56, 0x52, 0x54,
+            0xFD, 0xF6, 0x54, 0x58,
+            0xFD, 0x71, 0x54, 0x14,
+        ]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r0(0x4020); // 2.0
+
+        for _ in 0..4 {
+            cpu.run_tick().unwrap();
+        }
+        assert_eq!((cpu.regfile.get_r2(), cpu.regfile.get_r3()), (0x4028, 0));
+        assert_eq!(cpu.regfile.get_r4(), 0x8000_4002);
+        assert_eq!(cpu.regfile.get_r8(), 0x4140);
+        assert!(cpu.regfile.get_psl().get_z());
+        assert_eq!(cpu.regfile.get_pc(), 0x811);
+    }
+
+    #[test]
+    pub fn emod_poly_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // EMODF R1, R2, R3, R4, R5
+        bus.ram_mut()[0x800..0x806].copy_from_slice(&[0x54, 0x51, 0x52, 0x53, 0x54, 0x55]);
+        // POLYF R1, S^#2, (R6) ; POLYD R8, S^#2, (R7)
+        bus.ram_mut()[0x900..0x908].copy_from_slice(&[0x55, 0x51, 0x02, 0x66, 0x75, 0x58, 0x02, 0x67]);
+        for (i, c) in [0x4100_u32, 0x4140, 0x4080].iter().enumerate() {
+            bus.ram_mut()[0x1000 + 4 * i..0x1004 + 4 * i].copy_from_slice(&c.to_le_bytes());
+            bus.ram_mut()[0x1100 + 8 * i..0x1104 + 8 * i].copy_from_slice(&c.to_le_bytes());
+        }
+        // POLYG R8, S^#2, (R10) ; POLYH R8, S^#2, (R6)
+        bus.ram_mut()[0x920..0x92A].copy_from_slice(&[0xFD, 0x55, 0x58, 0x02, 0x6A, 0xFD, 0x75, 0x58, 0x02, 0x66]);
+        // EMODG R0, R2, R6, R8, R10 ; EMODH (R6), R4, (R7), R5, (R8)
+        bus.ram_mut()[0xA00..0xA0E].copy_from_slice(&[
+            0xFD, 0x54, 0x50, 0x52, 0x56, 0x58, 0x5A,
+            0xFD, 0x74, 0x66, 0x54, 0x67, 0x55, 0x68,
+        ]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+
+        let emodf = |cpu: &mut VAXCPU<_>, mulr, mulrx, muld| {
+            cpu.regfile.set_pc(0x801);
+            cpu.regfile.set_r1(mulr);
+            cpu.regfile.set_r2(mulrx);
+            cpu.regfile.set_r3(muld);
+            float::instr_emod::<_, FFloat>(cpu, &mut Cycles(0)).unwrap();
+            (cpu.regfile.get_r4(), cpu.regfile.get_r5())
+        };
+        // 3.0 * 2.75 = 8 + 0.25
+        assert_eq!(emodf(&mut cpu, 0x4140, 0, 0x4130), (8, 0x3F80));
+        assert_eq!(emodf(&mut cpu, 0x4140, 0, 0xC130), (-8_i32 as u32, 0xBF80));
+        assert!(cpu.regfile.get_psl().get_n());
+        // The extension adds 2^-25 to the multiplier, which 2^24 turns into 0.5.
+        assert_eq!(emodf(&mut cpu, 0x4080, 0x40, 0x4C80), (0x100_0000, 0x4000));
+
+        // 2x^2 + 3x + 1 at x = 2.
+        cpu.regfile.set_pc(0x901);
+        cpu.regfile.set_r1(0x4100);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1100);
+        float::instr_polyf(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r0(), 0x4270);
+        assert_eq!(cpu.regfile.get_r3(), 0x100C);
+
+        cpu.regfile.write_gpr_ext::<u64>(8, 0x4100);
+        cpu.regfile.set_r5(0xDEAD);
+        cpu.regfile.set_pc(0x905);
+        float::instr_polyq::<_, DFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.read_gpr_ext::<u64>(0), 0x4270);
+        assert_eq!((cpu.regfile.get_r3(), cpu.regfile.get_r5()), (0x1118, 0));
+
+        // The same in G and H.
+        for (i, (g, h)) in [(0x4020_u64, 0x4002_u128), (0x4028, 0x8000_4002), (0x4010, 0x4001)]
+            .iter().enumerate()
+        {
+            cpu.write_val(0x1200 + 8 * i as u32, *g).unwrap();
+            cpu.write_val(0x1300 + 16 * i as u32, *h).unwrap();
+        }
+        cpu.regfile.write_gpr_ext::<u64>(8, 0x4020);
+        cpu.regfile.set_r10(0x1200);
+        cpu.regfile.set_pc(0x922);
+        float::instr_polyq::<_, GFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.read_gpr_ext::<u64>(0), 0x404E);
+        assert_eq!(cpu.regfile.get_r3(), 0x1218);
+
+        cpu.regfile.write_gpr_ext::<u128>(8, 0x4002);
+        cpu.regfile.set_r6(0x1300);
+        cpu.regfile.set_pc(0x927);
+        float::instr_polyh(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.read_gpr_ext::<u128>(0), 0xE000_4004);
+        assert_eq!((cpu.regfile.get_r4(), cpu.regfile.get_r5()), (0, 0x1330));
+
+        // 3.0 * 2.75 = 8 + 0.25
+        cpu.regfile.write_gpr_ext::<u64>(0, 0x4028);
+        cpu.regfile.set_r2(0);
+        cpu.regfile.write_gpr_ext::<u64>(6, 0x4026);
+        cpu.regfile.set_pc(0xA02);
+        float::instr_emod::<_, GFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r8(), 8);
+        assert_eq!(cpu.regfile.read_gpr_ext::<u64>(10), 0x3FF0);
+        // The extension's high 11 bits put 2^-54 below 0.5, which 2^31 turns
+        // into 2^-23. The low five bits are ignored.
+        cpu.regfile.write_gpr_ext::<u64>(0, 0x4000);
+        cpu.regfile.set_r2(0x801F);
+        cpu.regfile.write_gpr_ext::<u64>(6, 0x4200);
+        cpu.regfile.set_pc(0xA02);
+        float::instr_emod::<_, GFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r8(), 0x4000_0000);
+        assert_eq!(cpu.regfile.read_gpr_ext::<u64>(10), 0x3EA0);
+
+        cpu.regfile.set_r6(0x1400);
+        cpu.regfile.set_r7(0x1410);
+        cpu.regfile.set_r8(0x1420);
+        cpu.write_val(0x1400u32, 0x8000_4002_u128).unwrap();
+        cpu.write_val(0x1410u32, 0x6000_4002_u128).unwrap();
+        cpu.regfile.set_r4(0);
+        cpu.regfile.set_pc(0xA09);
+        float::instr_emod::<_, HFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r5(), 8);
+        assert_eq!(cpu.read_val::<u128>(0x1420).unwrap(), 0x3FFF);
+        // With 15 extension bits, 2^-114 below 0.5, and bit 0 ignored.
+        cpu.write_val(0x1400u32, 0x4000_u128).unwrap();
+        cpu.write_val(0x1410u32, 0x4020_u128).unwrap();
+        cpu.regfile.set_r4(0x8001);
+        cpu.regfile.set_pc(0xA09);
+        float::instr_emod::<_, HFloat>(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r5(), 0x4000_0000);
+        assert_eq!(cpu.read_val::<u128>(0x1420).unwrap(), 0x3FAE);
+
+        cpu.write_val::<u8>(0x923, 32).unwrap();
+        cpu.regfile.set_pc(0x922);
+        let e = float::instr_polyq::<_, GFloat>(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        cpu.write_val::<u8>(0x902, 32).unwrap();
+        cpu.regfile.set_pc(0x901);
+        let e = float::instr_polyf(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn decimal_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // ADDP4 S^#3, (R6), S^#5, (R7)
+        bus.ram_mut()[0x800..0x805].copy_from_slice(&[0x20, 0x03, 0x66, 0x05, 0x67]);
+        // MULP S^#3, (R6), S^#3, (R6), S^#3, (R8) ; DIVP S^#1, (R9), S^#3, (R6), S^#3, (R8)
+        bus.ram_mut()[0x805..0x813].copy_from_slice(&[
+            0x25, 0x03, 0x66, 0x03, 0x66, 0x03, 0x68,
+            0x27, 0x01, 0x69, 0x03, 0x66, 0x03, 0x68,
+        ]);
+        bus.ram_mut()[0x1000..0x1002].copy_from_slice(&[0x12, 0x3C]); // 123
+        bus.ram_mut()[0x1010..0x1013].copy_from_slice(&[0x00, 0x45, 0x6D]); // -456
+        bus.ram_mut()[0x1030] = 0x0C; // 0
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1010);
+        cpu.regfile.set_r8(0x1020);
+        cpu.regfile.set_r9(0x1030);
+
+        // Interrupt the addition after decoding, then pick it back up.
+        cpu.run_tick().unwrap();
+        suspend_multi_instruction(&mut cpu, 0x800);
+        assert!(cpu.regfile.get_psl().get_fpd());
+        assert_eq!(cpu.regfile.get_pc(), 0x800);
+        cpu.run_tick().unwrap();
+        assert!(!cpu.regfile.get_psl().get_fpd());
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.read_phys::<[u8; 3]>(0x1010).unwrap(), [0x00, 0x33, 0x3D]);
+        assert_eq!(cpu.regfile.get_pc(), 0x805);
+        assert_eq!(
+            (cpu.regfile.get_r0(), cpu.regfile.get_r1(), cpu.regfile.get_r2(), cpu.regfile.get_r3()),
+            (0, 0x1000, 0, 0x1010)
+        );
+        assert!(cpu.regfile.get_psl().get_n());
+
+        // 123 * 123 doesn't fit in three digits.
+        cpu.regfile.get_psl_mut().set_dv(true);
+        cpu.run_tick().unwrap();
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::DecimalOverflow);
+        assert_eq!(cpu.read_phys::<[u8; 2]>(0x1020).unwrap(), [0x12, 0x9C]);
+        assert!(cpu.regfile.get_psl().get_v());
+        assert_eq!(cpu.multi_instr_active, MultiInstruction::None);
+
+        cpu.run_tick().unwrap();
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::DecimalDivZero);
+        assert_eq!(cpu.regfile.get_pc(), 0x813);
+    }
+
+    #[test]
+    pub fn decimal_conversion_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        bus.ram_mut()[0x800..0x81E].copy_from_slice(&[
+            0xF9, 0x51, 0x05, 0x66, // CVTLP R1, S^#5, (R6)
+            0x08, 0x05, 0x66, 0x06, 0x67, // CVTPS S^#5, (R6), S^#6, (R7)
+            0x09, 0x06, 0x67, 0x03, 0x68, // CVTSP S^#6, (R7), S^#3, (R8)
+            0x36, 0x05, 0x66, 0x59, // CVTPL S^#5, (R6), R9
+            0x24, 0x05, 0x66, 0x6A, 0x04, 0x67, // CVTPT S^#5, (R6), (R10), S^#4, (R7)
+            0x26, 0x04, 0x67, 0x6B, 0x05, 0x68, // CVTTP S^#4, (R7), (R11), S^#5, (R8)
+        ]);
+        bus.ram_mut()[0x1100 + 0x4D] = b'M';
+        bus.ram_mut()[0x1200 + b'M' as usize] = 0x4D;
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_r1(-1234_i32 as u32);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1010);
+        cpu.regfile.set_r8(0x1020);
+        cpu.regfile.set_r10(0x1100);
+        cpu.regfile.set_r11(0x1200);
+
+        cpu.regfile.set_pc(0x801);
+        decimal::instr_cvtlp(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.read_phys::<[u8; 3]>(0x1000).unwrap(), [0x01, 0x23, 0x4D]);
+        assert_eq!((cpu.regfile.get_r1(), cpu.regfile.get_r3()), (0, 0x1000));
+        assert!(cpu.regfile.get_psl().get_n());
+
+        cpu.regfile.set_pc(0x805);
+        decimal::instr_cvtps(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(&cpu.read_phys::<[u8; 7]>(0x1010).unwrap(), b"-001234");
+
+        cpu.regfile.set_pc(0x80A);
+        decimal::instr_cvtsp(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.read_phys::<[u8; 2]>(0x1020).unwrap(), [0x23, 0x4D]);
+        assert!(cpu.regfile.get_psl().get_v());
+
+        cpu.regfile.set_pc(0x80F);
+        decimal::instr_cvtpl(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.regfile.get_r9(), -1234_i32 as u32);
+        assert!(!cpu.regfile.get_psl().get_v());
+
+        cpu.regfile.set_pc(0x813);
+        decimal::instr_cvtpt(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(&cpu.read_phys::<[u8; 4]>(0x1010).unwrap(), b"123M");
+
+        cpu.regfile.set_pc(0x819);
+        decimal::instr_cvttp(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.read_phys::<[u8; 3]>(0x1020).unwrap(), [0x01, 0x23, 0x4D]);
+        assert_eq!(cpu.regfile.get_pc(), 0x81E);
+
+        // Numeric strings with anything but digits are reserved operands.
+        cpu.write_val(0x1010, b'x').unwrap();
+        cpu.regfile.set_pc(0x819);
+        let e = decimal::instr_cvttp(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn editpc_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // EDITPC S^#5, (R6), (R7), (R8) ; EDITPC S^#3, (R9), (R10), (R11)
+        bus.ram_mut()[0x800..0x80A].copy_from_slice(&[
+            0x38, 0x05, 0x66, 0x67, 0x68,
+            0x38, 0x03, 0x69, 0x6A, 0x6B,
+        ]);
+        bus.ram_mut()[0x1000..0x1003].copy_from_slice(&[0x12, 0x34, 0x5D]); // -12345
+        // EO$ADJUST_INPUT 7, EO$FLOAT 5, EO$INSERT '.', EO$MOVE 2, EO$END
+        bus.ram_mut()[0x1010..0x1018].copy_from_slice(&[0x47, 7, 0xA5, 0x44, b'.', 0x92, 0x00, 0x00]);
+        bus.ram_mut()[0x1030..0x1032].copy_from_slice(&[0x00, 0x0D]); // -0
+        // EO$LOAD_FILL '*', EO$SET_SIGNIF, EO$MOVE 3, EO$BLANK_ZERO 3, EO$END
+        bus.ram_mut()[0x1040..0x1047].copy_from_slice(&[0x40, b'*', 0x03, 0x93, 0x45, 3, 0x00]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1010);
+        cpu.regfile.set_r8(0x1020);
+        cpu.regfile.set_r9(0x1030);
+        cpu.regfile.set_r10(0x1040);
+        cpu.regfile.set_r11(0x1050);
+
+        cpu.run_tick().unwrap();
+        suspend_multi_instruction(&mut cpu, 0x800);
+        assert!(cpu.regfile.get_psl().get_fpd());
+        cpu.run_tick().unwrap();
+        cpu.run_tick().unwrap();
+        assert_eq!(&cpu.read_phys::<[u8; 9]>(0x1020).unwrap(), b"  -123.45");
+        assert_eq!(cpu.regfile.get_pc(), 0x805);
+        assert_eq!(
+            (cpu.regfile.get_r0(), cpu.regfile.get_r1(), cpu.regfile.get_r2(),
+             cpu.regfile.get_r3(), cpu.regfile.get_r4(), cpu.regfile.get_r5()),
+            (5, 0x1000, 0, 0x1016, 0, 0x1029)
+        );
+        let psl = cpu.regfile.get_psl();
+        assert!(psl.get_n() && !psl.get_z() && !psl.get_v() && psl.get_c());
+
+        // Negative zero comes out positive, and blanked.
+        cpu.run_tick().unwrap();
+        cpu.run_tick().unwrap();
+        assert_eq!(&cpu.read_phys::<[u8; 3]>(0x1050).unwrap(), b"***");
+        let psl = cpu.regfile.get_psl();
+        assert!(!psl.get_n() && psl.get_z());
+        assert_eq!(cpu.regfile.get_pc(), 0x80A);
+
+        // Unknown operators and leftover digits are reserved operands.
+        cpu.write_val(0x1044u32, 0xFFu8).unwrap();
+        cpu.regfile.set_pc(0x805);
+        cpu.run_tick().unwrap();
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        cpu.multi_instr_active = MultiInstruction::None;
+        cpu.write_val(0x1043u32, 0x92u8).unwrap();
+        cpu.write_val(0x1044u32, 0x00u8).unwrap();
+        cpu.regfile.set_pc(0x805);
+        cpu.run_tick().unwrap();
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        cpu.multi_instr_active = MultiInstruction::None;
+
+        // Losing significant digits with DV set traps once the edit is done.
+        // EO$ADJUST_INPUT 3, EO$MOVE 3, EO$END
+        cpu.write_block(0x1060, &[0x47, 3, 0x93, 0x00]).unwrap();
+        cpu.regfile.set_psl(PSL(0x80));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1060);
+        cpu.regfile.set_r8(0x1070);
+        cpu.run_tick().unwrap();
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::DecimalOverflow);
+        assert_eq!(&cpu.read_phys::<[u8; 3]>(0x1070).unwrap(), b"345");
+        assert!(cpu.regfile.get_psl().get_v());
+        assert_eq!(cpu.multi_instr_active, MultiInstruction::None);
+
+        // A source string wrapping past the top of memory.
+        cpu.write_val(0x1u32, 0x0Cu8).unwrap();
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r6(0xFFFF_FFFF);
+        cpu.regfile.set_r7(0x1010);
+        cpu.run_tick().unwrap();
+        exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap();
+        assert_eq!(cpu.multi_instr_active, MultiInstruction::None);
+    }
+
+    #[test]
+    pub fn string_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0x28, 0x08, 0x66, 0x67, // MOVC3 S^#8, (R6), (R7)
+            0x2C, 0x03, 0x66, 0x2A, 0x06, 0x68, // MOVC5 S^#3, (R6), S^#'*', S^#6, (R8)
+            0x29, 0x04, 0x66, 0x68, // CMPC3 S^#4, (R6), (R8)
+            0x2D, 0x03, 0x66, 0x2A, 0x06, 0x68, // CMPC5 S^#3, (R6), S^#'*', S^#6, (R8)
+            0x3A, 0x8F, b'C', 0x0A, 0x66, // LOCC I^#'C', S^#10, (R6)
+            0x3B, 0x8F, b'A', 0x0A, 0x66, // SKPC I^#'A', S^#10, (R6)
+            0x2A, 0x0A, 0x66, 0x69, 0x01, // SCANC S^#10, (R6), (R9), S^#1
+            0x2B, 0x0A, 0x66, 0x69, 0x02, // SPANC S^#10, (R6), (R9), S^#2
+            0x39, 0x02, 0x6A, 0x0A, 0x66, // MATCHC S^#2, (R10), S^#10, (R6)
+            0x2E, 0x03, 0x66, 0x2E, 0x6B, 0x05, 0x68, // MOVTC S^#3, (R6), S^#'.', (R11), S^#5, (R8)
+            0x2F, 0x0A, 0x66, 0x21, 0x6B, 0x0A, 0x68, // MOVTUC S^#10, (R6), S^#'!', (R11), S^#10, (R8)
+            0x2C, 0x00, 0x66, 0x2A, 0x8F, 0x00, 0x03, 0x6C, // MOVC5 S^#0, (R6), S^#'*', I^#0x300, (R12)
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        bus.ram_mut()[0x1000..0x1008].copy_from_slice(b"ABCDEFGH");
+        bus.ram_mut()[0x1030..0x1032].copy_from_slice(b"CD");
+        bus.ram_mut()[0x1100 + b'A' as usize] = 2;
+        bus.ram_mut()[0x1100 + b'B' as usize] = 2;
+        bus.ram_mut()[0x1100 + b'D' as usize] = 1;
+        bus.ram_mut()[0x1200 + b'A' as usize] = b'a';
+        bus.ram_mut()[0x1200 + b'B' as usize] = b'b';
+        bus.ram_mut()[0x1200 + b'C' as usize] = b'!';
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(0x1002);
+        cpu.regfile.set_r8(0x1020);
+        cpu.regfile.set_r9(0x1100);
+        cpu.regfile.set_r10(0x1030);
+        cpu.regfile.set_r11(0x1200);
+        cpu.regfile.set_r12(0x1400);
+
+        fn run(cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>) -> [u32; 6] {
+            cpu.run_tick().unwrap();
+            while cpu.multi_instr_active != MultiInstruction::None {
+                cpu.run_tick().unwrap();
+            }
+            let r = &cpu.regfile;
+            [r.get_r0(), r.get_r1(), r.get_r2(), r.get_r3(), r.get_r4(), r.get_r5()]
+        }
+
+        // The copy has to go backwards to survive the overlap.
+        assert_eq!(run(&mut cpu), [0, 0x1008, 0, 0x100A, 0, 0]);
+        assert_eq!(&cpu.read_phys::<[u8; 10]>(0x1000).unwrap(), b"ABABCDEFGH");
+        assert!(cpu.regfile.get_psl().get_z());
+
+        assert_eq!(run(&mut cpu), [0, 0x1003, 0, 0x1026, 0, 0]);
+        assert_eq!(&cpu.read_phys::<[u8; 6]>(0x1020).unwrap(), b"ABA***");
+        let psl = cpu.regfile.get_psl();
+        assert!(psl.get_n() && psl.get_c() && !psl.get_z());
+
+        assert_eq!(run(&mut cpu)[..4], [1, 0x1003, 1, 0x1023]);
+        let psl = cpu.regfile.get_psl();
+        assert!(!psl.get_n() && !psl.get_c() && !psl.get_z());
+
+        assert_eq!(run(&mut cpu)[..4], [0, 0x1003, 0, 0x1026]);
+        assert!(cpu.regfile.get_psl().get_z());
+
+        assert_eq!(run(&mut cpu)[..2], [6, 0x1004]);
+        assert!(!cpu.regfile.get_psl().get_z());
+        assert_eq!(run(&mut cpu)[..2], [9, 0x1001]);
+        assert_eq!(run(&mut cpu)[..4], [5, 0x1005, 0, 0x1100]);
+        assert_eq!(run(&mut cpu)[..4], [6, 0x1004, 0, 0x1100]);
+
+        assert_eq!(run(&mut cpu)[..4], [0, 0x1032, 4, 0x1006]);
+        assert!(cpu.regfile.get_psl().get_z());
+
+        assert_eq!(run(&mut cpu), [0, 0x1003, 0, 0x1200, 0, 0x1025]);
+        assert_eq!(&cpu.read_phys::<[u8; 5]>(0x1020).unwrap(), b"aba..");
+
+        // MOVTUC stops short at the escape.
+        assert_eq!(run(&mut cpu), [6, 0x1004, 0, 0x1200, 6, 0x1024]);
+        assert_eq!(&cpu.read_phys::<[u8; 5]>(0x1020).unwrap(), b"abab.");
+        let psl = cpu.regfile.get_psl();
+        assert!(psl.get_v() && psl.get_z());
+
+        // Long strings go in chunks, and can be interrupted between them.
+        cpu.run_tick().unwrap();
+        cpu.run_tick().unwrap();
+        assert_ne!(cpu.regfile.get_r4(), 0);
+        suspend_multi_instruction(&mut cpu, 0x83B);
+        assert_eq!(run(&mut cpu), [0, 0x1000, 0, 0x1700, 0, 0]);
+        assert_eq!(cpu.read_phys::<[u8; 2]>(0x16FF).unwrap(), [b'*', 0]);
+        assert_eq!(cpu.read_phys::<u8>(0x1400).unwrap(), b'*');
+        assert_eq!(cpu.regfile.get_pc(), 0x843);
+    }
+
+    #[test]
+    pub fn bitfield_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0xEE, 0x04, 0x08, 0x51, 0x52, // EXTV S^#4, S^#8, R1, R2
+            0xEF, 0x1C, 0x08, 0x53, 0x55, // EXTZV S^#28, S^#8, R3, R5
+            0xF0, 0x56, 0x0C, 0x10, 0x67, // INSV R6, S^#12, S^#16, (R7)
+            0xEC, 0x0C, 0x10, 0x67, 0x8F, 0xEF, 0xBE, 0xFF, 0xFF, // CMPV S^#12, S^#16, (R7), I^#-0x4111
+            0xED, 0x0C, 0x10, 0x67, 0x01, // CMPZV S^#12, S^#16, (R7), S^#1
+            0xEA, 0x00, 0x20, 0x67, 0x58, // FFS S^#0, S^#32, (R7), R8
+            0xEB, 0x0C, 0x08, 0x67, 0x59, // FFC S^#12, S^#8, (R7), R9
+            0xEF, 0x5A, 0x08, 0x6C, 0x5B, // EXTZV R10, S^#8, (R12), R11
+            0xEE, 0x00, 0x21, 0x51, 0x52, // EXTV S^#0, S^#33, R1, R2
+            0xEE, 0x20, 0x01, 0x51, 0x52, // EXTV S^#32, S^#1, R1, R2
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_pc(0x800);
+        cpu.regfile.set_r1(0x0000_0F50);
+        cpu.regfile.set_r3(0x5000_0000);
+        cpu.regfile.set_r4(0x0000_000A);
+        cpu.regfile.set_r6(0xBEEF);
+        cpu.regfile.set_r7(0x1000);
+        cpu.regfile.set_r10(-4_i32 as u32);
+        cpu.regfile.set_r12(0x1002);
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r2(), 0xFFFF_FFF5);
+        assert!(cpu.regfile.get_psl().get_n());
+
+        // Register fields run over into the next register.
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r5(), 0xA5);
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.read_phys::<u32>(0x1000).unwrap(), 0x0BEE_F000);
+
+        cpu.run_tick().unwrap();
+        assert!(cpu.regfile.get_psl().get_z());
+        cpu.run_tick().unwrap();
+        let psl = cpu.regfile.get_psl();
+        assert!(!psl.get_z() && !psl.get_n() && !psl.get_c());
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r8(), 12);
+        assert!(!cpu.regfile.get_psl().get_z());
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r9(), 16);
+
+        // Memory positions can be negative.
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r11(), 0xEF);
+
+        cpu.regfile.set_pc(0x82D);
+        let e = bitfield::instr_extv(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        cpu.regfile.set_pc(0x832);
+        let e = bitfield::instr_extv(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn branch_on_bit_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0xE0, 0x03, 0x51, 0x10, // BBS S^#3, R1, .+0x10
+            0xE1, 0x03, 0x51, 0x10, // BBC S^#3, R1, .+0x10
+            0xE4, 0x03, 0x51, 0x10, // BBSC S^#3, R1, .+0x10
+            0xE3, 0x23, 0x66, 0x10, // BBCS S^#35, (R6), .+0x10
+            0xE6, 0x23, 0x66, 0x10, // BBSSI S^#35, (R6), .+0x10
+            0xE7, 0x57, 0x66, 0x10, // BBCCI R7, (R6), .+0x10
+            0xE0, 0x20, 0x51, 0x10, // BBS S^#32, R1, .+0x10
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        bus.ram_mut()[0xFFF] = 0x80;
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_r1(0x8);
+        cpu.regfile.set_r6(0x1000);
+        cpu.regfile.set_r7(-1_i32 as u32);
+
+        let step = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>, pc: u32| {
+            cpu.regfile.set_pc(pc);
+            cpu.run_tick().unwrap();
+            cpu.regfile.get_pc()
+        };
+        assert_eq!(step(&mut cpu, 0x800), 0x814);
+        assert_eq!(step(&mut cpu, 0x804), 0x808);
+        assert_eq!(step(&mut cpu, 0x808), 0x81C);
+        assert_eq!(cpu.regfile.get_r1(), 0);
+        assert_eq!(step(&mut cpu, 0x80C), 0x820);
+        assert_eq!(cpu.read_phys::<u8>(0x1004).unwrap(), 0x08);
+        assert_eq!(step(&mut cpu, 0x810), 0x824);
+        assert_eq!(cpu.read_phys::<u8>(0x1004).unwrap(), 0x08);
+        assert_eq!(step(&mut cpu, 0x814), 0x818);
+        assert_eq!(cpu.read_phys::<u8>(0xFFF).unwrap(), 0);
+
+        cpu.regfile.set_pc(0x819);
+        let e = bitfield::instr_bbs(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn misc_instr_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0x0A, 0x05, 0x01, 0x0A, 0x04, 0x52, 0x53, // INDEX S^#5, S^#1, S^#10, S^#4, R2, R3
+            0x9C, 0x04, 0x52, 0x54,                   // ROTL S^#4, R2, R4
+            0x9C, 0x55, 0x52, 0x54,                   // ROTL R5, R2, R4
+            0xD9, 0x52, 0x56,                         // SBWC R2, R6
+            0x0B, 0x67, 0x58, 0x09, 0x69,             // CRC (R7), R8, S^#9, (R9)
+            0x0A, 0x0B, 0x01, 0x0A, 0x04, 0x52, 0x53, // INDEX S^#11, S^#1, S^#10, S^#4, R2, R3
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        // Nibble table for the usual CRC-32 polynomial.
+        for i in 0..16_u32 {
+            let mut crc = i;
+            for _ in 0..4 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            }
+            let at = 0x1000 + i as usize * 4;
+            bus.ram_mut()[at..at + 4].copy_from_slice(&crc.to_le_bytes());
+        }
+        bus.ram_mut()[0x1100..0x1109].copy_from_slice(b"123456789");
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_r2(0x8000_0002);
+        cpu.regfile.set_r5(0xFC);
+        cpu.regfile.set_pc(0x800);
+
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r3(), 0x1C);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r4(), 0x28);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r4(), 0x2800_0000);
+        assert!(!cpu.regfile.get_psl().get_n());
+
+        // 5 - 0x8000_0002 - 1 overflows and borrows.
+        cpu.regfile.set_r6(5);
+        cpu.regfile.get_psl_mut().set_c(true);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r6(), 0x8000_0002);
+        assert!(cpu.regfile.get_psl().get_c());
+        assert!(cpu.regfile.get_psl().get_v());
+        cpu.regfile.set_r2(0xFFFF_FFFF);
+        cpu.regfile.set_r6(0);
+        cpu.regfile.set_pc(0x80F);
+        cpu.run_tick().unwrap();
+        assert_eq!(cpu.regfile.get_r6(), 0);
+        assert!(cpu.regfile.get_psl().get_z());
+        assert!(cpu.regfile.get_psl().get_c());
+        assert!(!cpu.regfile.get_psl().get_v());
+
+        cpu.regfile.set_r7(0x1000);
+        cpu.regfile.set_r8(0xFFFF_FFFF);
+        cpu.regfile.set_r9(0x1100);
+        cpu.run_tick().unwrap();
+        assert_eq!(!cpu.regfile.get_r0(), 0xCBF4_3926);
+        assert_eq!(cpu.regfile.get_r3(), 0x1109);
+
+        // Out of range subscripts still store the result before trapping.
+        cpu.regfile.set_r2(0);
+        cpu.regfile.set_pc(0x818);
+        let e = arith::instr_index(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::SubscriptRange);
+        assert_eq!(cpu.regfile.get_r3(), 44);
+    }
+
+    #[test]
+    pub fn probe_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 page 0 -> PFN 4, supervisor readable, page 1 invalid but user readable.
+        bus.ram_mut()[0x1000..0x1004].copy_from_slice(&(0x8000_0000_u32 | (0b1010 << 27) | 4).to_le_bytes());
+        bus.ram_mut()[0x1004..0x1008].copy_from_slice(&(0b1111_u32 << 27).to_le_bytes());
+        bus.ram_mut()[0x800..0x808].copy_from_slice(&[
+            0x0C, 0x52, 0x04, 0x69, // PROBER R2, S^#4, (R9)
+            0x0D, 0x52, 0x04, 0x69, // PROBEW R2, S^#4, (R9)
+        ]);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        cpu.regfile.set_sbr(0x1000);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_mapen(true);
+
+        let probe = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>, pc: u32, mode: u32, prv: u8, addr: u32| {
+            cpu.regfile.set_pc(pc);
+            cpu.regfile.set_r2(mode);
+            cpu.regfile.set_r9(addr);
+            cpu.regfile.get_psl_mut().set_prv_mod(prv);
+            cpu.run_tick().unwrap();
+            !cpu.regfile.get_psl().get_z()
+        };
+        assert!(probe(&mut cpu, 0x8000_0000, 0, 0, 0x8000_0010));
+        assert!(probe(&mut cpu, 0x8000_0000, 2, 0, 0x8000_0010));
+        // The previous mode limits the probe, as does the operand.
+        assert!(!probe(&mut cpu, 0x8000_0000, 0, 3, 0x8000_0010));
+        assert!(!probe(&mut cpu, 0x8000_0000, 3, 0, 0x8000_0010));
+        // Valid bits don't matter, but both ends of the range do.
+        assert!(probe(&mut cpu, 0x8000_0000, 3, 0, 0x8000_0200));
+        assert!(!probe(&mut cpu, 0x8000_0000, 3, 0, 0x8000_01FE));
+        assert!(probe(&mut cpu, 0x8000_0000, 2, 0, 0x8000_01FE));
+        assert!(probe(&mut cpu, 0x8000_0004, 0, 0, 0x8000_0010));
+        assert!(!probe(&mut cpu, 0x8000_0004, 2, 0, 0x8000_0010));
+        assert!(!probe(&mut cpu, 0x8000_0000, 0, 0, 0x8000_0400));
+    }
+
+    #[test]
+    pub fn queue_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        let prog: &[u8] = &[
+            0x0E, 0x61, 0x62, // INSQUE (R1), (R2)
+            0x0F, 0x61, 0x50, // REMQUE (R1), R0
+            0x5C, 0x61, 0x62, // INSQHI (R1), (R2)
+            0x5D, 0x61, 0x62, // INSQTI (R1), (R2)
+            0x5E, 0x62, 0x50, // REMQHI (R2), R0
+            0x5F, 0x62, 0x50, // REMQTI (R2), R0
+            0x5C, 0x61, 0x44, 0x63, // INSQHI (R1), (R3)[R4]
+            0x5E, 0x83, 0x50, // REMQHI (R3)+, R0
+        ];
+        bus.ram_mut()[0x800..0x800 + prog.len()].copy_from_slice(prog);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_psl(PSL(0));
+        let run = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>, pc: u32, r1: u32, r2: u32| {
+            cpu.regfile.set_pc(pc);
+            cpu.regfile.set_r1(r1);
+            cpu.regfile.set_r2(r2);
+            cpu.run_tick().unwrap();
+            *cpu.regfile.get_psl()
+        };
+        let link = |cpu: &mut crate::cpu::VAXCPU<crate::bus::RAMBus>, addr: u32| {
+            cpu.read_val::<u32>(addr).unwrap()
+        };
+
+        // Absolute queue: header at 0x1000.
+        cpu.write_val::<u32>(0x1000, 0x1000).unwrap();
+        cpu.write_val::<u32>(0x1004, 0x1000).unwrap();
+        assert!(run(&mut cpu, 0x800, 0x1010, 0x1000).get_z());
+        assert!(!run(&mut cpu, 0x800, 0x1020, 0x1010).get_z());
+        assert_eq!(link(&mut cpu, 0x1000), 0x1010);
+        assert_eq!(link(&mut cpu, 0x1004), 0x1020);
+        assert_eq!(link(&mut cpu, 0x1014), 0x1000);
+        assert_eq!(link(&mut cpu, 0x1024), 0x1010);
+
+        let psl = run(&mut cpu, 0x803, 0x1010, 0);
+        assert!(!psl.get_z() && !psl.get_v());
+        assert_eq!(cpu.regfile.get_r0(), 0x1010);
+        assert_eq!(link(&mut cpu, 0x1000), 0x1020);
+        assert!(run(&mut cpu, 0x803, 0x1020, 0).get_z());
+        assert_eq!(link(&mut cpu, 0x1000), 0x1000);
+        assert!(run(&mut cpu, 0x803, 0x1000, 0).get_v());
+
+        // Self-relative queue: header at 0x1100, ends up 1130, 1110, 1120.
+        assert!(run(&mut cpu, 0x806, 0x1110, 0x1100).get_z());
+        assert!(!run(&mut cpu, 0x809, 0x1120, 0x1100).get_z());
+        assert!(!run(&mut cpu, 0x806, 0x1130, 0x1100).get_z());
+        assert_eq!(link(&mut cpu, 0x1100), 0x30);
+        assert_eq!(link(&mut cpu, 0x1104), 0x20);
+        assert_eq!(link(&mut cpu, 0x1130), -0x20_i32 as u32);
+        assert_eq!(link(&mut cpu, 0x1114), 0x20);
+        assert_eq!(link(&mut cpu, 0x1120), -0x20_i32 as u32);
+
+        assert!(!run(&mut cpu, 0x80F, 0, 0x1100).get_z());
+        assert_eq!(cpu.regfile.get_r0(), 0x1120);
+        assert!(!run(&mut cpu, 0x80C, 0, 0x1100).get_z());
+        assert_eq!(cpu.regfile.get_r0(), 0x1130);
+        assert_eq!(link(&mut cpu, 0x1100), 0x10);
+        assert_eq!(link(&mut cpu, 0x1104), 0x10);
+        let psl = run(&mut cpu, 0x80F, 0, 0x1100);
+        assert!(psl.get_z() && !psl.get_v());
+        assert_eq!(cpu.regfile.get_r0(), 0x1110);
+        assert_eq!(link(&mut cpu, 0x1100), 0);
+        assert_eq!(link(&mut cpu, 0x1104), 0);
+        let psl = run(&mut cpu, 0x80C, 0, 0x1100);
+        assert!(psl.get_z() && psl.get_v());
+        assert_eq!(cpu.regfile.get_r0(), 0x1100);
+
+        // Someone else holding the secondary interlock leaves the queue alone.
+        cpu.write_val::<u32>(0x1100, 1).unwrap();
+        let psl = run(&mut cpu, 0x806, 0x1110, 0x1100);
+        assert!(psl.get_c() && !psl.get_v());
+        let psl = run(&mut cpu, 0x80C, 0, 0x1100);
+        assert!(psl.get_c() && psl.get_v());
+        assert_eq!(link(&mut cpu, 0x1100), 1);
+        assert_eq!(link(&mut cpu, 0x1104), 0);
+
+        // Headers are quadwords, so indexing and autoincrement go by 8.
+        cpu.write_val::<u32>(0x1100, 0).unwrap();
+        cpu.regfile.set_r3(0x1000);
+        cpu.regfile.set_r4(0x20);
+        assert!(run(&mut cpu, 0x812, 0x1110, 0).get_z());
+        assert_eq!(link(&mut cpu, 0x1100), 0x10);
+        cpu.regfile.set_r3(0x1100);
+        let psl = run(&mut cpu, 0x816, 0, 0);
+        assert!(psl.get_z() && !psl.get_v());
+        assert_eq!(cpu.regfile.get_r0(), 0x1110);
+        assert_eq!(cpu.regfile.get_r3(), 0x1108);
+        assert_eq!(link(&mut cpu, 0x1100), 0);
+
+        // Unaligned headers and entries are reserved operands.
+        cpu.regfile.set_pc(0x807);
+        cpu.regfile.set_r1(0x1114);
+        let e = queue::instr_insqhi(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    pub fn interlocked_shared_bus_test() {
+        use emutk_core::bus::SharedBus;
+        use crate::bus::RAMBus;
+        use crate::cpu::VAXCPU;
+
+        let mut ram = RAMBus::new(8192);
+        ram.ram_mut()[0x800..0x810].copy_from_slice(&[
+            0xE6, 0x00, 0x66, 0xFC, // 1$: BBSSI S^#0, (R6), 1$
+            0xD6, 0x67,             //     INCL (R7)
+            0xE7, 0x00, 0x66, 0x00, //     BBCCI S^#0, (R6), .
+            0xD7, 0x58,             //     DECL R8
+            0x12, 0xF2,             //     BNEQ 1$
+            0x00,                   //     HALT
+            0x00,
+        ]);
+        let bus = SharedBus::new(ram);
+
+        // Two processors bump a counter under a spinlock at the same time.
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                let mut bus = bus.clone();
+                s.spawn(move || {
+                    let mut cpu = VAXCPU::new();
+                    cpu.give_bus(&mut bus);
+                    cpu.regfile.set_psl(PSL(0));
+                    cpu.regfile.set_pc(0x800);
+                    cpu.regfile.set_r6(0x1000);
+                    cpu.regfile.set_r7(0x1004);
+                    cpu.regfile.set_r8(1000);
+                    while !cpu.halted() {
+                        cpu.run_tick().unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(bus.lock().ram()[0x1004..0x1008], 2000_u32.to_le_bytes());
+        assert_eq!(bus.lock().ram()[0x1000], 0);
+    }
+
+    #[test]
+    pub fn interlocked_queue_shared_bus_test() {
+        use std::convert::TryInto;
+        use emutk_core::bus::SharedBus;
+        use crate::bus::RAMBus;
+        use crate::cpu::VAXCPU;
+
+        let mut ram = RAMBus::new(8192);
+        ram.ram_mut()[0x800..0x810].copy_from_slice(&[
+            0x5E, 0x66, 0x50, // 1$: REMQHI (R6), R0
+            0x1F, 0xFB,       //     BCS 1$
+            0x1D, 0x07,       //     BVS 3$
+            0x5D, 0x60, 0x67, // 2$: INSQTI (R0), (R7)
+            0x1F, 0xFB,       //     BCS 2$
+            0x11, 0xF2,       //     BRB 1$
+            0x00,             // 3$: HALT
+            0x00,
+        ]);
+        // Queue 64 entries on the header at 0x1000, leaving 0x1008 empty.
+        let entries = 64;
+        let entry = |i: u32| if i == 0 || i > entries { 0x1000 } else { 0x1100 + i * 8 };
+        for i in 0..=entries {
+            let e = entry(i);
+            let next = entry(i + 1);
+            let prev = if i == 0 { entry(entries) } else { entry(i - 1) };
+            let e_off = e as usize;
+            ram.ram_mut()[e_off..e_off + 4].copy_from_slice(&next.wrapping_sub(e).to_le_bytes());
+            ram.ram_mut()[e_off + 4..e_off + 8].copy_from_slice(&prev.wrapping_sub(e).to_le_bytes());
+        }
+        let bus = SharedBus::new(ram);
+
+        // Two processors move every entry from one queue to the other at once.
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                let mut bus = bus.clone();
+                s.spawn(move || {
+                    let mut cpu = VAXCPU::new();
+                    cpu.give_bus(&mut bus);
+                    cpu.regfile.set_psl(PSL(0));
+                    cpu.regfile.set_pc(0x800);
+                    cpu.regfile.set_r6(0x1000);
+                    cpu.regfile.set_r7(0x1008);
+                    while !cpu.halted() {
+                        cpu.run_tick().unwrap();
+                    }
+                });
+            }
+        });
+
+        let bus = bus.lock();
+        let link = |addr: u32| {
+            let a = addr as usize;
+            u32::from_le_bytes(bus.ram()[a..a + 4].try_into().unwrap())
+        };
+        assert_eq!((link(0x1000), link(0x1004)), (0, 0));
+        let mut seen = vec![];
+        let mut e = 0x1008_u32.wrapping_add(link(0x1008));
+        while e != 0x1008 {
+            seen.push(e);
+            e = e.wrapping_add(link(e));
+        }
+        seen.sort();
+        assert_eq!(seen, (1..=entries).map(entry).collect::<Vec<_>>());
+    }
+
+    #[test]
+    pub fn multi_none_test() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        cpu.give_bus(&mut bus);
+        assert_eq!(cpu.multi_instr_active, MultiInstruction::None);
+        let e = exec_multi_instructions(&mut cpu, &mut Cycles(0)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::MachineCheck);
+    }
+
+    #[test]
+    pub fn int_value_test() {
+        assert_eq!(util::int_value(0xFF_u8), -1);
+        assert_eq!(util::int_value(0x7FFF_u16), 0x7FFF);
+        assert_eq!(util::int_value(0x8000_0000_u32), i32::MIN as i128);
+        assert_eq!(util::int_value(0x8000_0000_0000_0000_u64), i64::MIN as i128);
+        assert_eq!(util::int_value(0x1234_5678_9ABC_u64), 0x1234_5678_9ABC);
+        assert_eq!(util::int_value(u128::MAX), -1);
+    }
+}
@@ -4,17 +4,26 @@ use crate::bus::VAXBus;
 use crate::Error;
 use crate::VAXNum;
 
-pub fn parse_read_operand
+/// Parse the operand specifier at PC for an operand accessed as `access`.
+pub fn parse_operand
     <B: VAXBus, T: VAXNum>
-    (cpu: &mut VAXCPU<B>)
+    (cpu: &mut VAXCPU<B>, access: AccessType)
     -> Result<UnresolvedOperand<T>, Error>
 {
     let pc = cpu.regfile.get_pc();
     let op_head: [u8;2] = cpu.read_val(pc)?;
     let mode = OperandMode::identify_operand(op_head)?;
     //println!("{:?}", mode);
-    cpu.regfile.set_pc(pc + mode.byte_size::<T>() as u32);
-    mode.create_resolvable::<B,T>(cpu, false)
+    cpu.regfile.set_pc(pc + mode.specifier_len() as u32);
+    mode.create_resolvable::<B,T>(cpu, access)
+}
+
+pub fn parse_read_operand
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<UnresolvedOperand<T>, Error>
+{
+    parse_operand(cpu, AccessType::Read)
 }
 
 pub fn parse_write_operand
@@ -22,12 +31,27 @@ pub fn parse_write_operand
     (cpu: &mut VAXCPU<B>)
     -> Result<UnresolvedOperand<T>, Error>
 {
-    let pc = cpu.regfile.get_pc();
-    let op_head: [u8;2] = cpu.read_val(pc)?;
-    let mode = OperandMode::identify_operand(op_head)?;
-    //println!("{:?}", mode);
-    cpu.regfile.set_pc(pc + mode.byte_size::<T>() as u32);
-    mode.create_resolvable::<B,T>(cpu, true)
+    parse_operand(cpu, AccessType::Write)
+}
+
+pub fn parse_modify_operand
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<UnresolvedOperand<T>, Error>
+{
+    parse_operand(cpu, AccessType::Modify)
+}
+
+/// Parse an address operand, returning the address. `T` is the type of the
+/// data it points at, which sets the step for autoincrement and indexing.
+pub fn parse_address_operand
+    <B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>)
+    -> Result<u32, Error>
+{
+    let mut op = parse_operand::<B,T>(cpu, AccessType::Address)?;
+    op.resolve(cpu)?;
+    op.address()
 }
 
 pub fn rrw_instr_wrap
@@ -114,11 +138,11 @@ pub fn rm_instr_wrap
     where F: Fn(V, O, &mut VAXCPU<B>) -> Result<O, Error>
 {
     let opr_a = parse_read_operand::<B,V>(cpu)?.read(cpu)?;
-    let mut opm_o = parse_write_operand::<B,O>(cpu)?;
+    let mut opm_o = parse_modify_operand::<B,O>(cpu)?;
+    opm_o.resolve(cpu)?;
 
     let v = func(opr_a, opm_o.read(cpu)?, cpu)?;
-    opm_o.execute_write(cpu, v);
-    Ok(())
+    opm_o.write(cpu, v)
 }
 
 pub fn rr_instr_wrap
@@ -162,11 +186,11 @@ pub fn m_instr_wrap
     -> Result<(), Error>
     where F: Fn(O, &mut VAXCPU<B>) -> Result<O, Error>
 {
-    let mut opm_o = parse_write_operand::<B,O>(cpu)?;
+    let mut opm_o = parse_modify_operand::<B,O>(cpu)?;
+    opm_o.resolve(cpu)?;
 
     let v = func(opm_o.read(cpu)?, cpu)?;
-    opm_o.execute_write(cpu, v);
-    Ok(())
+    opm_o.write(cpu, v)
 }
 
 pub fn w_instr_wrap
@@ -178,11 +202,10 @@ pub fn w_instr_wrap
     -> Result<(), Error>
     where F: Fn(&mut VAXCPU<B>) -> Result<O, Error>
 {
-    let mut opm_o = parse_write_operand::<B,O>(cpu)?;
+    let opw_o = parse_write_operand::<B,O>(cpu)?;
 
     let v = func(cpu)?;
-    opm_o.execute_write(cpu, v);
-    Ok(())
+    opw_o.write(cpu, v)
 }
 
 
@@ -205,6 +228,14 @@ pub fn rw_instr_wrap
     Ok(())
 }
 
+/// Sign extend an integer operand of any size, up to an octaword.
+pub fn int_value<T: VAXNum>(v: T) -> i128 {
+    let mut bytes = [0; 16];
+    v.copy_to_le_bytes(&mut bytes[..T::BYTE_LEN]);
+    let shift = 128 - T::BYTE_LEN as u32 * 8;
+    (i128::from_le_bytes(bytes) << shift) >> shift
+}
+
 #[inline]
 pub fn assert_kernel_mode<B: VAXBus>(cpu: &mut VAXCPU<B>) -> Result<(), Error> {
     if cpu.regfile.get_psl().get_cur_mod() == 0 {
@@ -1,7 +1,9 @@
 pub mod operands;
 mod instructiontypes;
+mod signatures;
 pub use instructiontypes::*;
 mod impls;
 pub use impls::execute_instr;
 pub use impls::MultiInstruction;
-pub use impls::exec_multi_instructions;
\ No newline at end of file
+pub use impls::exec_multi_instructions;
+pub use impls::{suspend_multi_instruction, resume_multi_instruction};
\ No newline at end of file
@@ -10,6 +10,104 @@ use emutk_core::{
     ByteReprNum,
 };
 use crate::bus::VAXBus;
+use std::fmt;
+
+/// How an instruction uses an operand, which decides the addressing modes it
+/// can legally be given.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AccessType {
+    /// `.r`, only read.
+    Read,
+    /// `.w`, only written. Literals aren't allowed.
+    Write,
+    /// `.m`, read then written back. Literals aren't allowed.
+    Modify,
+    /// `.a`, the operand's address is used rather than its value. Neither
+    /// literals nor registers have an address.
+    Address,
+    /// `.v`, the base of a bit field. Registers are allowed and hold the field
+    /// themselves, but literals aren't.
+    VariableBitField,
+    /// `.b`, a branch displacement. This isn't an operand specifier at all,
+    /// just a displacement in the instruction stream.
+    Branch,
+}
+
+/// The data type of an operand, which sets its size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Byte,
+    Word,
+    Long,
+    Quad,
+    Octa,
+    FFloat,
+    DFloat,
+    GFloat,
+    HFloat,
+}
+
+impl DataType {
+    pub fn byte_len(self) -> usize {
+        use DataType::*;
+        match self {
+            Byte => 1,
+            Word => 2,
+            Long | FFloat => 4,
+            Quad | DFloat | GFloat => 8,
+            Octa | HFloat => 16,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        use DataType::*;
+        matches!(self, FFloat | DFloat | GFloat | HFloat)
+    }
+
+    /// The letter used for the type in operand notation, like the `l` in `.rl`.
+    pub fn suffix(self) -> char {
+        use DataType::*;
+        match self {
+            Byte => 'b',
+            Word => 'w',
+            Long => 'l',
+            Quad => 'q',
+            Octa => 'o',
+            FFloat => 'f',
+            DFloat => 'd',
+            GFloat => 'g',
+            HFloat => 'h',
+        }
+    }
+}
+
+/// One operand of an instruction, as written in the architecture's notation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OperandSpec {
+    pub access: AccessType,
+    pub data: DataType,
+}
+
+impl OperandSpec {
+    pub const fn new(access: AccessType, data: DataType) -> Self {
+        OperandSpec { access, data }
+    }
+}
+
+impl fmt::Display for OperandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AccessType::*;
+        let access = match self.access {
+            Read => 'r',
+            Write => 'w',
+            Modify => 'm',
+            Address => 'a',
+            VariableBitField => 'v',
+            Branch => 'b',
+        };
+        write!(f, ".{}{}", access, self.data.suffix())
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum OperandMode {
@@ -77,154 +175,194 @@ impl OperandMode {
         })
     }
 
-    pub fn byte_size<T: ByteReprNum>(self) -> usize {
+    /// The mode nibble and register of the operand, or of the base operand if
+    /// it's indexed, along with the index register. Literals have no register,
+    /// so their value is returned in its place.
+    pub fn parts(self) -> (u8, u8, Option<u8>) {
         use OperandMode::*;
         match self {
-            Literal(_) | Register(_) | RegisterDeferred(_) |
-            Autodecrement(_) | Autoincrement(_) | AutoincrementDeferred(_) =>
-                1, 
-            ByteDisplacement(_) | ByteDisplacementDeferred(_) =>
-                2,
-            WordDisplacement(_) | WordDisplacementDeferred(_) =>
-                3,
-            LongwordDisplacement(_) | LongwordDisplacementDeferred(_) =>
-                5,
-            
-            IndexedRegisterDeferred(_, _) | IndexedAutodecrement(_, _) |
-            IndexedAutoincrement(_, _) | IndexedAutoincrementDeferred(_, _) 
-            => 2,
+            Literal(v) => (0, v, None),
+            Register(r) => (5, r, None),
+            RegisterDeferred(r) => (6, r, None),
+            Autodecrement(r) => (7, r, None),
+            Autoincrement(r) => (8, r, None),
+            AutoincrementDeferred(r) => (9, r, None),
+            ByteDisplacement(r) => (10, r, None),
+            ByteDisplacementDeferred(r) => (11, r, None),
+            WordDisplacement(r) => (12, r, None),
+            WordDisplacementDeferred(r) => (13, r, None),
+            LongwordDisplacement(r) => (14, r, None),
+            LongwordDisplacementDeferred(r) => (15, r, None),
+            IndexedRegisterDeferred(x, r) => (6, r, Some(x)),
+            IndexedAutodecrement(x, r) => (7, r, Some(x)),
+            IndexedAutoincrement(x, r) => (8, r, Some(x)),
+            IndexedAutoincrementDeferred(x, r) => (9, r, Some(x)),
+            IndexedByteDisplacement(x, r) => (10, r, Some(x)),
+            IndexedByteDisplacementDeferred(x, r) => (11, r, Some(x)),
+            IndexedWordDisplacement(x, r) => (12, r, Some(x)),
+            IndexedWordDisplacementDeferred(x, r) => (13, r, Some(x)),
+            IndexedLongwordDisplacement(x, r) => (14, r, Some(x)),
+            IndexedLongwordDisplacementDeferred(x, r) => (15, r, Some(x)),
+        }
+    }
 
-            IndexedByteDisplacement(_, _) | IndexedByteDisplacementDeferred(_, _)
-                => 3,
-            IndexedWordDisplacement(_, _) | IndexedWordDisplacementDeferred(_, _)
-                => 4,
-            IndexedLongwordDisplacement(_, _) | IndexedLongwordDisplacementDeferred(_, _)
-                => 6,
+    /// Length of the specifier byte, plus the index prefix if there is one.
+    pub fn specifier_len(self) -> usize {
+        match self.parts() {
+            (_, _, Some(_)) => 2,
+            _ => 1,
         }
     }
 
+    /// Total length of the operand in the instruction stream, including any
+    /// displacement, immediate data or absolute address following the specifier.
+    pub fn byte_size<T: ByteReprNum>(self) -> usize {
+        self.stream_len(T::BYTE_LEN)
+    }
+
+    /// Like `byte_size`, for an operand of `data_len` bytes.
+    pub fn stream_len(self, data_len: usize) -> usize {
+        let extra = match self.parts() {
+            (8, PC, _) => data_len, // Immediate
+            (9, PC, _) => 4, // Absolute
+            (10, _, _) | (11, _, _) => 1,
+            (12, _, _) | (13, _, _) => 2,
+            (14, _, _) | (15, _, _) => 4,
+            _ => 0,
+        };
+        self.specifier_len() + extra
+    }
+
+    /// Check that the mode can be used for an operand of `data_len` bytes that
+    /// is accessed as `access`. Everything that can be told from the specifier
+    /// alone is checked here, so decoding without a CPU agrees with execution.
+    pub fn check_access(self, access: AccessType, data_len: usize) -> Result<(), Error> {
+        let legal = match self.parts() {
+            (0, _, _) => access == AccessType::Read,
+            // Multi-register operands can't run into PC either.
+            (5, r, _) => access != AccessType::Address
+                && r as usize + data_len.max(4) / 4 <= PC as usize,
+            (6, PC, _) | (7, PC, _) => false,
+            (mode, r, Some(x)) => x != PC && !((7..=9).contains(&mode) && x == r),
+            _ => true,
+        };
+        if legal {
+            Ok(())
+        } else {
+            Err(Error::new_address_mode_fault())
+        }
+    }
+
+    /// Evaluate the operand, consuming any displacement or immediate data that
+    /// follows the specifier. PC should already point past the specifier
+    /// itself, just as it would in hardware, which makes PC relative, immediate
+    /// and absolute operands work out without special cases. Modes that can't
+    /// be used for `access` are reserved addressing mode faults.
     pub fn create_resolvable<B: VAXBus, T: VAXNum>
-        (&self, cpu: &mut VAXCPU<B>, write_intent: bool) -> Result<UnresolvedOperand<T>, Error>
+        (&self, cpu: &mut VAXCPU<B>, access: AccessType) -> Result<UnresolvedOperand<T>, Error>
     {
-        use OperandMode::*;
-        let pc = cpu.regfile.get_pc();
-        match self {
-            IndexedRegisterDeferred(r, i) => {
-                let v = cpu.regfile.read_gpr(*i);
-                let idx = cpu.regfile.read_gpr(*r).wrapping_mul(T::BYTE_LEN as u32);
-                //println!("IDXD: {:01$x}", v.wrapping_add(idx), 8);
-                Ok(UnresolvedOperand::Mem(v.wrapping_add(idx)))
-            },
-            IndexedAutodecrement(_, _) => todo!(),
-            IndexedAutoincrement(_, _) => todo!(),
-            IndexedAutoincrementDeferred(r, i) => {
-                /*let v = cpu.regfile.read_gpr(*r);
-                cpu.regfile.write_gpr(*r, v.wrapping_add(T::BYTE_LEN as u32));
-                let idx = cpu.regfile.read_gpr(*i) * T::BYTE_LEN as u32;
-                Ok(UnresolvedOperand::DeferredMem(v.wrapping_add(idx)))*/
-                todo!()
-            },
-            IndexedByteDisplacement(_, _) => todo!(),
-            IndexedByteDisplacementDeferred(_, _) => todo!(),
-            IndexedWordDisplacement(_, _) => todo!(),
-            IndexedWordDisplacementDeferred(_, _) => todo!(),
-            IndexedLongwordDisplacement(_, _) => todo!(),
-            IndexedLongwordDisplacementDeferred(_, _) => todo!(),
-            Literal(v) => if write_intent {
-                Err(Error::new_address_mode_fault())
-            } else {
-                Ok(UnresolvedOperand::Value(T::primitive_from(*v), 0))
-            },
-            Register(r) => {
-                let v = cpu.regfile.read_gpr_ext(*r);
-                Ok(UnresolvedOperand::Value(v, *r))
-            },
-            RegisterDeferred(r) => {
-                let v = cpu.regfile.read_gpr(*r);
-                Ok(UnresolvedOperand::Mem(v))
+        self.check_access(access, T::BYTE_LEN)?;
+        match self.parts() {
+            (0, v, _) => Ok(UnresolvedOperand::Literal(T::primitive_from(v))),
+            (5, r, _) => {
+                let v = cpu.regfile.read_gpr_ext(r);
+                Ok(UnresolvedOperand::Register(v, r))
             },
-            Autoincrement(r) => {
-                let v = cpu.regfile.read_gpr(*r);
-                cpu.regfile.write_gpr(*r, v.wrapping_add(T::BYTE_LEN as u32));
-                Ok(UnresolvedOperand::Mem(v))
+            (mode, r, None) => memory_operand(cpu, mode, r),
+            (mode, r, Some(x)) => {
+                let offset = cpu.regfile.read_gpr(x).wrapping_mul(T::BYTE_LEN as u32);
+                let mut base = memory_operand::<B, T>(cpu, mode, r)?;
+                base.resolve(cpu)?;
+                Ok(UnresolvedOperand::Mem(base.address()?.wrapping_add(offset)))
             },
-            Autodecrement(r) => {
-                let v = cpu.regfile.read_gpr(*r);
-                cpu.regfile.write_gpr(*r, v.wrapping_sub(T::BYTE_LEN as u32));
-                Ok(UnresolvedOperand::Mem(v.wrapping_sub(T::BYTE_LEN as u32)))
-            },
-            AutoincrementDeferred(r) => {
-                let v = cpu.regfile.read_gpr(*r);
-                cpu.regfile.write_gpr(*r, v.wrapping_add(T::BYTE_LEN as u32));
-                Ok(UnresolvedOperand::DeferredMem(v))
-            },
-            ByteDisplacement(r) => {
-                let disp = cpu.read_val::<i8>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp as i32) as u32;
-                Ok(UnresolvedOperand::Mem(adj_addr))
-            }
-
-            ByteDisplacementDeferred(r) => {
-                let disp = cpu.read_val::<i8>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp as i32) as u32;
-                Ok(UnresolvedOperand::DeferredMem(adj_addr))
-            }
-
-            WordDisplacement(r) => {
-                let disp = cpu.read_val::<i16>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp as i32) as u32;
-                Ok(UnresolvedOperand::Mem(adj_addr))
-            }
+        }
+    }
+}
 
-            WordDisplacementDeferred(r) => {
-                let disp = cpu.read_val::<i16>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp as i32) as u32;
-                Ok(UnresolvedOperand::DeferredMem(adj_addr))
-            }
+pub(crate) const PC: u8 = 15;
 
-            LongwordDisplacement(r) => {
-                let disp = cpu.read_val::<i32>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp) as u32;
-                Ok(UnresolvedOperand::Mem(adj_addr))
-            }
+/// Read a sign extended displacement from the instruction stream.
+fn read_displacement<B: VAXBus, D: ByteReprNum + Into<i32>>(cpu: &mut VAXCPU<B>) -> Result<u32, Error> {
+    let pc = cpu.regfile.get_pc();
+    let disp = cpu.read_val::<D>(pc)?;
+    cpu.regfile.set_pc(pc.wrapping_add(D::BYTE_LEN as u32));
+    Ok(disp.into() as u32)
+}
 
-            LongwordDisplacementDeferred(r) => {
-                let disp = cpu.read_val::<i32>(pc)?;
-                let addr = cpu.regfile.read_gpr(*r);
-                let adj_addr = (addr as i32).wrapping_add(disp) as u32;
-                Ok(UnresolvedOperand::DeferredMem(adj_addr))
+/// Evaluate the modes that refer to memory, given the mode nibble and register.
+fn memory_operand<B: VAXBus, T: VAXNum>
+    (cpu: &mut VAXCPU<B>, mode: u8, r: u8) -> Result<UnresolvedOperand<T>, Error>
+{
+    let size = T::BYTE_LEN as u32;
+    Ok(match mode {
+        6 => UnresolvedOperand::Mem(cpu.regfile.read_gpr(r)),
+        7 => {
+            let v = cpu.regfile.read_gpr(r).wrapping_sub(size);
+            cpu.regfile.write_gpr(r, v);
+            UnresolvedOperand::Mem(v)
+        },
+        8 => {
+            let v = cpu.regfile.read_gpr(r);
+            cpu.regfile.write_gpr(r, v.wrapping_add(size));
+            UnresolvedOperand::Mem(v)
+        },
+        9 => {
+            // Always steps over a longword address, whatever the operand size.
+            let v = cpu.regfile.read_gpr(r);
+            cpu.regfile.write_gpr(r, v.wrapping_add(4));
+            UnresolvedOperand::DeferredMem(v)
+        },
+        10..=15 => {
+            // The displacement comes first, so PC relative modes are relative
+            // to the end of the operand.
+            let disp = match mode {
+                10 | 11 => read_displacement::<B, i8>(cpu)?,
+                12 | 13 => read_displacement::<B, i16>(cpu)?,
+                _ => read_displacement::<B, i32>(cpu)?,
+            };
+            let addr = cpu.regfile.read_gpr(r).wrapping_add(disp);
+            if mode.is_multiple_of(2) {
+                UnresolvedOperand::Mem(addr)
+            } else {
+                UnresolvedOperand::DeferredMem(addr)
             }
-        }
-    }
+        },
+        _ => return Err(Error::new_address_mode_fault()),
+    })
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum UnresolvedOperand<T: VAXNum> {
     Mem(u32),
     DeferredMem(u32),
-    Value(T, u8),
+    /// A register, holding its value when the operand was parsed.
+    Register(T, u8),
+    Literal(T),
 }
 
 impl<T: VAXNum>  UnresolvedOperand<T> {
+    /// Follow a deferred operand's pointer, leaving a plain memory operand.
+    #[inline]
+    pub fn resolve<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<(), Error>
+    {
+        if let UnresolvedOperand::DeferredMem(addr) = self {
+            let addr2 = cpu.read_val::<u32>(*addr)?;
+            *self = UnresolvedOperand::Mem(addr2);
+        }
+        Ok(())
+    }
+
+    /// Resolve the operand and make sure it can be written to.
     #[inline] 
     pub fn validate<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>) -> Result<(), Error> 
     {
+        self.resolve(cpu)?;
         match self {
             UnresolvedOperand::Mem(addr) => {
                 cpu.can_write_val::<T>(*addr)
             }
-            UnresolvedOperand::DeferredMem(addr) => {
-                let addr2 = cpu.read_val::<u32>(*addr)?;
-                cpu.can_write_val::<T>(addr2)?;    
-                *self = UnresolvedOperand::Mem(addr2);
-                Ok(())
-            }
-            UnresolvedOperand::Value(_,_) => Ok(()),
+            UnresolvedOperand::Literal(_) => Err(Error::new_address_mode_fault()),
+            _ => Ok(()),
         }
     }
     
@@ -232,7 +370,7 @@ impl<T: VAXNum>  UnresolvedOperand<T> {
     pub fn execute_write<B: VAXBus>(&mut self, cpu: &mut VAXCPU<B>, value: T) 
     {
         match self {
-            UnresolvedOperand::Value(_, r) => {
+            UnresolvedOperand::Register(_, r) => {
                 cpu.regfile.write_gpr_ext::<T>(*r, value);
             }
             UnresolvedOperand::Mem(addr) => {
@@ -257,7 +395,7 @@ impl<T: VAXNum>  UnresolvedOperand<T> {
             UnresolvedOperand::Mem(addr) => {
                 cpu.read_val(addr).unwrap()
             }
-            UnresolvedOperand::Value(v, _) => v,
+            UnresolvedOperand::Register(v, _) | UnresolvedOperand::Literal(v) => v,
             _ => unreachable!(), // Reaching here means someone forgot to validate ):
         }
     }
@@ -265,8 +403,12 @@ impl<T: VAXNum>  UnresolvedOperand<T> {
     #[inline]
     pub fn read<B: VAXBus>(mut self, cpu: &mut VAXCPU<B>) -> Result<T, Error>
     {
-        self.validate(cpu)?;
-        Ok(self.execute_read(cpu))
+        self.resolve(cpu)?;
+        match self {
+            UnresolvedOperand::Mem(addr) => cpu.read_val(addr),
+            UnresolvedOperand::Register(v, _) | UnresolvedOperand::Literal(v) => Ok(v),
+            _ => unreachable!(),
+        }
     }
 
     #[inline]
@@ -276,7 +418,7 @@ impl<T: VAXNum>  UnresolvedOperand<T> {
             UnresolvedOperand::Mem(addr) => {
                 Ok(addr)
             }
-            UnresolvedOperand::Value(_, _) => {
+            UnresolvedOperand::Register(_, _) | UnresolvedOperand::Literal(_) => {
                 Err(Error::new_address_mode_fault())
             }
             _ => unreachable!()
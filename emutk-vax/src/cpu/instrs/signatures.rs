@@ -0,0 +1,397 @@
+use super::InstructionType;
+use super::operands::{AccessType, DataType, OperandSpec};
+
+macro_rules! specs {
+    ($($name:ident = $access:ident $data:ident;)+) => {
+        $(
+            const $name: OperandSpec =
+                OperandSpec::new(AccessType::$access, DataType::$data);
+        )+
+    };
+}
+
+// Named after the architecture's notation, so `RL` is `.rl`.
+specs! {
+    RB = Read Byte;
+    RW = Read Word;
+    RL = Read Long;
+    RQ = Read Quad;
+    RO = Read Octa;
+    RF = Read FFloat;
+    RD = Read DFloat;
+    RG = Read GFloat;
+    RH = Read HFloat;
+    WB = Write Byte;
+    WW = Write Word;
+    WL = Write Long;
+    WQ = Write Quad;
+    WO = Write Octa;
+    WF = Write FFloat;
+    WD = Write DFloat;
+    WG = Write GFloat;
+    WH = Write HFloat;
+    MB = Modify Byte;
+    MW = Modify Word;
+    ML = Modify Long;
+    MF = Modify FFloat;
+    MD = Modify DFloat;
+    MG = Modify GFloat;
+    MH = Modify HFloat;
+    AB = Address Byte;
+    AW = Address Word;
+    AL = Address Long;
+    AQ = Address Quad;
+    AO = Address Octa;
+    VB = VariableBitField Byte;
+    BB = Branch Byte;
+    BW = Branch Word;
+    BL = Branch Long;
+}
+
+impl InstructionType {
+    /// The operand specifiers the instruction takes, in order. CASEx's
+    /// displacement table isn't included, as its length depends on the
+    /// operands. None for the vector instructions, which aren't modelled.
+    pub fn operands(self) -> Option<&'static [OperandSpec]> {
+        use InstructionType::*;
+        Some(match self {
+            ADAWI => &[RW, MW],
+            ADDB2 => &[RB, MB],
+            ADDB3 => &[RB, RB, WB],
+            ADDW2 => &[RW, MW],
+            ADDW3 => &[RW, RW, WW],
+            ADDL2 => &[RL, ML],
+            ADDL3 => &[RL, RL, WL],
+            ADWC => &[RL, ML],
+            ASHL => &[RB, RL, WL],
+            ASHQ => &[RB, RQ, WQ],
+            BICB2 => &[RB, MB],
+            BICB3 => &[RB, RB, WB],
+            BICW2 => &[RW, MW],
+            BICW3 => &[RW, RW, WW],
+            BICL2 => &[RL, ML],
+            BICL3 => &[RL, RL, WL],
+            BISB2 => &[RB, MB],
+            BISB3 => &[RB, RB, WB],
+            BISW2 => &[RW, MW],
+            BISW3 => &[RW, RW, WW],
+            BISL2 => &[RL, ML],
+            BISL3 => &[RL, RL, WL],
+            BITB => &[RB, RB],
+            BITW => &[RW, RW],
+            BITL => &[RL, RL],
+            CLRB => &[WB],
+            CLRW => &[WW],
+            CLRL => &[WL],
+            CLRQ => &[WQ],
+            CLRO => &[WO],
+            CMPB => &[RB, RB],
+            CMPW => &[RW, RW],
+            CMPL => &[RL, RL],
+            CVTBW => &[RB, WW],
+            CVTBL => &[RB, WL],
+            CVTWB => &[RW, WB],
+            CVTWL => &[RW, WL],
+            CVTLB => &[RL, WB],
+            CVTLW => &[RL, WW],
+            DECB => &[MB],
+            DECW => &[MW],
+            DECL => &[ML],
+            DIVB2 => &[RB, MB],
+            DIVB3 => &[RB, RB, WB],
+            DIVW2 => &[RW, MW],
+            DIVW3 => &[RW, RW, WW],
+            DIVL2 => &[RL, ML],
+            DIVL3 => &[RL, RL, WL],
+            EDIV => &[RL, RQ, WL, WL],
+            EMUL => &[RL, RL, RL, WQ],
+            INCB => &[MB],
+            INCW => &[MW],
+            INCL => &[ML],
+            MCOMB => &[RB, WB],
+            MCOMW => &[RW, WW],
+            MCOML => &[RL, WL],
+            MNEGB => &[RB, WB],
+            MNEGW => &[RW, WW],
+            MNEGL => &[RL, WL],
+            MOVB => &[RB, WB],
+            MOVW => &[RW, WW],
+            MOVL => &[RL, WL],
+            MOVQ => &[RQ, WQ],
+            MOVO => &[RO, WO],
+            MOVZBW => &[RB, WW],
+            MOVZBL => &[RB, WL],
+            MOVZWL => &[RW, WL],
+            MULB2 => &[RB, MB],
+            MULB3 => &[RB, RB, WB],
+            MULW2 => &[RW, MW],
+            MULW3 => &[RW, RW, WW],
+            MULL2 => &[RL, ML],
+            MULL3 => &[RL, RL, WL],
+            PUSHL => &[RL],
+            ROTL => &[RB, RL, WL],
+            SBWC => &[RL, ML],
+            SUBB2 => &[RB, MB],
+            SUBB3 => &[RB, RB, WB],
+            SUBW2 => &[RW, MW],
+            SUBW3 => &[RW, RW, WW],
+            SUBL2 => &[RL, ML],
+            SUBL3 => &[RL, RL, WL],
+            TSTB => &[RB],
+            TSTW => &[RW],
+            TSTL => &[RL],
+            XORB2 => &[RB, MB],
+            XORB3 => &[RB, RB, WB],
+            XORW2 => &[RW, MW],
+            XORW3 => &[RW, RW, WW],
+            XORL2 => &[RL, ML],
+            XORL3 => &[RL, RL, WL],
+            MOVAB => &[AB, WL],
+            MOVAW => &[AW, WL],
+            MOVAL => &[AL, WL],
+            MOVAQ => &[AQ, WL],
+            MOVAO => &[AO, WL],
+            PUSHAB => &[AB],
+            PUSHAW => &[AW],
+            PUSHAL => &[AL],
+            PUSHAQ => &[AQ],
+            PUSHAO => &[AO],
+            CMPV => &[RL, RB, VB, RL],
+            CMPZV => &[RL, RB, VB, RL],
+            EXTV => &[RL, RB, VB, WL],
+            EXTZV => &[RL, RB, VB, WL],
+            FFC => &[RL, RB, VB, WL],
+            FFS => &[RL, RB, VB, WL],
+            INSV => &[RL, RL, RB, VB],
+            ACBB => &[RB, RB, MB, BW],
+            ACBW => &[RW, RW, MW, BW],
+            ACBL => &[RL, RL, ML, BW],
+            ACBF => &[RF, RF, MF, BW],
+            ACBD => &[RD, RD, MD, BW],
+            ACBG => &[RG, RG, MG, BW],
+            ACBH => &[RH, RH, MH, BW],
+            AOBLEQ => &[RL, ML, BB],
+            AOBLSS => &[RL, ML, BB],
+            BGTR => &[BB],
+            BLEQ => &[BB],
+            BNEQ => &[BB],
+            BEQL => &[BB],
+            BGEQ => &[BB],
+            BLSS => &[BB],
+            BGTRU => &[BB],
+            BLEQU => &[BB],
+            BVC => &[BB],
+            BVS => &[BB],
+            BGEQU => &[BB],
+            BLSSU => &[BB],
+            BBS => &[RL, VB, BB],
+            BBC => &[RL, VB, BB],
+            BBSS => &[RL, VB, BB],
+            BBCS => &[RL, VB, BB],
+            BBSC => &[RL, VB, BB],
+            BBCC => &[RL, VB, BB],
+            BBSSI => &[RL, VB, BB],
+            BBCCI => &[RL, VB, BB],
+            BLBS => &[RL, BB],
+            BLBC => &[RL, BB],
+            BRB => &[BB],
+            BRW => &[BW],
+            BSBB => &[BB],
+            BSBW => &[BW],
+            CASEB => &[RB, RB, RB],
+            CASEW => &[RW, RW, RW],
+            CASEL => &[RL, RL, RL],
+            JMP => &[AB],
+            JSB => &[AB],
+            RSB => &[],
+            SOBGEQ => &[ML, BB],
+            SOBGTR => &[ML, BB],
+            CALLG => &[AB, AB],
+            CALLS => &[RL, AB],
+            RET => &[],
+            BICPSW => &[RW],
+            BISPSW => &[RW],
+            BPT => &[],
+            BUGW => &[BW],
+            BUGL => &[BL],
+            HALT => &[],
+            INDEX => &[RL, RL, RL, RL, RL, WL],
+            MOVPSL => &[WL],
+            NOP => &[],
+            POPR => &[RW],
+            PUSHR => &[RW],
+            INSQHI => &[AB, AQ],
+            INSQTI => &[AB, AQ],
+            INSQUE => &[AB, AB],
+            REMQHI => &[AQ, WL],
+            REMQTI => &[AQ, WL],
+            REMQUE => &[AB, WL],
+            ADDF2 => &[RF, MF],
+            ADDF3 => &[RF, RF, WF],
+            ADDD2 => &[RD, MD],
+            ADDD3 => &[RD, RD, WD],
+            ADDG2 => &[RG, MG],
+            ADDG3 => &[RG, RG, WG],
+            ADDH2 => &[RH, MH],
+            ADDH3 => &[RH, RH, WH],
+            CMPF => &[RF, RF],
+            CMPD => &[RD, RD],
+            CMPG => &[RG, RG],
+            CMPH => &[RH, RH],
+            CVTBF => &[RB, WF],
+            CVTWF => &[RW, WF],
+            CVTLF => &[RL, WF],
+            CVTBD => &[RB, WD],
+            CVTWD => &[RW, WD],
+            CVTLD => &[RL, WD],
+            CVTBG => &[RB, WG],
+            CVTWG => &[RW, WG],
+            CVTLG => &[RL, WG],
+            CVTBH => &[RB, WH],
+            CVTWH => &[RW, WH],
+            CVTLH => &[RL, WH],
+            CVTFB => &[RF, WB],
+            CVTFW => &[RF, WW],
+            CVTFL => &[RF, WL],
+            CVTRFL => &[RF, WL],
+            CVTDB => &[RD, WB],
+            CVTDW => &[RD, WW],
+            CVTDL => &[RD, WL],
+            CVTRDL => &[RD, WL],
+            CVTGB => &[RG, WB],
+            CVTGW => &[RG, WW],
+            CVTGL => &[RG, WL],
+            CVTRGL => &[RG, WL],
+            CVTHB => &[RH, WB],
+            CVTHW => &[RH, WW],
+            CVTHL => &[RH, WL],
+            CVTRHL => &[RH, WL],
+            CVTFD => &[RF, WD],
+            CVTFG => &[RF, WG],
+            CVTFH => &[RF, WH],
+            CVTDF => &[RD, WF],
+            CVTDH => &[RD, WH],
+            CVTGF => &[RG, WF],
+            CVTGH => &[RG, WH],
+            CVTHF => &[RH, WF],
+            CVTHD => &[RH, WD],
+            CVTHG => &[RH, WG],
+            DIVF2 => &[RF, MF],
+            DIVF3 => &[RF, RF, WF],
+            DIVD2 => &[RD, MD],
+            DIVD3 => &[RD, RD, WD],
+            DIVG2 => &[RG, MG],
+            DIVG3 => &[RG, RG, WG],
+            DIVH2 => &[RH, MH],
+            DIVH3 => &[RH, RH, WH],
+            EMODF => &[RF, RB, RF, WL, WF],
+            EMODD => &[RD, RB, RD, WL, WD],
+            EMODG => &[RG, RW, RG, WL, WG],
+            EMODH => &[RH, RW, RH, WL, WH],
+            MNEGF => &[RF, WF],
+            MNEGD => &[RD, WD],
+            MNEGG => &[RG, WG],
+            MNEGH => &[RH, WH],
+            MOVF => &[RF, WF],
+            MOVD => &[RD, WD],
+            MOVG => &[RG, WG],
+            MOVH => &[RH, WH],
+            MULF2 => &[RF, MF],
+            MULF3 => &[RF, RF, WF],
+            MULD2 => &[RD, MD],
+            MULD3 => &[RD, RD, WD],
+            MULG2 => &[RG, MG],
+            MULG3 => &[RG, RG, WG],
+            MULH2 => &[RH, MH],
+            MULH3 => &[RH, RH, WH],
+            POLYF => &[RF, RW, AB],
+            POLYD => &[RD, RW, AB],
+            POLYG => &[RG, RW, AB],
+            POLYH => &[RH, RW, AB],
+            SUBF2 => &[RF, MF],
+            SUBF3 => &[RF, RF, WF],
+            SUBD2 => &[RD, MD],
+            SUBD3 => &[RD, RD, WD],
+            SUBG2 => &[RG, MG],
+            SUBG3 => &[RG, RG, WG],
+            SUBH2 => &[RH, MH],
+            SUBH3 => &[RH, RH, WH],
+            TSTF => &[RF],
+            TSTD => &[RD],
+            TSTG => &[RG],
+            TSTH => &[RH],
+            CMPC3 => &[RW, AB, AB],
+            CMPC5 => &[RW, AB, RB, RW, AB],
+            LOCC => &[RB, RW, AB],
+            MATCHC => &[RW, AB, RW, AB],
+            MOVC3 => &[RW, AB, AB],
+            MOVC5 => &[RW, AB, RB, RW, AB],
+            MOVTC => &[RW, AB, RB, AB, RW, AB],
+            MOVTUC => &[RW, AB, RB, AB, RW, AB],
+            SCANC => &[RW, AB, AB, RB],
+            SKPC => &[RB, RW, AB],
+            SPANC => &[RW, AB, AB, RB],
+            CRC => &[AB, RL, RW, AB],
+            ADDP4 => &[RW, AB, RW, AB],
+            ADDP6 => &[RW, AB, RW, AB, RW, AB],
+            ASHP => &[RB, RW, AB, RB, RW, AB],
+            CMPP3 => &[RW, AB, AB],
+            CMPP4 => &[RW, AB, RW, AB],
+            CVTLP => &[RL, RW, AB],
+            CVTPL => &[RW, AB, WL],
+            CVTPS => &[RW, AB, RW, AB],
+            CVTPT => &[RW, AB, AB, RW, AB],
+            CVTSP => &[RW, AB, RW, AB],
+            CVTTP => &[RW, AB, AB, RW, AB],
+            DIVP => &[RW, AB, RW, AB, RW, AB],
+            MOVP => &[RW, AB, AB],
+            MULP => &[RW, AB, RW, AB, RW, AB],
+            SUBP4 => &[RW, AB, RW, AB],
+            SUBP6 => &[RW, AB, RW, AB, RW, AB],
+            EDITPC => &[RW, AB, AB, AB],
+            PROBER => &[RB, RW, AB],
+            PROBEW => &[RB, RW, AB],
+            REI => &[],
+            CHMK => &[RW],
+            CHME => &[RW],
+            CHMS => &[RW],
+            CHMU => &[RW],
+            LDPCTX => &[],
+            SVPCTX => &[],
+            MTPR => &[RL, RL],
+            MFPR => &[RL, WL],
+            WAIT => &[],
+            PROBEVMR => &[RB, RW, AB],
+            PROBEVMW => &[RB, RW, AB],
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::FromPrimitive;
+
+    #[test]
+    fn every_scalar_instruction_has_operands() {
+        for op in 0..=0xFFFFu16 {
+            let instr = match InstructionType::from_u16(op) {
+                Some(i) => i,
+                None => continue,
+            };
+            let name = instr.to_str();
+            let vector = name.starts_with('V')
+                || ["IOTA", "MFVP", "MTVP"].contains(&name);
+            assert_eq!(instr.operands().is_none(), vector, "{}", name);
+        }
+    }
+
+    #[test]
+    fn operand_signatures() {
+        assert_eq!(InstructionType::MOVC5.operands().unwrap(), &[RW, AB, RB, RW, AB]);
+        assert_eq!(InstructionType::BRB.operands().unwrap(), &[BB]);
+        assert_eq!(InstructionType::EXTZV.operands().unwrap(), &[RL, RB, VB, WL]);
+        assert_eq!(InstructionType::RSB.operands().unwrap(), &[]);
+    }
+}
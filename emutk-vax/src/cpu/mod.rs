@@ -8,15 +8,25 @@ use num_derive::*;
 pub mod exec;
 pub mod instrs;
 pub mod regfile;
+mod exception;
+#[cfg(feature = "trace")]
+pub mod trace;
 
 mod psl;
 pub use psl::PSL;
-use regfile::VAXRegisterFile;
+use regfile::{VAXRegisterFile, RegisterSnapshot};
 
 use crate::Error;
 use crate::bus::VAXBus;
 use crate::CVZN;
 use crate::cpu::instrs::MultiInstruction;
+use crate::interrupt::InterruptController;
+use crate::mmu::{
+    MemoryAccessType,
+    TranslationBuffer,
+    PAGE_SIZE,
+    page_offset,
+};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
 pub enum PrivilegeMode {
@@ -39,12 +49,24 @@ pub struct VAXCPU<'bus, Bus: VAXBus + 'static> {
     cur_cycle: Cycles,
 
     multi_instr_active: MultiInstruction,
+
+    pub tb: TranslationBuffer,
+
+    /// Registers as they were at the start of the current instruction.
+    instr_start: RegisterSnapshot,
+
+    interrupts: InterruptController,
+
+    #[cfg(feature = "trace")]
+    tracer: Option<trace::Tracer>,
 }
 
 impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
     pub fn new() -> Self {
+        let regfile = VAXRegisterFile::new();
         let mut cpu = VAXCPU {
-            regfile: VAXRegisterFile::new(),
+            instr_start: regfile.snapshot(),
+            regfile,
 
             halted: false,
             bus: None,
@@ -53,6 +75,10 @@ impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
 
             cur_cycle: Cycles(0),
             multi_instr_active: MultiInstruction::None,
+            tb: TranslationBuffer::new(),
+            interrupts: InterruptController::new(),
+            #[cfg(feature = "trace")]
+            tracer: None,
         };
         cpu.setup_instr_table();
         cpu
@@ -70,6 +96,15 @@ impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
         self.cur_cycle.0
     }
 
+    /// The interrupt controller devices should request interrupts from.
+    pub fn interrupt_controller(&self) -> &InterruptController {
+        &self.interrupts
+    }
+
+    pub fn set_interrupt_controller(&mut self, ic: InterruptController) {
+        self.interrupts = ic;
+    }
+
     pub fn give_bus(&mut self, bus: &'bus mut Bus) {
         if let Some(_) = self.bus {
             panic!("Attempted to give CPU that already has a bus a bus.");
@@ -83,46 +118,251 @@ impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
         v
     }
 
-    pub fn read_val<T: ByteRepr>(&mut self, addr: u32) ->  Result<T, Error> {
-        let bus = (&mut self.bus).as_mut().expect("No bus!");
+    /// Read a processor register.
+    pub fn read_msr(&mut self, mid: u16) -> Result<u32, Error> {
+        self.regfile.read_msr(mid)
+    }
 
-        if self.regfile.get_mapen() {
-            todo!()
-        } else {
-            let (cyc, res) = bus.read_val(addr as usize);
-            let out: T = res.unwrap(); 
-            self.cur_cycle += cyc;
-            Ok(out)
+    /// Write a processor register. Registers that act on CPU state outside the
+    /// register file, such as the translation buffer, are handled here.
+    pub fn write_msr(&mut self, mid: u16, val: u32) -> Result<(), Error> {
+        match mid {
+            57 => self.tb.invalidate_all(), // TBIA
+            58 => self.tb.invalidate_single(val), // TBIS
+            63 => { // TBCHK
+                let present = self.tb.check(val);
+                self.regfile.get_psl_mut().set_z(present);
+            },
+            _ => return self.regfile.write_msr(mid, val),
         }
+        Ok(())
     }
 
-    pub fn write_val<T: ByteRepr>(&mut self, addr: u32, val: T) -> Result<(), Error> {
+    /// Read a value from physical memory, bypassing memory management.
+    pub fn read_phys<T: ByteRepr>(&mut self, addr: u32) -> Result<T, Error> {
+        let bus = self.bus.as_mut().expect("No bus!");
+        let (cyc, res) = bus.read_val(addr as usize);
+        self.cur_cycle += cyc;
+        res.map_err(|_| Error::new_machine_check())
+    }
+
+    /// Write a value to physical memory, bypassing memory management.
+    pub fn write_phys<T: ByteRepr>(&mut self, addr: u32, val: T) -> Result<(), Error> {
         let bus = (&mut self.bus).as_mut().expect("No bus!");
+        let (cyc, res) = bus.write_val(addr as usize, val);
+        self.cur_cycle += cyc;
+        res.map_err(|_| Error::new_machine_check())
+    }
+
+    pub fn read_val<T: ByteRepr>(&mut self, addr: u32) ->  Result<T, Error> {
+        let val = if self.regfile.get_mapen() {
+            match self.translate_range(addr, T::BYTE_LEN, MemoryAccessType::Read)? {
+                (phys, None) => self.read_phys(phys)?,
+                (phys, Some(phys2)) => {
+                    // Page crossing, the two halves may live anywhere in physical memory.
+                    let split = (PAGE_SIZE - page_offset(addr)) as usize;
+                    let mut buf = [0_u8; 16];
+                    for (i, byte) in buf[..T::BYTE_LEN].iter_mut().enumerate() {
+                        *byte = if i < split {
+                            self.read_phys(phys + i as u32)?
+                        } else {
+                            self.read_phys(phys2 + (i - split) as u32)?
+                        };
+                    }
+                    T::from_le_bytes(&buf[..T::BYTE_LEN])
+                }
+            }
+        } else {
+            self.read_phys(addr)?
+        };
+        #[cfg(feature = "trace")]
+        self.trace_mem(addr, T::BYTE_LEN as u32, false, Some(trace::le_value(val)));
+        Ok(val)
+    }
 
+    pub fn write_val<T: ByteRepr>(&mut self, addr: u32, val: T) -> Result<(), Error> {
         if self.regfile.get_mapen() {
-            todo!()
+            match self.translate_range(addr, T::BYTE_LEN, MemoryAccessType::Write)? {
+                (phys, None) => self.write_phys(phys, val)?,
+                (phys, Some(phys2)) => {
+                    let split = (PAGE_SIZE - page_offset(addr)) as usize;
+                    let mut buf = [0_u8; 16];
+                    val.copy_to_le_bytes(&mut buf);
+                    for (i, b) in buf[..T::BYTE_LEN].iter().enumerate() {
+                        if i < split {
+                            self.write_phys(phys + i as u32, *b)?;
+                        } else {
+                            self.write_phys(phys2 + (i - split) as u32, *b)?;
+                        }
+                    }
+                }
+            }
         } else {
-            //println!("WR: {:?} to {:02$x}", val.into_le_bytes(), addr, 8);
-            let (cyc, _) = bus.write_val(addr as usize, val);
-            self.cur_cycle += cyc;
-            Ok(())
+            self.write_phys(addr, val)?;
         }
+        #[cfg(feature = "trace")]
+        self.trace_mem(addr, T::BYTE_LEN as u32, true, Some(trace::le_value(val)));
+        Ok(())
     }
 
-    pub fn can_read_val<T: ByteRepr>(&mut self, _addr: u32) -> Result<(), Error> {
+    pub fn can_read_val<T: ByteRepr>(&mut self, addr: u32) -> Result<(), Error> {
         if self.regfile.get_mapen() {
-            todo!()
-        } else {
-            Ok(())
+            self.translate_range(addr, T::BYTE_LEN, MemoryAccessType::Read)?;
         }
+        Ok(())
     }
 
-    pub fn can_write_val<T: ByteRepr>(&mut self, _addr: u32) -> Result<(), Error> {
+    pub fn can_write_val<T: ByteRepr>(&mut self, addr: u32) -> Result<(), Error> {
         if self.regfile.get_mapen() {
-            todo!()
+            self.translate_range(addr, T::BYTE_LEN, MemoryAccessType::Write)?;
+        }
+        Ok(())
+    }
+
+    /// Atomically replace the value at `addr` with `op` of it, returning the old value.
+    /// This is how the interlocked instructions get exclusive access to memory
+    /// other processors on the bus might be touching.
+    pub fn interlocked_update<T, F>(&mut self, addr: u32, op: F) -> Result<T, Error>
+        where T: ByteRepr + Clone, F: FnOnce(T) -> T
+    {
+        let phys = if self.regfile.get_mapen() {
+            match self.translate_range(addr, T::BYTE_LEN, MemoryAccessType::Write)? {
+                (phys, None) => phys,
+                // Interlocked operands are always aligned, so this is a program bug.
+                (_, Some(_)) => return Err(Error::new_reserved_operand_fault()),
+            }
         } else {
-            Ok(())
+            addr
+        };
+        #[cfg(feature = "trace")]
+        let mut written = 0;
+        let bus = self.bus.as_mut().expect("No bus!");
+        let (cyc, res) = bus.interlocked_update(phys as usize, |old| {
+            let new = op(old);
+            #[cfg(feature = "trace")]
+            {
+                written = trace::le_value(new);
+            }
+            new
+        });
+        self.cur_cycle += cyc;
+        let old = res.map_err(|_| Error::new_machine_check())?;
+        #[cfg(feature = "trace")]
+        {
+            self.trace_mem(addr, T::BYTE_LEN as u32, false, Some(trace::le_value(old)));
+            self.trace_mem(addr, T::BYTE_LEN as u32, true, Some(written));
+        }
+        Ok(old)
+    }
+
+    /// Translate `len` bytes at `addr` into runs of physical memory, one per page.
+    /// Every page is checked before returning, so a fault leaves nothing touched.
+    fn translate_block(&mut self, addr: u32, len: u32, access: MemoryAccessType)
+        -> Result<Vec<(u32, u32)>, Error>
+    {
+        if !self.regfile.get_mapen() {
+            return Ok(vec![(addr, len)]);
+        }
+        let mut runs = vec![];
+        let mut va = addr;
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(PAGE_SIZE - page_offset(va));
+            let (phys, _) = self.translate_range(va, 1, access)?;
+            runs.push((phys, n));
+            va = va.wrapping_add(n);
+            left -= n;
+        }
+        Ok(runs)
+    }
+
+    fn block_phys<F>(&mut self, op: F) -> Result<(), Error>
+        where F: FnOnce(&mut Bus) -> (Cycles, Result<(), ()>)
+    {
+        let bus = self.bus.as_mut().expect("No bus!");
+        let (cyc, res) = op(bus);
+        self.cur_cycle += cyc;
+        res.map_err(|_| Error::new_machine_check())
+    }
+
+    /// Read `buf.len()` bytes starting at `addr`, a page at a time through the
+    /// bus's block operations. Faults happen before anything is read.
+    pub fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        let mut offs = 0;
+        for (phys, n) in self.translate_block(addr, buf.len() as u32, MemoryAccessType::Read)? {
+            let chunk = &mut buf[offs..offs + n as usize];
+            self.block_phys(|bus| bus.read_block(phys as usize, chunk))?;
+            offs += n as usize;
         }
+        #[cfg(feature = "trace")]
+        self.trace_mem(addr, buf.len() as u32, false, None);
+        Ok(())
+    }
+
+    /// Write all of `buf` starting at `addr`. Faults happen before anything is written.
+    pub fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), Error> {
+        let mut offs = 0;
+        for (phys, n) in self.translate_block(addr, buf.len() as u32, MemoryAccessType::Write)? {
+            let chunk = &buf[offs..offs + n as usize];
+            self.block_phys(|bus| bus.write_block(phys as usize, chunk))?;
+            offs += n as usize;
+        }
+        #[cfg(feature = "trace")]
+        self.trace_mem(addr, buf.len() as u32, true, None);
+        Ok(())
+    }
+
+    /// Write `len` copies of `val` starting at `addr`. Faults happen before anything
+    /// is written.
+    pub fn fill_block(&mut self, addr: u32, val: u8, len: u32) -> Result<(), Error> {
+        for (phys, n) in self.translate_block(addr, len, MemoryAccessType::Write)? {
+            self.block_phys(|bus| bus.fill_block(phys as usize, val, n as usize))?;
+        }
+        #[cfg(feature = "trace")]
+        self.trace_mem(addr, len, true, None);
+        Ok(())
+    }
+
+    /// Copy `len` bytes from `src` to `dst`, as if the whole source was read before
+    /// anything was written. Faults happen before anything is written.
+    pub fn copy_block(&mut self, src: u32, dst: u32, len: u32) -> Result<(), Error> {
+        let src_runs = self.translate_block(src, len, MemoryAccessType::Read)?;
+        let dst_runs = self.translate_block(dst, len, MemoryAccessType::Write)?;
+
+        // Split things up wherever either side crosses into a new page.
+        let mut pieces = vec![];
+        let (mut si, mut di) = (0, 0);
+        let (mut soffs, mut doffs) = (0, 0);
+        while si < src_runs.len() && di < dst_runs.len() {
+            let (sphys, slen) = src_runs[si];
+            let (dphys, dlen) = dst_runs[di];
+            let n = (slen - soffs).min(dlen - doffs);
+            pieces.push((sphys + soffs, dphys + doffs, n));
+            soffs += n;
+            doffs += n;
+            if soffs == slen {
+                si += 1;
+                soffs = 0;
+            }
+            if doffs == dlen {
+                di += 1;
+                doffs = 0;
+            }
+        }
+
+        // Copying upwards has to start from the top so the source survives.
+        if dst > src {
+            pieces.reverse();
+        }
+        for (sphys, dphys, n) in pieces {
+            self.block_phys(|bus| bus.copy_block(sphys as usize, dphys as usize, n as usize))?;
+        }
+        #[cfg(feature = "trace")]
+        {
+            self.trace_mem(src, len, false, None);
+            self.trace_mem(dst, len, true, None);
+        }
+        Ok(())
     }
 
     pub fn commit_flags(&mut self, flags: CVZN) {
@@ -133,6 +373,16 @@ impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
         psl.set_n(flags.get_n());
         self.regfile.set_psl(psl);
     }
+
+    /// Trap if the instruction that just completed set V with integer overflow
+    /// traps enabled.
+    pub fn check_integer_overflow(&self) -> Result<(), Error> {
+        let psl = self.regfile.get_psl();
+        if psl.get_v() && psl.get_iv() {
+            return Err(Error::new_integer_overflow_trap());
+        }
+        Ok(())
+    }
 }
 
 impl<'bus, Bus: VAXBus> VAXCPU<'bus, Bus> {
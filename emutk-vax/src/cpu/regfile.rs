@@ -45,17 +45,10 @@ pub struct VAXRegisterFile {
     /// Memory Management Enable
     mapen: bool,
 
-    /// Software Interrupt Request
-    sirr: u32,
     /// Software Interrupt Summary
     sisr: u32,
-
-    /// Translation Buffer Invalidate All
-    tbia: u32,
-    /// Translation Buffer Invalidate Single
-    tbis: u32,
-    /// Translation Buffer Check
-    tbchk: u32,
+    /// AST Level
+    astlvl: u32,
 
     // Common MSR
     ///TODO: Figure out usage.
@@ -64,6 +57,22 @@ pub struct VAXRegisterFile {
     conpc: u32,
 }
 
+/// The registers an instruction can change before it faults, saved so that the
+/// instruction can be backed out.
+#[derive(Copy, Clone, Debug)]
+pub struct RegisterSnapshot {
+    gpr: [u32;14],
+    stkptrs: [u32;5],
+    pc: u32,
+    psl: PSL,
+}
+
+impl RegisterSnapshot {
+    pub fn get_pc(&self) -> u32 {
+        self.pc
+    }
+}
+
 macro_rules! gpr_funcs {
     (;$($reg:literal, $get_reg:ident, $get_reg_mut:ident, $set_reg:ident);+) => {
         $(
@@ -149,12 +158,10 @@ impl VAXRegisterFile {
         ; slr, get_slr, set_slr
         ; pcbb, get_pcbb, set_pcbb
         ; scbb, get_scbb, set_scbb
-        ; sirr, get_sirr, set_sirr
         ; sisr, get_sisr, set_sisr
-        ; tbia, get_tbia, set_tbia
-        ; tbis, get_tbis, set_tbis
-        ; tbchk, get_tbchk, set_tbchk
+        ; astlvl, get_astlvl, set_astlvl
         ; conpsl, get_conpsl, set_conpsl
+        ; conpc, get_conpc, set_conpc
     );
     
     pub fn get_mapen(&self) -> bool {
@@ -180,6 +187,22 @@ impl VAXRegisterFile {
 
 /// CPU-level register file reads/writes
 impl VAXRegisterFile {
+    pub fn snapshot(&self) -> RegisterSnapshot {
+        RegisterSnapshot {
+            gpr: self.gpr,
+            stkptrs: self.stkptrs,
+            pc: self.pc,
+            psl: self.psl,
+        }
+    }
+
+    pub fn restore(&mut self, snap: &RegisterSnapshot) {
+        self.gpr = snap.gpr;
+        self.stkptrs = snap.stkptrs;
+        self.pc = snap.pc;
+        self.psl = snap.psl;
+    }
+
     pub fn get_sp(&self) -> u32 {
         if self.psl.get_is() {
             self.get_isp()
@@ -199,6 +222,18 @@ impl VAXRegisterFile {
         }
     }
 
+    /// Request a software interrupt at `ipl`, as done by writing SIRR.
+    pub fn request_software_interrupt(&mut self, ipl: u8) {
+        if ipl != 0 {
+            self.sisr |= 1 << ipl;
+        }
+    }
+
+    /// The highest IPL with a software interrupt pending, or 0 if there are none.
+    pub fn highest_software_interrupt(&self) -> u8 {
+        (31 - (self.sisr | 1).leading_zeros()) as u8
+    }
+
     pub fn read_gpr(&self, gpr: u8) -> u32 {
         assert!(gpr < 16);
         match gpr {
@@ -291,6 +326,9 @@ impl VAXRegisterFile {
             16 => Ok(self.get_pcbb()),
             17 => Ok(self.get_scbb()),
             18 => Ok(self.psl.get_ipl() as u32),
+            19 => Ok(self.get_astlvl()),
+            21 => Ok(self.get_sisr()),
+            42 => Ok(self.get_conpc()),
             43 => Ok(self.get_conpsl()),
             56 => Ok(self.get_mapen() as u32),
             #[cfg(not(feature = "sys_debug"))]
@@ -318,7 +356,18 @@ impl VAXRegisterFile {
             13 => self.set_slr(val),
             16 => self.set_pcbb(val),
             17 => self.set_scbb(val),
+            18 => self.psl.set_ipl((val & 0x1F) as u8),
+            19 => {
+                if val > 4 {
+                    return Err(Error::new_reserved_operand_fault());
+                }
+                self.set_astlvl(val)
+            },
+            20 => self.request_software_interrupt((val & 0xF) as u8),
+            21 => self.set_sisr(val & 0xFFFE),
+            42 => self.set_conpc(val),
             43 => self.set_conpsl(val),
+            56 => self.set_mapen(val & 0x1 != 0),
             35 => { // debug things using TXDB
                 use std::io::Write;
                 let out = std::io::stdout();
@@ -360,12 +409,9 @@ impl VAXRegisterFile {
         
             mapen: false,
         
-            sirr: 0,
             sisr: 0,
+            astlvl: 4,
         
-            tbia: 0,
-            tbis: 0,
-            tbchk: 0,
 
             conpc: 0,
             conpsl: 0,
@@ -0,0 +1,609 @@
+//! Instruction tracing. With the `trace` feature enabled a [`TraceSink`] can be
+//! attached to a CPU with [`VAXCPU::set_tracer`], and every instruction it
+//! wants is handed to it as a [`TraceRecord`] once it's done: its PC and bytes,
+//! the registers and PSL it changed, the memory it touched and the exception it
+//! raised, if any. Without the feature none of the hooks exist.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::ops::Range;
+use std::rc::Rc;
+
+use emutk_core::ByteRepr;
+
+use crate::bus::VAXBus;
+use crate::cpu::VAXCPU;
+use crate::cpu::instrs::{InstructionType, MultiInstruction};
+use crate::disasm::{disassemble_without_table, Syntax};
+use crate::{Error, ErrorKind};
+
+/// A register an instruction changed. PC isn't included, it's the next
+/// record's PC.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegChange {
+    pub reg: u8,
+    pub old: u32,
+    pub new: u32,
+}
+
+/// A data access an instruction made, by virtual address. Reads of the
+/// instruction's own bytes aren't included.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemAccess {
+    pub addr: u32,
+    pub len: u32,
+    pub write: bool,
+    /// The value read or written, zero extended. `None` for block accesses,
+    /// like the string instructions make.
+    pub data: Option<u128>,
+}
+
+/// Everything one traced instruction did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub pc: u32,
+    /// The instruction's bytes, as the CPU read them while decoding it. Short
+    /// if it faulted part way, and without a CASEx's displacement table.
+    pub bytes: Vec<u8>,
+    pub regs: Vec<RegChange>,
+    /// The old and new PSL, if it changed.
+    pub psl: Option<(u32, u32)>,
+    pub mem: Vec<MemAccess>,
+    /// The exception the instruction raised. The registers are as they were
+    /// when it was raised, before it was dispatched.
+    pub exception: Option<ErrorKind>,
+}
+
+impl TraceRecord {
+    pub fn instr(&self) -> Option<InstructionType> {
+        let b0 = *self.bytes.first()?;
+        InstructionType::from_instrid([b0, self.bytes.get(1).copied().unwrap_or(0)])
+    }
+
+    /// The instruction as assembly.
+    pub fn disassemble(&self, syntax: Syntax) -> String {
+        disassemble_without_table(&self.bytes, self.pc, syntax).1
+    }
+}
+
+/// Somewhere trace records go.
+pub trait TraceSink {
+    /// Whether to take the record of the instruction `instr` at `pc`.
+    /// Unwanted ones are dropped before anything's done with them.
+    fn wants(&mut self, _pc: u32, _instr: Option<InstructionType>) -> bool {
+        true
+    }
+
+    fn record(&mut self, rec: &TraceRecord);
+}
+
+/// Lets a sink be looked at while the CPU holds onto it.
+impl<S: TraceSink> TraceSink for Rc<RefCell<S>> {
+    fn wants(&mut self, pc: u32, instr: Option<InstructionType>) -> bool {
+        self.borrow_mut().wants(pc, instr)
+    }
+
+    fn record(&mut self, rec: &TraceRecord) {
+        self.borrow_mut().record(rec)
+    }
+}
+
+fn reg_name(reg: u8) -> String {
+    match reg {
+        12 => "AP".to_string(),
+        13 => "FP".to_string(),
+        14 => "SP".to_string(),
+        15 => "PC".to_string(),
+        r => format!("R{}", r),
+    }
+}
+
+/// Writes records as text, an instruction per line followed by indented lines
+/// for what it did:
+///
+/// ```text
+/// 00000007  ADDL3 R0, R1, -(SP)
+///           R0: 00000001 -> 00000002
+///           SP: 00000200 -> 000001FC
+///           write 000001FC.L 00000007
+/// ```
+pub struct LogSink<W: Write> {
+    out: W,
+    syntax: Syntax,
+}
+
+impl<W: Write> LogSink<W> {
+    pub fn new(out: W) -> Self {
+        LogSink { out, syntax: Syntax::Macro32 }
+    }
+
+    pub fn syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_record(&mut self, rec: &TraceRecord) -> std::io::Result<()> {
+        writeln!(self.out, "{:08X}  {}", rec.pc, rec.disassemble(self.syntax))?;
+        for r in &rec.regs {
+            writeln!(self.out, "          {}: {:08X} -> {:08X}", reg_name(r.reg), r.old, r.new)?;
+        }
+        if let Some((old, new)) = rec.psl {
+            writeln!(self.out, "          PSL: {:08X} -> {:08X}", old, new)?;
+        }
+        for m in &rec.mem {
+            let dir = if m.write { "write" } else { "read " };
+            match m.data {
+                Some(v) => {
+                    let size = match m.len {
+                        1 => 'B',
+                        2 => 'W',
+                        4 => 'L',
+                        8 => 'Q',
+                        _ => 'O',
+                    };
+                    writeln!(self.out, "          {} {:08X}.{} {:0w$X}", dir, m.addr, size, v, w = m.len as usize * 2)?;
+                }
+                None => writeln!(self.out, "          {} {:08X} [{} bytes]", dir, m.addr, m.len)?,
+            }
+        }
+        if let Some(kind) = rec.exception {
+            writeln!(self.out, "          exception: {:?}", kind)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceSink for LogSink<W> {
+    fn record(&mut self, rec: &TraceRecord) {
+        // A trace that can't be written isn't worth stopping the machine for.
+        let _ = self.write_record(rec);
+    }
+}
+
+/// Keeps the most recent records in a fixed amount of memory, packed into a
+/// compact binary form. Handy for finding out how a program got somewhere
+/// without logging everything it did.
+pub struct RingSink {
+    buf: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl RingSink {
+    /// A ring holding up to `capacity` bytes of records.
+    pub fn new(capacity: usize) -> Self {
+        RingSink { buf: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// The records still in the ring, oldest first.
+    pub fn records(&self) -> Vec<TraceRecord> {
+        let (a, b) = self.buf.as_slices();
+        let bytes = [a, b].concat();
+        let mut out = vec![];
+        let mut r = Reader { b: &bytes, pos: 0 };
+        while r.pos < bytes.len() {
+            let len = r.u32() as usize;
+            let mut rec = Reader { b: &bytes[r.pos..r.pos + len], pos: 0 };
+            out.push(rec.record());
+            r.pos += len;
+        }
+        out
+    }
+}
+
+impl TraceSink for RingSink {
+    fn record(&mut self, rec: &TraceRecord) {
+        let body = encode(rec);
+        let total = body.len() + 4;
+        if total > self.capacity {
+            return;
+        }
+        while self.buf.len() + total > self.capacity {
+            let mut len = [0; 4];
+            for (i, b) in len.iter_mut().enumerate() {
+                *b = self.buf[i];
+            }
+            self.buf.drain(..4 + u32::from_le_bytes(len) as usize);
+        }
+        self.buf.extend(&(body.len() as u32).to_le_bytes());
+        self.buf.extend(&body);
+    }
+}
+
+/// Exception kinds as stored in the ring, by their position here. Every kind
+/// has to be listed.
+const KINDS: [ErrorKind; 22] = {
+    use ErrorKind::*;
+    [
+        IntegerOverflow, IntegerDivZero, DecimalDivZero, DecimalOverflow, SubscriptRange,
+        FloatingOverflow, FloatingDivZero, FloatingUnderflow, AccessControlViolation,
+        TranslationNotValid, ReservedAddressingMode, ReservedOperand, ReservedInstruction,
+        PrivilegedInstruction, OpcodeReservedToCustomers, ChangeMode, Breakpoint, Trace,
+        KernelStackNotValid, InterruptStackNotValid, MachineCheck, Debug,
+    ]
+};
+
+/// Lay out a record as:
+///
+/// - PC (4 bytes), then the instruction length (1) and bytes,
+/// - the number of registers changed (1), then each as the register number (1),
+///   old (4) and new (4) values,
+/// - flags (1): bit 0 for a PSL change, followed by the old (4) and new (4)
+///   PSL, bit 1 for an exception, followed by its kind (1),
+/// - the number of memory accesses (4), then each as its address (4), length
+///   (4), flags (1) with bit 0 for writes and bit 1 if the data follows, and
+///   the data itself.
+fn encode(rec: &TraceRecord) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend(&rec.pc.to_le_bytes());
+    out.push(rec.bytes.len() as u8);
+    out.extend(&rec.bytes);
+    out.push(rec.regs.len() as u8);
+    for r in &rec.regs {
+        out.push(r.reg);
+        out.extend(&r.old.to_le_bytes());
+        out.extend(&r.new.to_le_bytes());
+    }
+    out.push(rec.psl.is_some() as u8 | (rec.exception.is_some() as u8) << 1);
+    if let Some((old, new)) = rec.psl {
+        out.extend(&old.to_le_bytes());
+        out.extend(&new.to_le_bytes());
+    }
+    if let Some(kind) = rec.exception {
+        out.push(KINDS.iter().position(|&k| k == kind).unwrap() as u8);
+    }
+    out.extend(&(rec.mem.len() as u32).to_le_bytes());
+    for m in &rec.mem {
+        out.extend(&m.addr.to_le_bytes());
+        out.extend(&m.len.to_le_bytes());
+        out.push(m.write as u8 | (m.data.is_some() as u8) << 1);
+        if let Some(v) = m.data {
+            out.extend(&v.to_le_bytes()[..m.len as usize]);
+        }
+    }
+    out
+}
+
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let s = &self.b[self.pos..self.pos + len];
+        self.pos += len;
+        s
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u32(&mut self) -> u32 {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.take(4));
+        u32::from_le_bytes(b)
+    }
+
+    fn record(&mut self) -> TraceRecord {
+        let pc = self.u32();
+        let n = self.u8() as usize;
+        let bytes = self.take(n).to_vec();
+        let n = self.u8();
+        let regs = (0..n).map(|_| RegChange { reg: self.u8(), old: self.u32(), new: self.u32() }).collect();
+        let flags = self.u8();
+        let psl = if flags & 1 != 0 { Some((self.u32(), self.u32())) } else { None };
+        let exception = if flags & 2 != 0 { KINDS.get(self.u8() as usize).copied() } else { None };
+        let n = self.u32();
+        let mem = (0..n).map(|_| {
+            let addr = self.u32();
+            let len = self.u32();
+            let flags = self.u8();
+            let data = if flags & 2 != 0 {
+                let mut buf = [0; 16];
+                buf[..len as usize].copy_from_slice(self.take(len as usize));
+                Some(u128::from_le_bytes(buf))
+            } else {
+                None
+            };
+            MemAccess { addr, len, write: flags & 1 != 0, data }
+        }).collect();
+        TraceRecord { pc, bytes, regs, psl, mem, exception }
+    }
+}
+
+/// Passes on only the instructions in a range of addresses and/or with
+/// particular opcodes. With neither set, everything goes through.
+pub struct Filter<S: TraceSink> {
+    inner: S,
+    pcs: Option<Range<u32>>,
+    opcodes: Vec<InstructionType>,
+}
+
+impl<S: TraceSink> Filter<S> {
+    pub fn new(inner: S) -> Self {
+        Filter { inner, pcs: None, opcodes: vec![] }
+    }
+
+    /// Only trace instructions starting in `pcs`.
+    pub fn pc_range(mut self, pcs: Range<u32>) -> Self {
+        self.pcs = Some(pcs);
+        self
+    }
+
+    /// Only trace these instructions. Can be given more than once.
+    pub fn opcode(mut self, instr: InstructionType) -> Self {
+        self.opcodes.push(instr);
+        self
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: TraceSink> TraceSink for Filter<S> {
+    fn wants(&mut self, pc: u32, instr: Option<InstructionType>) -> bool {
+        let in_range = self.pcs.as_ref().is_none_or(|r| r.contains(&pc));
+        let opcode = self.opcodes.is_empty() || instr.is_some_and(|i| self.opcodes.contains(&i));
+        in_range && opcode && self.inner.wants(pc, instr)
+    }
+
+    fn record(&mut self, rec: &TraceRecord) {
+        self.inner.record(rec)
+    }
+}
+
+/// A CPU's tracer, and what it's found out about the instruction in progress.
+pub(crate) struct Tracer {
+    sink: Box<dyn TraceSink>,
+    cur: Option<InFlight>,
+    /// The PC and bytes of an instruction that's carrying on in the next tick,
+    /// which won't read them again.
+    resume: Option<(u32, Vec<u8>)>,
+}
+
+struct InFlight {
+    rec: TraceRecord,
+    regs: [u32; 16],
+    psl: u32,
+}
+
+impl Tracer {
+    pub(crate) fn new(sink: Box<dyn TraceSink>) -> Self {
+        Tracer { sink, cur: None, resume: None }
+    }
+
+    pub(crate) fn into_sink(self) -> Box<dyn TraceSink> {
+        self.sink
+    }
+}
+
+/// A value's bytes as a zero extended little endian number.
+pub(crate) fn le_value<T: ByteRepr>(val: T) -> u128 {
+    let mut buf = [0; 16];
+    val.copy_to_le_bytes(&mut buf[..T::BYTE_LEN]);
+    u128::from_le_bytes(buf)
+}
+
+impl<B: VAXBus> VAXCPU<'_, B> {
+    /// Send a record of every instruction executed from now on to `sink`,
+    /// replacing any sink there already was.
+    pub fn set_tracer(&mut self, sink: impl TraceSink + 'static) {
+        self.tracer = Some(Tracer::new(Box::new(sink)));
+    }
+
+    /// Stop tracing, returning the sink.
+    pub fn take_tracer(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take().map(Tracer::into_sink)
+    }
+
+    /// Start recording the instruction at `pc`. This mustn't touch memory, or
+    /// tracing would change what the machine does.
+    pub(crate) fn trace_begin(&mut self, pc: u32) {
+        let resuming = self.multi_instr_active != MultiInstruction::None;
+        let tracer = match self.tracer.as_mut() {
+            Some(t) => t,
+            None => return,
+        };
+        let bytes = match tracer.resume.take() {
+            Some((at, bytes)) if resuming && at == pc => bytes,
+            _ => vec![],
+        };
+        let mut regs = [0; 16];
+        for (i, r) in regs.iter_mut().enumerate() {
+            *r = self.regfile.read_gpr(i as u8);
+        }
+        tracer.cur = Some(InFlight {
+            rec: TraceRecord { pc, bytes, regs: vec![], psl: None, mem: vec![], exception: None },
+            regs,
+            psl: self.regfile.get_psl().0,
+        });
+    }
+
+    /// Note a memory access by the instruction being traced. Reads carrying on
+    /// from the bytes seen so far, either at PC or of what PC has just stepped
+    /// over, are the decoder working through the instruction stream and go
+    /// into the instruction's bytes instead.
+    pub(crate) fn trace_mem(&mut self, addr: u32, len: u32, write: bool, data: Option<u128>) {
+        let pc = self.regfile.get_pc();
+        let cur = match self.tracer.as_mut().and_then(|t| t.cur.as_mut()) {
+            Some(cur) => cur,
+            None => return,
+        };
+        let rec = &mut cur.rec;
+        let offs = addr.wrapping_sub(rec.pc) as usize;
+        let end = pc.wrapping_sub(rec.pc) as usize;
+        let stream = offs == end || offs + len as usize <= end;
+        if !write && stream && offs <= rec.bytes.len() {
+            // The decoder looks ahead at times, so reads can overlap.
+            let v = data.unwrap_or(0).to_le_bytes();
+            for (i, b) in v[..len as usize].iter().enumerate() {
+                match rec.bytes.get_mut(offs + i) {
+                    Some(old) => *old = *b,
+                    None => rec.bytes.push(*b),
+                }
+            }
+            return;
+        }
+        rec.mem.push(MemAccess { addr, len, write, data });
+    }
+
+    /// Finish off the instruction being traced and hand it to the sink.
+    pub(crate) fn trace_end(&mut self, res: &Result<(), Error>) {
+        let resuming = self.multi_instr_active != MultiInstruction::None;
+        let tracer = match self.tracer.as_mut() {
+            Some(t) => t,
+            None => return,
+        };
+        let InFlight { mut rec, regs, psl } = match tracer.cur.take() {
+            Some(cur) => cur,
+            None => return,
+        };
+        // Drop anything the decoder looked at past the end.
+        let (len, _) = disassemble_without_table(&rec.bytes, rec.pc, Syntax::Macro32);
+        rec.bytes.truncate(len);
+        if resuming {
+            tracer.resume = Some((rec.pc, rec.bytes.clone()));
+        }
+        if !tracer.sink.wants(rec.pc, rec.instr()) {
+            return;
+        }
+
+        for (i, &old) in regs[..15].iter().enumerate() {
+            let new = self.regfile.read_gpr(i as u8);
+            if new != old {
+                rec.regs.push(RegChange { reg: i as u8, old, new });
+            }
+        }
+        let new_psl = self.regfile.get_psl().0;
+        if new_psl != psl {
+            rec.psl = Some((psl, new_psl));
+        }
+        rec.exception = res.as_ref().err().map(Error::kind);
+        tracer.sink.record(&rec);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::exec::simple_test_cpu_with_data;
+
+    fn trace(src: &str, sink: impl TraceSink + 'static, steps: usize) {
+        let prog = assemble(src, 0, Syntax::Macro32).unwrap();
+        let (mut cpu, mut bus) = simple_test_cpu_with_data(&prog);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sp(0x200);
+        cpu.set_tracer(sink);
+        for _ in 0..steps {
+            // Dispatching an exception can fail without an SCB, the trace of
+            // the instruction that raised it is still there.
+            if cpu.run_tick().is_err() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn records() {
+        let ring = Rc::new(RefCell::new(RingSink::new(4096)));
+        trace("MOVL #^X12345678, R0\n\
+               PUSHL R0\n\
+               MOVL (SP)+, R1\n\
+               MOVC3 #3, (R2), 8(R2)\n\
+               HALT\n", ring.clone(), 20);
+        let recs = ring.borrow().records();
+        assert_eq!(recs.last().unwrap().instr(), Some(InstructionType::HALT));
+
+        assert_eq!(recs[0].pc, 0);
+        assert_eq!(recs[0].instr(), Some(InstructionType::MOVL));
+        assert_eq!(recs[0].bytes.len(), 7);
+        assert_eq!(recs[0].disassemble(Syntax::Macro32), "MOVL #^X12345678, R0");
+        assert_eq!(recs[0].regs, vec![RegChange { reg: 0, old: 0, new: 0x12345678 }]);
+        // Immediate data comes from the instruction stream, not a data access.
+        assert_eq!(recs[0].mem, vec![]);
+
+        assert_eq!(recs[1].pc, 7);
+        assert_eq!(recs[1].regs, vec![RegChange { reg: 14, old: 0x200, new: 0x1FC }]);
+        assert_eq!(recs[1].mem, vec![
+            MemAccess { addr: 0x1FC, len: 4, write: true, data: Some(0x12345678) },
+        ]);
+        assert_eq!(recs[2].mem, vec![
+            MemAccess { addr: 0x1FC, len: 4, write: false, data: Some(0x12345678) },
+        ]);
+
+        // MOVC3 can be interrupted, each piece of it is recorded on its own.
+        let movc: Vec<_> = recs.iter().filter(|r| r.pc == 12).collect();
+        assert!(movc.iter().all(|r| r.instr() == Some(InstructionType::MOVC3)));
+        assert!(movc.iter().flat_map(|r| &r.mem).any(|m| m.write && m.addr == 8));
+        assert!(movc.iter().flat_map(|r| &r.regs).any(|r| r.reg == 3 && r.new == 11));
+        assert!(movc.iter().all(|r| r.exception.is_none()));
+    }
+
+    #[test]
+    fn timing_unchanged() {
+        let prog = assemble("MOVL #^X12345678, R0\n\
+                             PUSHL R0\n\
+                             MOVC3 #3, (R2), 8(R2)\n\
+                             HALT\n", 0, Syntax::Macro32).unwrap();
+        let run = |traced: bool| {
+            let (mut cpu, mut bus) = simple_test_cpu_with_data(&prog);
+            cpu.give_bus(&mut bus);
+            cpu.regfile.set_sp(0x200);
+            if traced {
+                cpu.set_tracer(RingSink::new(4096));
+            }
+            while !cpu.halted() {
+                cpu.run_tick().unwrap();
+            }
+            cpu.cur_cycle()
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn exceptions() {
+        let ring = Rc::new(RefCell::new(RingSink::new(4096)));
+        trace("BISPSW #^X20\nCHMK #5\n", ring.clone(), 2);
+        let recs = ring.borrow().records();
+        let (old, new) = recs[0].psl.unwrap();
+        assert_eq!(new & !old, 0x20);
+        assert_eq!(recs[1].exception, Some(ErrorKind::ChangeMode));
+    }
+
+    #[test]
+    fn ring_drops_oldest() {
+        let ring = Rc::new(RefCell::new(RingSink::new(64)));
+        trace("INCL R0\nINCL R0\nINCL R0\nINCL R0\nINCL R0\nINCL R0\n", ring.clone(), 6);
+        let recs = ring.borrow().records();
+        assert!(recs.len() < 6);
+        let last = recs.last().unwrap();
+        assert_eq!(last.regs, vec![RegChange { reg: 0, old: 5, new: 6 }]);
+    }
+
+    #[test]
+    fn filters_and_log() {
+        let log = Rc::new(RefCell::new(LogSink::new(vec![])));
+        let filter = Filter::new(log.clone()).pc_range(0..5).opcode(InstructionType::PUSHL);
+        trace("MOVL S^#5, R0\nPUSHL R0\nPUSHL R0\n", filter, 3);
+        let text = String::from_utf8(log.borrow().out.clone()).unwrap();
+        assert_eq!(text, "00000003  PUSHL R0\n\
+                          \x20         SP: 00000200 -> 000001FC\n\
+                          \x20         write 000001FC.L 00000005\n");
+    }
+}
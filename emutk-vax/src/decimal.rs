@@ -0,0 +1,371 @@
+use std::cmp::Ordering;
+
+use crate::Error;
+
+/// Longest packed decimal string, in digits.
+pub const MAX_DECIMAL_DIGITS: u16 = 31;
+
+/// Preferred sign nibbles, used for everything written.
+const SIGN_PLUS: u8 = 0xC;
+const SIGN_MINUS: u8 = 0xD;
+
+/// Bytes taken up by a packed decimal string of `len` digits.
+#[inline]
+pub fn packed_size(len: u16) -> u32 {
+    len as u32 / 2 + 1
+}
+
+/// A signed decimal number of any length, used for the packed decimal
+/// instructions.
+///
+/// Packed decimal strings hold two digits per byte, most significant first, with
+/// the sign in the low nibble of the last byte:
+/// ```text
+///   7    4 3    0
+/// +-------+------+
+/// |  MSD  |  ..  | :A
+/// +-------+------+
+///       ...
+/// +-------+------+
+/// |  LSD  | SIGN | :A+len/2
+/// +-------+------+
+/// ```
+/// Even length strings have an unused zero nibble in front of the MSD. Signs of
+/// 0xB and 0xD are negative, 0xA, 0xC, 0xE and 0xF are positive.
+#[derive(Clone, Debug)]
+pub struct Decimal {
+    pub neg: bool,
+    /// Least significant first.
+    digits: Vec<u8>,
+}
+
+fn trimmed(d: &[u8]) -> &[u8] {
+    let len = d.iter().rposition(|&x| x != 0).map_or(0, |i| i + 1);
+    &d[..len]
+}
+
+fn cmp_mag(a: &[u8], b: &[u8]) -> Ordering {
+    let (a, b) = (trimmed(a), trimmed(b));
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let s = a.get(i).unwrap_or(&0) + b.get(i).unwrap_or(&0) + carry;
+        res.push(s % 10);
+        carry = s / 10;
+    }
+    res.push(carry);
+    res
+}
+
+/// `a - b`, where `a` is at least as large as `b`.
+fn sub_mag(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &x) in a.iter().enumerate() {
+        let y = b.get(i).unwrap_or(&0) + borrow;
+        if x >= y {
+            res.push(x - y);
+            borrow = 0;
+        } else {
+            res.push(x + 10 - y);
+            borrow = 1;
+        }
+    }
+    res
+}
+
+fn mul_mag(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut acc = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            acc[i + j] += x as u32 * y as u32;
+        }
+    }
+    let mut carry = 0;
+    acc.iter()
+        .map(|&v| {
+            let s = v + carry;
+            carry = s / 10;
+            (s % 10) as u8
+        })
+        .collect()
+}
+
+/// Truncating `a / b`, where `b` isn't zero.
+fn div_mag(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut quo = vec![0; a.len()];
+    let mut rem: Vec<u8> = vec![];
+    for (i, &d) in a.iter().enumerate().rev() {
+        rem.insert(0, d);
+        let mut q = 0;
+        while cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            q += 1;
+        }
+        quo[i] = q;
+    }
+    quo
+}
+
+impl Decimal {
+    pub fn zero() -> Self {
+        Decimal {
+            neg: false,
+            digits: vec![],
+        }
+    }
+
+    /// Parse a packed decimal string of `len` digits, faulting on bad digits or
+    /// signs.
+    pub fn from_packed(bytes: &[u8], len: u16) -> Result<Self, Error> {
+        debug_assert_eq!(bytes.len() as u32, packed_size(len));
+        let last = bytes[bytes.len() - 1];
+        let sign = last & 0xF;
+        if sign < 0xA {
+            return Err(Error::new_reserved_operand_fault());
+        }
+
+        let mut digits = Vec::with_capacity(len as usize);
+        for i in 0..len as usize {
+            // Digit i counting from the least significant, which sits next to the sign.
+            let nibble = i + 1;
+            let byte = bytes[bytes.len() - 1 - nibble / 2];
+            let d = if nibble % 2 == 0 { byte & 0xF } else { byte >> 4 };
+            if d > 9 {
+                return Err(Error::new_reserved_operand_fault());
+            }
+            digits.push(d);
+        }
+
+        Ok(Decimal {
+            neg: sign == 0xB || sign == 0xD,
+            digits,
+        })
+    }
+
+    /// Pack into a string of `len` digits, dropping any that don't fit. Also
+    /// returns whether anything nonzero was dropped.
+    ///
+    /// A zero result is written as positive unless it's what was left after an
+    /// overflow.
+    pub fn to_packed(&self, len: u16) -> (Vec<u8>, bool) {
+        let len = len as usize;
+        let overflow = self.digits.iter().skip(len).any(|&d| d != 0);
+        let kept = &self.digits[..len.min(self.digits.len())];
+        let neg = self.neg && (overflow || !trimmed(kept).is_empty());
+
+        let mut bytes = vec![0; packed_size(len as u16) as usize];
+        let last = bytes.len() - 1;
+        bytes[last] = if neg { SIGN_MINUS } else { SIGN_PLUS };
+        for (i, &d) in kept.iter().enumerate() {
+            let nibble = i + 1;
+            let byte = &mut bytes[last - nibble / 2];
+            *byte |= if nibble % 2 == 0 { d } else { d << 4 };
+        }
+        (bytes, overflow)
+    }
+
+    /// Build a number from digits, most significant first.
+    pub fn from_digits(neg: bool, digits: &[u8]) -> Self {
+        Decimal {
+            neg,
+            digits: digits.iter().rev().copied().collect(),
+        }
+    }
+
+    /// The lowest `len` digits, most significant first. Also returns whether
+    /// anything nonzero was dropped.
+    pub fn to_digits(&self, len: u16) -> (Vec<u8>, bool) {
+        let len = len as usize;
+        let overflow = self.digits.iter().skip(len).any(|&d| d != 0);
+        let digits = (0..len).rev()
+            .map(|i| *self.digits.get(i).unwrap_or(&0))
+            .collect();
+        (digits, overflow)
+    }
+
+    pub fn from_int(v: i128) -> Self {
+        let mut mag = v.unsigned_abs();
+        let mut digits = vec![];
+        while mag != 0 {
+            digits.push((mag % 10) as u8);
+            mag /= 10;
+        }
+        Decimal {
+            neg: v < 0,
+            digits,
+        }
+    }
+
+    /// The value as an integer. Only valid for numbers of up to 38 digits.
+    pub fn to_int(&self) -> i128 {
+        let mag = trimmed(&self.digits).iter().rev()
+            .fold(0_i128, |acc, &d| acc * 10 + d as i128);
+        if self.neg { -mag } else { mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        trimmed(&self.digits).is_empty()
+    }
+
+    /// True if the value is less than zero. Negative zero isn't.
+    pub fn is_negative(&self) -> bool {
+        self.neg && !self.is_zero()
+    }
+
+    pub fn negated(&self) -> Self {
+        Decimal {
+            neg: !self.neg,
+            digits: self.digits.clone(),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.neg == other.neg {
+            return Decimal {
+                neg: self.neg,
+                digits: add_mag(&self.digits, &other.digits),
+            };
+        }
+        match cmp_mag(&self.digits, &other.digits) {
+            Ordering::Less => Decimal {
+                neg: other.neg,
+                digits: sub_mag(&other.digits, &self.digits),
+            },
+            _ => Decimal {
+                neg: self.neg,
+                digits: sub_mag(&self.digits, &other.digits),
+            },
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.negated())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Decimal {
+            neg: self.neg != other.neg,
+            digits: mul_mag(&self.digits, &other.digits),
+        }
+    }
+
+    /// Divide, truncating towards zero. `other` must not be zero.
+    pub fn div(&self, other: &Self) -> Self {
+        assert!(!other.is_zero());
+        Decimal {
+            neg: self.neg != other.neg,
+            digits: div_mag(&self.digits, trimmed(&other.digits)),
+        }
+    }
+
+    /// Multiply by 10^`cnt`. When shifting right, `round` is added to the most
+    /// significant digit shifted out, carrying into the result.
+    pub fn shift(&self, cnt: i8, round: u8) -> Self {
+        let digits = if cnt >= 0 {
+            let mut d = vec![0; cnt as usize];
+            d.extend_from_slice(&self.digits);
+            d
+        } else {
+            let n = (-(cnt as i16)) as usize;
+            let lost = if n <= self.digits.len() { self.digits[n - 1] } else { 0 };
+            let kept = self.digits.get(n..).unwrap_or(&[]);
+            if lost + round > 9 {
+                add_mag(kept, &[1])
+            } else {
+                kept.to_vec()
+            }
+        };
+        Decimal {
+            neg: self.neg,
+            digits,
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.is_negative(), other.is_negative()) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.digits, &other.digits),
+            (true, true) => cmp_mag(&other.digits, &self.digits),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn dec(bytes: &[u8], len: u16) -> Decimal {
+        Decimal::from_packed(bytes, len).unwrap()
+    }
+
+    #[test]
+    fn packing() {
+        let d = dec(&[0x12, 0x34, 0x5D], 5);
+        assert!(d.is_negative());
+        assert_eq!(d.to_packed(5), (vec![0x12, 0x34, 0x5D], false));
+        assert_eq!(d.to_packed(6), (vec![0x00, 0x12, 0x34, 0x5D], false));
+        assert_eq!(d.to_packed(2), (vec![0x04, 0x5D], true));
+        // Preferred signs are used, and -0 turns into +0.
+        assert_eq!(dec(&[0x12, 0x3A], 3).to_packed(3).0, vec![0x12, 0x3C]);
+        assert_eq!(dec(&[0x00, 0x0B], 3).to_packed(3).0, vec![0x00, 0x0C]);
+        assert_eq!(dec(&[0x0C], 0).to_packed(0).0, vec![0x0C]);
+
+        let e = Decimal::from_packed(&[0x1A, 0x3C], 3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+        let e = Decimal::from_packed(&[0x12, 0x39], 3).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ReservedOperand);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = dec(&[0x99, 0x9C], 3); // 999
+        let b = dec(&[0x00, 0x2D], 3); // -2
+        assert_eq!(a.add(&b).to_packed(3).0, vec![0x99, 0x7C]);
+        assert_eq!(b.sub(&a).to_packed(4).0, vec![0x01, 0x00, 0x1D]);
+        assert_eq!(a.add(&a).to_packed(3), (vec![0x99, 0x8C], true));
+        assert_eq!(a.mul(&b).to_packed(5).0, vec![0x01, 0x99, 0x8D]);
+        assert_eq!(a.div(&b).to_packed(3).0, vec![0x49, 0x9D]);
+        assert!(b < a);
+        assert_eq!(dec(&[0x0C], 1).cmp(&dec(&[0x0D], 1)), Ordering::Equal);
+
+        let c = dec(&[0x12, 0x34, 0x5C], 5);
+        assert_eq!(c.shift(2, 0).to_packed(7).0, vec![0x12, 0x34, 0x50, 0x0C]);
+        assert_eq!(c.shift(-2, 5).to_packed(3).0, vec![0x12, 0x3C]);
+        assert_eq!(c.shift(-1, 5).to_packed(4).0, vec![0x01, 0x23, 0x5C]);
+        assert!(c.shift(-9, 5).is_zero());
+    }
+
+    #[test]
+    fn conversions() {
+        let d = Decimal::from_int(-1234);
+        assert_eq!(d.to_packed(4).0, vec![0x01, 0x23, 0x4D]);
+        assert_eq!(d.to_int(), -1234);
+        assert_eq!(d.to_digits(6), (vec![0, 0, 1, 2, 3, 4], false));
+        assert_eq!(d.to_digits(2), (vec![3, 4], true));
+        assert_eq!(Decimal::from_digits(true, &[0, 1, 2, 3, 4]), d);
+        assert_eq!(Decimal::from_int(i32::MIN as i128).to_int(), i32::MIN as i128);
+    }
+}
@@ -0,0 +1,407 @@
+//! Disassembly of VAX instructions, using the same opcode table, operand
+//! decoding and operand signatures as execution does.
+
+use crate::cpu::instrs::InstructionType;
+use crate::cpu::instrs::operands::{AccessType, DataType, OperandMode, OperandSpec, PC};
+
+/// Assembler syntax to disassemble into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Syntax {
+    /// DEC's VAX MACRO-32, like `MOVL 4(R1), @#^X1100`.
+    Macro32,
+    /// GNU as, like the bootrom sources use: `movl 4(%r1), *$0x1100`.
+    Gnu,
+}
+
+/// Disassemble the instruction at the start of `bytes`, which is at `pc`,
+/// into MACRO-32 syntax. Returns the instruction's length and its text.
+pub fn disassemble(bytes: &[u8], pc: u32) -> (usize, String) {
+    disassemble_with(bytes, pc, Syntax::Macro32)
+}
+
+/// Disassemble the instruction at the start of `bytes`, which is at `pc`.
+///
+/// Anything that isn't a complete, valid instruction (unknown opcodes, vector
+/// instructions, reserved addressing modes or running out of bytes) comes out
+/// as a single data byte, so a caller stepping through memory always makes
+/// progress. A CASEx's displacement table is included as `.WORD`s on lines of
+/// their own if its limit is a constant, as that's the only way to know its
+/// length. Float immediates are shown as their raw bits.
+pub fn disassemble_with(bytes: &[u8], pc: u32, syntax: Syntax) -> (usize, String) {
+    disassemble_inner(bytes, pc, syntax, true)
+}
+
+/// Like [`disassemble_with`], but leaving out a CASEx's displacement table,
+/// for when only the instruction itself is at hand.
+pub(crate) fn disassemble_without_table(bytes: &[u8], pc: u32, syntax: Syntax) -> (usize, String) {
+    disassemble_inner(bytes, pc, syntax, false)
+}
+
+fn disassemble_inner(bytes: &[u8], pc: u32, syntax: Syntax, tables: bool) -> (usize, String) {
+    if bytes.is_empty() {
+        return (0, String::new());
+    }
+    let mut dis = Disassembler { bytes, pos: 0, pc, syntax, tables };
+    match dis.instruction() {
+        Some(text) => (dis.pos, text),
+        None => {
+            let text = match syntax {
+                Syntax::Macro32 => format!(".BYTE ^X{:02X}", bytes[0]),
+                Syntax::Gnu => format!(".byte 0x{:02x}", bytes[0]),
+            };
+            (1, text)
+        }
+    }
+}
+
+struct Disassembler<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    pc: u32,
+    syntax: Syntax,
+    tables: bool,
+}
+
+impl<'a> Disassembler<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Read a little endian value of up to 16 bytes, zero extended.
+    fn take_val(&mut self, len: usize) -> Option<u128> {
+        let mut buf = [0; 16];
+        buf[..len].copy_from_slice(self.take(len)?);
+        Some(u128::from_le_bytes(buf))
+    }
+
+    /// Read a sign extended displacement of `len` bytes.
+    fn take_disp(&mut self, len: usize) -> Option<i32> {
+        let v = self.take_val(len)? as u32;
+        Some(match len {
+            1 => v as u8 as i8 as i32,
+            2 => v as u16 as i16 as i32,
+            _ => v as i32,
+        })
+    }
+
+    /// Address of the next byte to be read.
+    fn cur_pc(&self) -> u32 {
+        self.pc.wrapping_add(self.pos as u32)
+    }
+
+    fn instruction(&mut self) -> Option<String> {
+        let head = [self.bytes[0], self.bytes.get(1).copied().unwrap_or(0)];
+        let instr = InstructionType::from_instrid(head)?;
+        self.take(instr.opcode_len())?;
+        let specs = instr.operands()?;
+
+        let mut ops = Vec::with_capacity(specs.len());
+        let mut constants = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let (text, constant) = self.operand(instr, *spec)?;
+            ops.push(text);
+            constants.push(constant);
+        }
+
+        let name = instr.to_str();
+        let mut text = match self.syntax {
+            Syntax::Macro32 => name.to_string(),
+            Syntax::Gnu => name.to_ascii_lowercase(),
+        };
+        if !ops.is_empty() {
+            text.push(' ');
+            text.push_str(&ops.join(", "));
+        }
+
+        use InstructionType::{CASEB, CASEW, CASEL};
+        if self.tables && matches!(instr, CASEB | CASEW | CASEL) {
+            if let Some(limit) = constants[2] {
+                let directive = match self.syntax {
+                    Syntax::Macro32 => ".WORD",
+                    Syntax::Gnu => ".word",
+                };
+                for _ in 0..=(limit as u16) {
+                    let disp = self.take_disp(2)?;
+                    text.push_str(&format!("\n{} {}", directive, disp));
+                }
+            }
+        }
+        Some(text)
+    }
+
+    /// Decode one operand, returning its text and, for literals and
+    /// immediates, its value.
+    fn operand(&mut self, instr: InstructionType, spec: OperandSpec) -> Option<(String, Option<u128>)> {
+        let data_len = spec.data.byte_len();
+        if spec.access == AccessType::Branch {
+            let disp = self.take_disp(data_len)?;
+            // BUGW and BUGL carry a message code rather than a displacement.
+            let v = match instr {
+                InstructionType::BUGW | InstructionType::BUGL => disp as u32 & mask(data_len),
+                _ => self.cur_pc().wrapping_add(disp as u32),
+            };
+            return Some((self.hex(v as u128), None));
+        }
+
+        let head = [
+            *self.bytes.get(self.pos)?,
+            self.bytes.get(self.pos + 1).copied().unwrap_or(0),
+        ];
+        let mode = OperandMode::identify_operand(head).ok()?;
+        mode.check_access(spec.access, data_len).ok()?;
+        self.take(mode.specifier_len())?;
+
+        let (m, r, index) = mode.parts();
+        let mut constant = None;
+        let text = match (m, r) {
+            (0, v) => {
+                constant = Some(v as u128);
+                self.literal(v, spec.data)
+            }
+            (5, r) => self.reg(r).to_string(),
+            (6, r) => format!("({})", self.reg(r)),
+            (7, r) => format!("-({})", self.reg(r)),
+            (8, PC) => {
+                let v = self.take_val(data_len)?;
+                constant = Some(v);
+                self.immediate(v, spec.data)
+            }
+            (8, r) => format!("({})+", self.reg(r)),
+            (9, PC) => {
+                let addr = self.take_val(4)?;
+                format!("{}{}{}", self.deferred(), self.imm_sign(), self.hex(addr))
+            }
+            (9, r) => format!("{}({})+", self.deferred(), self.reg(r)),
+            (m, r) => {
+                let len = match m {
+                    10 | 11 => 1,
+                    12 | 13 => 2,
+                    _ => 4,
+                };
+                let disp = self.take_disp(len)?;
+                let deferred = if m % 2 == 1 { self.deferred() } else { "" };
+                if r == PC {
+                    // Relative to the end of the displacement, where PC is now.
+                    let target = self.cur_pc().wrapping_add(disp as u32);
+                    format!("{}{}{}", deferred, self.size_prefix(len), self.hex(target as u128))
+                } else {
+                    let prefix = if len == disp_len(disp) { "" } else { self.size_prefix(len) };
+                    format!("{}{}{}({})", deferred, prefix, disp, self.reg(r))
+                }
+            }
+        };
+        Some(match index {
+            Some(x) => (format!("{}[{}]", text, self.reg(x)), None),
+            None => (text, constant),
+        })
+    }
+
+    fn reg(&self, r: u8) -> &'static str {
+        const MACRO32: [&str; 16] = [
+            "R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7",
+            "R8", "R9", "R10", "R11", "AP", "FP", "SP", "PC",
+        ];
+        const GNU: [&str; 16] = [
+            "%r0", "%r1", "%r2", "%r3", "%r4", "%r5", "%r6", "%r7",
+            "%r8", "%r9", "%r10", "%r11", "%ap", "%fp", "%sp", "%pc",
+        ];
+        match self.syntax {
+            Syntax::Macro32 => MACRO32[r as usize],
+            Syntax::Gnu => GNU[r as usize],
+        }
+    }
+
+    fn hex(&self, v: u128) -> String {
+        match self.syntax {
+            Syntax::Macro32 => format!("^X{:X}", v),
+            Syntax::Gnu => format!("0x{:x}", v),
+        }
+    }
+
+    fn deferred(&self) -> &'static str {
+        match self.syntax {
+            Syntax::Macro32 => "@",
+            Syntax::Gnu => "*",
+        }
+    }
+
+    fn imm_sign(&self) -> &'static str {
+        match self.syntax {
+            Syntax::Macro32 => "#",
+            Syntax::Gnu => "$",
+        }
+    }
+
+    fn size_prefix(&self, len: usize) -> &'static str {
+        match (self.syntax, len) {
+            (Syntax::Macro32, 1) => "B^",
+            (Syntax::Macro32, 2) => "W^",
+            (Syntax::Macro32, _) => "L^",
+            (Syntax::Gnu, 1) => "b`",
+            (Syntax::Gnu, 2) => "w`",
+            (Syntax::Gnu, _) => "l`",
+        }
+    }
+
+    /// Short literals of float types hold a small float rather than an integer.
+    fn literal(&self, v: u8, data: DataType) -> String {
+        if data.is_float() {
+            let exp = (v >> 3) as i32;
+            let frac = (v & 0x7) as f64;
+            let val = (8.0 + frac) / 16.0 * 2f64.powi(exp);
+            match self.syntax {
+                Syntax::Macro32 => format!("S^#{:?}", val),
+                Syntax::Gnu => format!("$0f{:?}", val),
+            }
+        } else {
+            match self.syntax {
+                Syntax::Macro32 => format!("S^#{}", v),
+                Syntax::Gnu => format!("${}", v),
+            }
+        }
+    }
+
+    /// Immediates that could have been short literals are marked as such, so
+    /// reassembling them gives back the same bytes.
+    fn immediate(&self, v: u128, data: DataType) -> String {
+        let forced = if data.is_float() || v <= 63 {
+            match self.syntax {
+                Syntax::Macro32 => "I^",
+                Syntax::Gnu => "i`",
+            }
+        } else {
+            ""
+        };
+        format!("{}{}{}", forced, self.imm_sign(), self.hex(v))
+    }
+}
+
+fn mask(len: usize) -> u32 {
+    if len >= 4 {
+        !0
+    } else {
+        (1 << (len * 8)) - 1
+    }
+}
+
+/// Length of the smallest displacement that holds `disp`, which is the one an
+/// assembler picks when it isn't told otherwise.
+fn disp_len(disp: i32) -> usize {
+    if disp as i8 as i32 == disp {
+        1
+    } else if disp as i16 as i32 == disp {
+        2
+    } else {
+        4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(bytes: &[u8], pc: u32, syntax: Syntax) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let (len, text) = disassemble_with(&bytes[pos..], pc + pos as u32, syntax);
+            out.push(text);
+            pos += len;
+        }
+        out
+    }
+
+    #[test]
+    fn addressing_modes() {
+        let prog: &[u8] = &[
+            0xD0, 0xA1, 0x04, 0x53,
+            0xD0, 0xC1, 0x10, 0x00, 0x53,
+            0xD0, 0xB1, 0xFC, 0x53,
+            0xD0, 0x8F, 0x78, 0x56, 0x34, 0x12, 0x53,
+            0xD0, 0x8F, 0x05, 0x00, 0x00, 0x00, 0x53,
+            0xD0, 0x9F, 0x00, 0x11, 0x00, 0x00, 0x53,
+            0xD0, 0xCF, 0xEA, 0x07, 0x53,
+            0xD0, 0x42, 0x91, 0x7E,
+            0x9E, 0x43, 0xE1, 0x10, 0x00, 0x00, 0x00, 0x5E,
+            0x7D, 0x01, 0x50,
+        ];
+        assert_eq!(listing(prog, 0x800, Syntax::Macro32), [
+            "MOVL 4(R1), R3",
+            "MOVL W^16(R1), R3",
+            "MOVL @-4(R1), R3",
+            "MOVL #^X12345678, R3",
+            "MOVL I^#^X5, R3",
+            "MOVL @#^X1100, R3",
+            "MOVL W^^X1010, R3",
+            "MOVL @(R1)+[R2], -(SP)",
+            "MOVAB L^16(R1)[R3], SP",
+            "MOVQ S^#1, R0",
+        ]);
+        assert_eq!(listing(prog, 0x800, Syntax::Gnu), [
+            "movl 4(%r1), %r3",
+            "movl w`16(%r1), %r3",
+            "movl *-4(%r1), %r3",
+            "movl $0x12345678, %r3",
+            "movl i`$0x5, %r3",
+            "movl *$0x1100, %r3",
+            "movl w`0x1010, %r3",
+            "movl *(%r1)+[%r2], -(%sp)",
+            "movab l`16(%r1)[%r3], %sp",
+            "movq $1, %r0",
+        ]);
+    }
+
+    #[test]
+    fn branches_and_signatures() {
+        let prog: &[u8] = &[
+            0x12, 0xFE,                   // BNEQ .
+            0x31, 0x00, 0x01,             // BRW .+0x103
+            0xE0, 0x03, 0x50, 0x02,       // BBS S^#3, R0, .+6
+            0xF5, 0x51, 0xF0,             // SOBGTR R1, ...
+            0xFD, 0x45, 0x0C, 0x50, 0x52, // MULG3 S^#1.5, R0, R2
+            0x2C, 0x50, 0x61, 0x20, 0x50, 0x62, // MOVC5 R0, (R1), S^#32, R0, (R2)
+            0x04,                         // RET
+        ];
+        assert_eq!(listing(prog, 0x200, Syntax::Macro32), [
+            "BNEQ ^X200",
+            "BRW ^X305",
+            "BBS S^#3, R0, ^X20B",
+            "SOBGTR R1, ^X1FC",
+            "MULG3 S^#1.5, R0, R2",
+            "MOVC5 R0, (R1), S^#32, R0, (R2)",
+            "RET",
+        ]);
+        assert_eq!(disassemble_with(&prog[12..], 0, Syntax::Gnu).1, "mulg3 $0f1.5, %r0, %r2");
+    }
+
+    #[test]
+    fn case_tables() {
+        // CASEB R0, S^#0, S^#1 with two table entries, then a HALT.
+        let prog: &[u8] = &[0x8F, 0x50, 0x00, 0x01, 0x04, 0x00, 0x02, 0x00, 0x00];
+        let (len, text) = disassemble(prog, 0);
+        assert_eq!(len, 8);
+        assert_eq!(text, "CASEB R0, S^#0, S^#1\n.WORD 4\n.WORD 2");
+        // Without a constant limit there's no telling how long the table is.
+        let (len, _) = disassemble(&[0x8F, 0x50, 0x00, 0x51], 0);
+        assert_eq!(len, 4);
+    }
+
+    #[test]
+    fn invalid_instructions_are_data() {
+        let cases: &[&[u8]] = &[
+            &[0xFF, 0xFF],             // Not an opcode.
+            &[0xD0, 0x50],             // Missing an operand.
+            &[0xD4, 0x05],             // CLRL S^#5
+            &[0xDE, 0x51, 0x52],       // MOVAL R1, R2
+            &[0xD0, 0x4F, 0x61, 0x50], // PC as an index.
+            &[0x9F, 0x9F, 0x00, 0x11], // Truncated absolute address.
+        ];
+        for bytes in cases {
+            assert_eq!(disassemble(bytes, 0), (1, format!(".BYTE ^X{:02X}", bytes[0])));
+        }
+        assert_eq!(disassemble_with(&[0xD4, 0x05], 0, Syntax::Gnu).1, ".byte 0xd4");
+        assert_eq!(disassemble(&[], 0), (0, String::new()));
+    }
+}
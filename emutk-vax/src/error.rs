@@ -31,6 +31,36 @@ pub enum ErrorKind {
     Debug, // Emulation only.
 }
 
+/// How an exception relates to the instruction that raised it.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum ExceptionClass {
+    /// The instruction is backed out and restarted after the handler returns.
+    Fault,
+    /// The instruction completed, the handler returns to the next one.
+    Trap,
+    /// The instruction can't be restarted.
+    Abort,
+    /// Not dispatched through the SCB, the processor stops.
+    Halt,
+}
+
+impl ErrorKind {
+    pub fn class(self) -> ExceptionClass {
+        use ErrorKind::*;
+        match self {
+            IntegerOverflow | IntegerDivZero | DecimalDivZero | DecimalOverflow
+                | SubscriptRange | ChangeMode => ExceptionClass::Trap,
+            FloatingOverflow | FloatingDivZero | FloatingUnderflow
+                | AccessControlViolation | TranslationNotValid
+                | ReservedAddressingMode | ReservedOperand
+                | ReservedInstruction | PrivilegedInstruction | OpcodeReservedToCustomers
+                | Breakpoint | Trace => ExceptionClass::Fault,
+            KernelStackNotValid | MachineCheck => ExceptionClass::Abort,
+            InterruptStackNotValid | Debug => ExceptionClass::Halt,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Error {
     kind: ErrorKind,
@@ -63,12 +93,130 @@ impl Error {
         }
     }
 
+    pub fn new_access_control_violation(param: u32, va: u32) -> Self {
+        Error {
+            kind: ErrorKind::AccessControlViolation,
+            data: [param, va],
+        }
+    }
+    pub fn new_translation_not_valid(param: u32, va: u32) -> Self {
+        Error {
+            kind: ErrorKind::TranslationNotValid,
+            data: [param, va],
+        }
+    }
+    fn new_arithmetic(kind: ErrorKind, code: u32) -> Self {
+        Error {
+            kind,
+            data: [code, 0],
+        }
+    }
+    pub fn new_integer_overflow_trap() -> Self {
+        Self::new_arithmetic(ErrorKind::IntegerOverflow, 1)
+    }
+    pub fn new_integer_div_zero_trap() -> Self {
+        Self::new_arithmetic(ErrorKind::IntegerDivZero, 2)
+    }
+    pub fn new_decimal_div_zero_trap() -> Self {
+        Self::new_arithmetic(ErrorKind::DecimalDivZero, 4)
+    }
+    pub fn new_decimal_overflow_trap() -> Self {
+        Self::new_arithmetic(ErrorKind::DecimalOverflow, 6)
+    }
+    pub fn new_subscript_range_trap() -> Self {
+        Self::new_arithmetic(ErrorKind::SubscriptRange, 7)
+    }
+    pub fn new_floating_overflow_fault() -> Self {
+        Self::new_arithmetic(ErrorKind::FloatingOverflow, 8)
+    }
+    pub fn new_floating_div_zero_fault() -> Self {
+        Self::new_arithmetic(ErrorKind::FloatingDivZero, 9)
+    }
+    pub fn new_floating_underflow_fault() -> Self {
+        Self::new_arithmetic(ErrorKind::FloatingUnderflow, 10)
+    }
+
+    /// A CHMx instruction, entering `mode` with the sign extended `code`.
+    /// `target` is the mode the opcode asked for, which picks the SCB vector,
+    /// `mode` is that after being limited to the current mode.
+    pub fn new_change_mode_trap(code: u16, target: u8, mode: u8) -> Self {
+        Error {
+            kind: ErrorKind::ChangeMode,
+            data: [code as i16 as i32 as u32, (target as u32) << 8 | mode as u32],
+        }
+    }
+
+    pub fn new_kernel_stack_not_valid() -> Self {
+        Error {
+            kind: ErrorKind::KernelStackNotValid,
+            data: [0; 2],
+        }
+    }
+    pub fn new_interrupt_stack_not_valid() -> Self {
+        Error {
+            kind: ErrorKind::InterruptStackNotValid,
+            data: [0; 2],
+        }
+    }
+    pub fn new_machine_check() -> Self {
+        Error {
+            kind: ErrorKind::MachineCheck,
+            data: [0; 2],
+        }
+    }
+
     pub fn new_debug_halt() -> Self {
         Error {
             kind: ErrorKind::Debug,
             data: [0; 2],
         }
     }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Exception specific data, such as the fault parameter and virtual address
+    /// of a memory management fault.
+    pub fn data(&self) -> [u32; 2] {
+        self.data
+    }
+
+    /// Offset of this exception's vector in the System Control Block, or `None`
+    /// if it isn't dispatched through the SCB.
+    pub fn scb_vector(&self) -> Option<u32> {
+        use ErrorKind::*;
+        Some(match self.kind {
+            MachineCheck => 0x04,
+            KernelStackNotValid => 0x08,
+            ReservedInstruction | PrivilegedInstruction => 0x10,
+            OpcodeReservedToCustomers => 0x14,
+            ReservedOperand => 0x18,
+            ReservedAddressingMode => 0x1C,
+            AccessControlViolation => 0x20,
+            TranslationNotValid => 0x24,
+            Trace => 0x28,
+            Breakpoint => 0x2C,
+            IntegerOverflow | IntegerDivZero | DecimalDivZero | DecimalOverflow
+                | SubscriptRange | FloatingOverflow | FloatingDivZero
+                | FloatingUnderflow => 0x34,
+            ChangeMode => 0x40 + 4 * (self.data[1] >> 8),
+            InterruptStackNotValid | Debug => return None,
+        })
+    }
+
+    /// The longwords pushed below the PC and PSL in this exception's frame,
+    /// in order from the top of the stack.
+    pub fn frame_params(&self) -> &[u32] {
+        use ErrorKind::*;
+        match self.kind {
+            AccessControlViolation | TranslationNotValid => &self.data[..],
+            IntegerOverflow | IntegerDivZero | DecimalDivZero | DecimalOverflow
+                | SubscriptRange | FloatingOverflow | FloatingDivZero
+                | FloatingUnderflow | ChangeMode | MachineCheck => &self.data[..1],
+            _ => &[],
+        }
+    }
 }
 
 impl fmt::Display for Error {
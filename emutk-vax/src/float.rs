@@ -1,80 +1,582 @@
-use std::ops::{
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Neg,
-};
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
-const HFLOAT_SIGN_MASK: u128  = 0x8000_0000_0000_0000_0000_0000_0000_0000;
-const DFLOAT_SIGN_MASK: u64   = 0x8000_0000_0000_0000;
-const HFLOAT_FRACT_MASK: u128 = 0x00FF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF;
-const DFLOAT_FRACT_MASK: u64  = 0x00FF_FFFF_FFFF_FFFF;
+use crate::{Error, VAXNum};
 
-#[derive(Copy, Clone)]
-pub struct HFloat(u128);
+/// The sign bit, which is bit 15 of the first word in every format.
+const FLOAT_SIGN_MASK: u16 = 0x8000;
 
-impl HFloat {
-    pub fn mantissa(self) -> u8 {
-        let lsb = self.0.to_le_bytes()[0];
-        lsb & 0x7F
+/// Layout of a VAX floating point format.
+///
+/// All formats are made of 16 bit words, with the first (lowest addressed) word
+/// holding the sign, the exponent, and the most significant fraction bits. The
+/// fraction continues into the following words, most significant first.
+///
+/// ```text
+///  1 1
+///  5 4                  0
+/// +-+--------+----------+
+/// |S|  EXP   | FRACTION | :A
+/// +-+--------+----------+
+/// |      FRACTION       | :A+2
+/// +---------------------+
+///           ...
+/// ```
+/// A value is 0.1FRACTION * 2^(EXP - bias), with the leading 1 hidden. An exponent
+/// of 0 is a zero if the sign is clear, and a reserved operand if it's set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FloatFormat {
+    words: u32,
+    exp_bits: u32,
+}
+
+impl FloatFormat {
+    pub const F: FloatFormat = FloatFormat { words: 2, exp_bits: 8 };
+    pub const D: FloatFormat = FloatFormat { words: 4, exp_bits: 8 };
+    pub const G: FloatFormat = FloatFormat { words: 4, exp_bits: 11 };
+    pub const H: FloatFormat = FloatFormat { words: 8, exp_bits: 15 };
+
+    #[inline]
+    fn bits(self) -> u32 {
+        self.words * 16
     }
 
-    pub fn fractional(self) -> u128 {
-        self.0 & HFLOAT_FRACT_MASK >> 8
+    /// Number of stored fraction bits, not counting the hidden bit.
+    #[inline]
+    pub fn frac_bits(self) -> u32 {
+        self.bits() - 1 - self.exp_bits
     }
 
-    pub fn sign(self) -> bool {
-        self.0 & HFLOAT_SIGN_MASK != 0
+    #[inline]
+    fn bias(self) -> i32 {
+        1 << (self.exp_bits - 1)
     }
 
-    pub fn finite(self) -> bool {
-        if self.mantissa() == 0x7F {
-            false
-        } else {
-            true
+    #[inline]
+    fn max_exp(self) -> i32 {
+        (1 << self.exp_bits) - 1
+    }
+
+    /// Convert between the in-memory word order and one where the first word is
+    /// the most significant. Swapping twice gives back the original.
+    #[inline]
+    fn swap_words(self, v: u128) -> u128 {
+        let mut res = 0;
+        for i in 0..self.words {
+            let w = (v >> (16 * i)) & 0xFFFF;
+            res |= w << (16 * (self.words - 1 - i));
         }
+        res
+    }
+
+    pub fn unpack(self, raw: u128) -> Result<UnpackedFloat, Error> {
+        let n = self.swap_words(raw);
+        let frac_bits = self.frac_bits();
+        let sign = (n >> (self.bits() - 1)) & 1 != 0;
+        let exp = ((n >> frac_bits) as i32) & self.max_exp();
+        if exp == 0 {
+            return if sign {
+                Err(Error::new_reserved_operand_fault())
+            } else {
+                Ok(UnpackedFloat::ZERO)
+            };
+        }
+
+        let frac = (n & ((1 << frac_bits) - 1)) | (1 << frac_bits);
+        Ok(UnpackedFloat {
+            sign,
+            exp: exp - self.bias(),
+            frac: frac << (127 - frac_bits),
+        })
+    }
+
+    /// Pack a value into this format, rounding it if `round` is set and truncating
+    /// it otherwise. Underflows are a fault if `fu` is set and give zero otherwise.
+    pub fn pack(self, v: UnpackedFloat, round: bool, fu: bool) -> Result<u128, Error> {
+        let v = v.normalized();
+        if v.is_zero() {
+            return Ok(0);
+        }
+
+        let frac_bits = self.frac_bits();
+        let drop = 127 - frac_bits;
+        let mut frac = v.frac;
+        let mut exp = v.exp;
+        if round {
+            let (f, carry) = frac.overflowing_add(1 << (drop - 1));
+            if carry {
+                frac = 1 << 127;
+                exp += 1;
+            } else {
+                frac = f;
+            }
+        }
+
+        let biased = exp + self.bias();
+        if biased > self.max_exp() {
+            return Err(Error::new_floating_overflow_fault());
+        }
+        if biased <= 0 {
+            return if fu {
+                Err(Error::new_floating_underflow_fault())
+            } else {
+                Ok(0)
+            };
+        }
+
+        let n = ((v.sign as u128) << (self.bits() - 1))
+            | ((biased as u128) << frac_bits)
+            | ((frac >> drop) & ((1 << frac_bits) - 1));
+        Ok(self.swap_words(n))
+    }
+
+    /// Expand a short literal operand, which holds a 3 bit exponent and 3 bit fraction.
+    pub fn short_literal(self, lit: u8) -> u128 {
+        let v = UnpackedFloat {
+            sign: false,
+            exp: (lit >> 3) as i32 & 0x7,
+            frac: ((0x8 | (lit & 0x7)) as u128) << 124,
+        };
+        self.pack(v, false, false).unwrap()
     }
 }
 
-impl Neg for HFloat {
-    type Output = HFloat;
+/// A floating point value with the hidden bit made explicit and a wide fraction,
+/// used for computing results in any format.
+///
+/// The value is `frac / 2^128 * 2^exp`. Nonzero values have bit 127 of `frac` set,
+/// a zero `frac` is zero.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnpackedFloat {
+    pub sign: bool,
+    pub exp: i32,
+    pub frac: u128,
+}
 
-    fn neg(self) -> Self::Output {
-        HFloat(self.0 ^ HFLOAT_SIGN_MASK)
+/// Shift right, ORing any bits shifted out into the lowest bit so that rounding
+/// can still tell the result is inexact.
+#[inline]
+fn shift_right_sticky(v: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        v
+    } else if shift >= 128 {
+        (v != 0) as u128
+    } else {
+        (v >> shift) | ((v & ((1 << shift) - 1) != 0) as u128)
     }
 }
 
-pub struct DFloat(u64);
+/// Full 256 bit product of two u128s, as (high, low).
+#[inline]
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const LO: u128 = 0xFFFF_FFFF_FFFF_FFFF;
+    let (a1, a0) = (a >> 64, a & LO);
+    let (b1, b0) = (b >> 64, b & LO);
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+    let mid = (p00 >> 64) + (p01 & LO) + (p10 & LO);
+    let lo = (p00 & LO) | (mid << 64);
+    let hi = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+impl UnpackedFloat {
+    pub const ZERO: UnpackedFloat = UnpackedFloat {
+        sign: false,
+        exp: 0,
+        frac: 0,
+    };
 
-impl DFloat {
-    pub fn mantissa(self) -> u8 {
-        let lsb = self.0.to_le_bytes()[0];
-        lsb & 0x7F
+    #[inline]
+    pub fn is_zero(self) -> bool {
+        self.frac == 0
     }
 
-    pub fn fractional(self) -> u64 {
-        self.0 & DFLOAT_FRACT_MASK
+    fn normalized(self) -> Self {
+        if self.is_zero() {
+            return UnpackedFloat::ZERO;
+        }
+        let lz = self.frac.leading_zeros();
+        UnpackedFloat {
+            sign: self.sign,
+            exp: self.exp - lz as i32,
+            frac: self.frac << lz,
+        }
     }
 
-    pub fn sign(self) -> bool {
-        self.0 & DFLOAT_SIGN_MASK != 0
+    pub fn from_int(v: i128) -> Self {
+        UnpackedFloat {
+            sign: v < 0,
+            exp: 128,
+            frac: v.unsigned_abs(),
+        }.normalized()
     }
 
-    pub fn finite(self) -> bool {
-        if self.mantissa() == 0x7F {
-            false
+    /// Convert a finite f64, which is always exact.
+    pub fn from_f64(v: f64) -> Self {
+        let bits = v.to_bits();
+        let exp = ((bits >> 52) & 0x7FF) as i32;
+        let mant = (bits & ((1 << 52) - 1)) as u128;
+        // IEEE's 1.m is 0.1m one exponent up, and denormals share the lowest one.
+        let (frac, exp) = if exp == 0 {
+            (mant, -1021)
         } else {
-            true
+            (mant | (1 << 52), exp - 1022)
+        };
+        UnpackedFloat {
+            sign: v.is_sign_negative(),
+            exp,
+            frac: frac << 75,
+        }.normalized()
+    }
+
+    /// Convert to an integer, rounding half away from zero if `round` is set and
+    /// truncating otherwise. The flag is set if the result doesn't fit in an i128,
+    /// in which case the low bits are returned.
+    pub fn to_int(self, round: bool) -> (i128, bool) {
+        if self.is_zero() {
+            return (0, false);
+        }
+
+        let mut overflow = false;
+        let mag = if self.exp <= 0 {
+            // Below one, only 0.5 and up can round to anything.
+            (round && self.exp == 0) as u128
+        } else if self.exp < 128 {
+            let shift = (128 - self.exp) as u32;
+            let mut mag = self.frac >> shift;
+            if round && (self.frac >> (shift - 1)) & 1 != 0 {
+                mag += 1;
+            }
+            mag
+        } else {
+            overflow = true;
+            let shift = (self.exp - 128) as u32;
+            if shift < 128 { self.frac << shift } else { 0 }
+        };
+
+        if mag > i128::MAX as u128 {
+            overflow = true;
+        }
+        let v = if self.sign { (mag as i128).wrapping_neg() } else { mag as i128 };
+        (v, overflow)
+    }
+
+    /// Drop all but the top `bits` bits of the fraction.
+    pub fn truncated(self, bits: u32) -> Self {
+        if bits >= 128 {
+            return self;
+        }
+        UnpackedFloat {
+            frac: self.frac & !(u128::MAX >> bits),
+            ..self
+        }.normalized()
+    }
+
+    /// The integer part, truncated, and the fraction part. Both keep the sign.
+    pub fn split_int(self) -> (UnpackedFloat, UnpackedFloat) {
+        if self.is_zero() || self.exp <= 0 {
+            return (UnpackedFloat::ZERO, self);
+        }
+        if self.exp >= 128 {
+            return (self, UnpackedFloat::ZERO);
+        }
+        let mask = u128::MAX >> self.exp;
+        let int = UnpackedFloat { frac: self.frac & !mask, ..self };
+        let fract = UnpackedFloat { frac: self.frac & mask, ..self }.normalized();
+        (int, fract)
+    }
+}
+
+impl Add for UnpackedFloat {
+    type Output = UnpackedFloat;
+
+    fn add(self, other: Self) -> Self::Output {
+        if self.is_zero() {
+            return other;
+        }
+        if other.is_zero() {
+            return self;
+        }
+
+        // Make `a` the larger magnitude.
+        let (a, b) = if (self.exp, self.frac) >= (other.exp, other.frac) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let bf = shift_right_sticky(b.frac, (a.exp - b.exp) as u32);
+
+        if a.sign == b.sign {
+            let (sum, carry) = a.frac.overflowing_add(bf);
+            if carry {
+                UnpackedFloat {
+                    sign: a.sign,
+                    exp: a.exp + 1,
+                    frac: (1 << 127) | shift_right_sticky(sum, 1),
+                }
+            } else {
+                UnpackedFloat { sign: a.sign, exp: a.exp, frac: sum }
+            }
+        } else {
+            UnpackedFloat {
+                sign: a.sign,
+                exp: a.exp,
+                frac: a.frac - bf,
+            }.normalized()
+        }
+    }
+}
+
+impl Sub for UnpackedFloat {
+    type Output = UnpackedFloat;
+
+    fn sub(self, other: Self) -> Self::Output {
+        self + -other
+    }
+}
+
+impl Mul for UnpackedFloat {
+    type Output = UnpackedFloat;
+
+    fn mul(self, other: Self) -> Self::Output {
+        if self.is_zero() || other.is_zero() {
+            return UnpackedFloat::ZERO;
+        }
+
+        let (mut hi, mut lo) = mul_wide(self.frac, other.frac);
+        let mut exp = self.exp + other.exp;
+        if hi >> 127 == 0 {
+            hi = (hi << 1) | (lo >> 127);
+            lo <<= 1;
+            exp -= 1;
+        }
+        UnpackedFloat {
+            sign: self.sign ^ other.sign,
+            exp,
+            frac: hi | (lo != 0) as u128,
         }
     }
 }
 
+/// ## Panics
+/// Panics if dividing by zero.
+impl Div for UnpackedFloat {
+    type Output = UnpackedFloat;
 
-impl Neg for DFloat {
-    type Output = DFloat;
+    fn div(self, other: Self) -> Self::Output {
+        assert!(!other.is_zero());
+        if self.is_zero() {
+            return UnpackedFloat::ZERO;
+        }
+
+        // Long division, giving self.frac / other.frac * 2^127. Both are
+        // normalized, so the first quotient bit is the only one that can be 1
+        // without the remainder having been shifted past 128 bits.
+        let d = other.frac;
+        let mut r = self.frac;
+        let mut q: u128 = 0;
+        for i in 0..128 {
+            let mut carry = false;
+            if i > 0 {
+                carry = r >> 127 != 0;
+                r <<= 1;
+            }
+            q <<= 1;
+            if carry || r >= d {
+                r = r.wrapping_sub(d);
+                q |= 1;
+            }
+        }
+        UnpackedFloat {
+            sign: self.sign ^ other.sign,
+            exp: self.exp - other.exp + 1,
+            frac: q | (r != 0) as u128,
+        }.normalized()
+    }
+}
+
+impl Neg for UnpackedFloat {
+    type Output = UnpackedFloat;
 
     fn neg(self) -> Self::Output {
-        DFloat(self.0 ^ DFLOAT_SIGN_MASK)
+        if self.is_zero() {
+            self
+        } else {
+            UnpackedFloat { sign: !self.sign, ..self }
+        }
     }
-}
\ No newline at end of file
+}
+
+impl PartialOrd for UnpackedFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UnpackedFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.is_zero(), other.is_zero()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => if other.sign { Ordering::Greater } else { Ordering::Less },
+            (false, true) => if self.sign { Ordering::Less } else { Ordering::Greater },
+            (false, false) => {
+                if self.sign != other.sign {
+                    return if self.sign { Ordering::Less } else { Ordering::Greater };
+                }
+                let mag = (self.exp, self.frac).cmp(&(other.exp, other.frac));
+                if self.sign { mag.reverse() } else { mag }
+            }
+        }
+    }
+}
+
+/// A VAX floating point type, stored as its raw bits.
+pub trait VAXFloat: Copy {
+    type Raw: VAXNum;
+    const FORMAT: FloatFormat;
+
+    fn from_raw(raw: Self::Raw) -> Self;
+    fn to_raw(self) -> Self::Raw;
+
+    /// Unpack the value, failing with a reserved operand fault on -0.
+    fn unpack(self) -> Result<UnpackedFloat, Error>;
+    fn pack(v: UnpackedFloat, round: bool, fu: bool) -> Result<Self, Error>;
+
+    fn from_short_literal(lit: u8) -> Self;
+}
+
+macro_rules! impl_vaxfloat {
+    ($($name:ident, $raw:ty, $fmt:expr);+) => {
+        $(
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub struct $name(pub $raw);
+
+        impl VAXFloat for $name {
+            type Raw = $raw;
+            const FORMAT: FloatFormat = $fmt;
+
+            #[inline]
+            fn from_raw(raw: $raw) -> Self {
+                $name(raw)
+            }
+
+            #[inline]
+            fn to_raw(self) -> $raw {
+                self.0
+            }
+
+            fn unpack(self) -> Result<UnpackedFloat, Error> {
+                Self::FORMAT.unpack(self.0 as u128)
+            }
+
+            fn pack(v: UnpackedFloat, round: bool, fu: bool) -> Result<Self, Error> {
+                Ok($name(Self::FORMAT.pack(v, round, fu)? as $raw))
+            }
+
+            fn from_short_literal(lit: u8) -> Self {
+                $name(Self::FORMAT.short_literal(lit) as $raw)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> Self::Output {
+                $name(self.0 ^ FLOAT_SIGN_MASK as $raw)
+            }
+        }
+        )+
+    };
+}
+
+impl_vaxfloat!(
+    FFloat, u32, FloatFormat::F;
+    DFloat, u64, FloatFormat::D;
+    GFloat, u64, FloatFormat::G;
+    HFloat, u128, FloatFormat::H
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn f(v: UnpackedFloat) -> u32 {
+        FFloat::pack(v, true, false).unwrap().0
+    }
+
+    #[test]
+    fn ffloat_encoding() {
+        // 1.0 is 0.1b * 2^1, exponent 129.
+        let one = FFloat(0x0000_4080);
+        let u = one.unpack().unwrap();
+        assert_eq!(u, UnpackedFloat::from_int(1));
+        assert_eq!(f(UnpackedFloat::from_int(1)), 0x4080);
+        assert_eq!(f(UnpackedFloat::from_int(-3)), 0x0000_C140);
+        let tenth = UnpackedFloat::from_int(1) / UnpackedFloat::from_int(10);
+        assert_eq!(f(tenth), 0xCCCD_3ECC);
+        assert_eq!(f(UnpackedFloat::from_f64(0.1)), 0xCCCD_3ECC);
+        assert_eq!(UnpackedFloat::from_f64(-3.0), UnpackedFloat::from_int(-3));
+        assert!(UnpackedFloat::from_f64(-0.0).is_zero());
+
+        assert_eq!(FFloat(0x8000).unpack().unwrap_err().kind(), ErrorKind::ReservedOperand);
+        assert!(FFloat(0x0000_007F).unpack().unwrap().is_zero()); // Dirty zero.
+        assert_eq!(FFloat::from_short_literal(0).0, 0x4000); // 0.5
+        assert_eq!(FFloat::from_short_literal(63).0, 0x43F0); // 120.0
+        assert_eq!(DFloat::from_short_literal(63).0, 0x43F0);
+        assert_eq!(GFloat::from_short_literal(0).0, 0x4000);
+        assert_eq!(HFloat::from_short_literal(8).0, 0x4001); // 1.0
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = UnpackedFloat::from_int(7);
+        let b = UnpackedFloat::from_int(-2);
+        assert_eq!(a + b, UnpackedFloat::from_int(5));
+        assert_eq!(b - a, UnpackedFloat::from_int(-9));
+        assert_eq!(a * b, UnpackedFloat::from_int(-14));
+        assert_eq!((a / b).to_int(false), (-3, false));
+        assert_eq!((a / b).to_int(true), (-4, false));
+        assert!((a - a).is_zero());
+        assert!(b < a);
+        assert!(-a < b);
+
+        let third = UnpackedFloat::from_int(1) / UnpackedFloat::from_int(3);
+        let d = DFloat::pack(third, true, false).unwrap();
+        assert_eq!(d.0, 0xAAAB_AAAA_AAAA_3FAA);
+    }
+
+    #[test]
+    fn range_faults() {
+        let big = FFloat(0xFFFF_7FFF).unpack().unwrap();
+        let e = FFloat::pack(big * big, true, false).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::FloatingOverflow);
+
+        let tiny = FFloat(0x0000_0080).unpack().unwrap();
+        assert_eq!(FFloat::pack(tiny * tiny, true, false).unwrap().0, 0);
+        let e = FFloat::pack(tiny * tiny, true, true).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::FloatingUnderflow);
+
+        // The same value fits easily in G, which has a wider exponent.
+        assert!(GFloat::pack(big * big, true, false).is_ok());
+    }
+
+    #[test]
+    fn extended_formats() {
+        let third = UnpackedFloat::from_int(1) / UnpackedFloat::from_int(3);
+        assert_eq!(GFloat::pack(third, true, false).unwrap().0, 0x5555_5555_5555_3FF5);
+        assert_eq!(GFloat::pack(UnpackedFloat::from_int(1), true, false).unwrap().0, 0x4010);
+
+        // H keeps the whole first word for sign and exponent.
+        let h = HFloat::pack(UnpackedFloat::from_int(-3), true, false).unwrap();
+        assert_eq!(h.0, 0x8000_C002);
+        assert_eq!(h.unpack().unwrap().to_int(false), (-3, false));
+        let big = UnpackedFloat::from_int(i64::MAX as i128);
+        let h = HFloat::pack(big, true, false).unwrap();
+        assert_eq!(h.unpack().unwrap().to_int(false), (i64::MAX as i128, false));
+        assert_eq!((-h).unpack().unwrap(), -big);
+    }
+}
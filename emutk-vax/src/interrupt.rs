@@ -0,0 +1,150 @@
+use std::sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+};
+
+/// Lowest IPL a device may request at.
+pub const DEVICE_IPL_MIN: u8 = 0x14;
+/// Highest IPL a device may request at.
+pub const DEVICE_IPL_MAX: u8 = 0x17;
+
+struct LineState {
+    ipl: u8,
+    vector: u32,
+    asserted: bool,
+}
+
+struct Shared {
+    /// Bit n is set while any line at IPL n is asserted.
+    summary: AtomicU32,
+    lines: Mutex<Vec<LineState>>,
+}
+
+impl Shared {
+    fn update_summary(&self, lines: &[LineState]) {
+        let summary = lines.iter()
+            .filter(|l| l.asserted)
+            .fold(0, |acc, l| acc | (1 << l.ipl));
+        self.summary.store(summary, Ordering::Release);
+    }
+}
+
+/// Hardware interrupt request lines shared between devices and the CPU.
+///
+/// Devices assert a line at one of the device IPLs (0x14 to 0x17), and the CPU
+/// takes the request once its IPL drops below that level. When several lines at
+/// the same IPL are asserted, the one created first wins, like a bus grant chain.
+/// A request is cleared when the CPU acknowledges it.
+#[derive(Clone)]
+pub struct InterruptController {
+    shared: Arc<Shared>,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        InterruptController {
+            shared: Arc::new(Shared {
+                summary: AtomicU32::new(0),
+                lines: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// Create a new request line at `ipl`, vectoring through SCB offset `vector`.
+    /// ## Panics
+    /// Panics if `ipl` isn't a device IPL, or `vector` isn't longword aligned.
+    pub fn line(&self, ipl: u8, vector: u32) -> InterruptLine {
+        assert!((DEVICE_IPL_MIN..=DEVICE_IPL_MAX).contains(&ipl));
+        assert!(vector & 0x3 == 0);
+        let mut lines = self.shared.lines.lock().unwrap();
+        lines.push(LineState {
+            ipl,
+            vector,
+            asserted: false,
+        });
+        InterruptLine {
+            shared: self.shared.clone(),
+            idx: lines.len() - 1,
+        }
+    }
+
+    /// The highest IPL with an asserted line, or 0 if there are none.
+    #[inline]
+    pub fn highest_pending(&self) -> u8 {
+        let summary = self.shared.summary.load(Ordering::Acquire);
+        (32 - summary.leading_zeros()).saturating_sub(1) as u8
+    }
+
+    /// Acknowledge the highest priority request at `ipl`, returning its vector.
+    pub(crate) fn acknowledge(&self, ipl: u8) -> Option<u32> {
+        let mut lines = self.shared.lines.lock().unwrap();
+        let line = lines.iter_mut().find(|l| l.asserted && l.ipl == ipl)?;
+        line.asserted = false;
+        let vector = line.vector;
+        self.shared.update_summary(&lines);
+        Some(vector)
+    }
+}
+
+impl Default for InterruptController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A device's handle to its interrupt request line.
+pub struct InterruptLine {
+    shared: Arc<Shared>,
+    idx: usize,
+}
+
+impl InterruptLine {
+    pub fn assert(&self) {
+        self.set(true);
+    }
+
+    /// Withdraw a request that hasn't been acknowledged yet.
+    pub fn deassert(&self) {
+        self.set(false);
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.shared.lines.lock().unwrap()[self.idx].asserted
+    }
+
+    fn set(&self, val: bool) {
+        let mut lines = self.shared.lines.lock().unwrap();
+        lines[self.idx].asserted = val;
+        self.shared.update_summary(&lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitration() {
+        let ic = InterruptController::new();
+        let a = ic.line(0x15, 0x200);
+        let b = ic.line(0x15, 0x204);
+        let c = ic.line(0x17, 0x208);
+        assert_eq!(ic.highest_pending(), 0);
+
+        b.assert();
+        a.assert();
+        c.assert();
+        assert_eq!(ic.highest_pending(), 0x17);
+        assert_eq!(ic.acknowledge(0x17), Some(0x208));
+        assert!(!c.is_asserted());
+        assert_eq!(ic.highest_pending(), 0x15);
+        assert_eq!(ic.acknowledge(0x15), Some(0x200));
+        assert_eq!(ic.acknowledge(0x15), Some(0x204));
+        assert_eq!(ic.acknowledge(0x15), None);
+
+        a.assert();
+        a.deassert();
+        assert_eq!(ic.highest_pending(), 0);
+    }
+}
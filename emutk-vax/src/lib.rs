@@ -5,12 +5,17 @@ extern crate test;
 pub mod cpu;
 pub mod bus;
 pub mod mmu;
+pub mod interrupt;
+pub mod disasm;
+pub mod asm;
 mod error;
 pub use error::*;
 mod arith;
 pub use arith::*;
 mod float;
 pub use float::*;
+mod decimal;
+pub use decimal::*;
 
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -2,14 +2,25 @@ use crate::cpu::{
     PrivilegeMode,
     VAXCPU,
 };
-
-use emutk_core::{
-    ByteRepr,
-};
+use crate::bus::VAXBus;
+use crate::{Error, ErrorKind};
 
 use num_traits::{ToPrimitive, FromPrimitive};
 use num_derive::*;
 
+/// Size of a VAX page, in bytes.
+pub const PAGE_SIZE: u32 = 512;
+const PAGE_SHIFT: u32 = 9;
+const PAGE_OFFSET_MASK: u32 = PAGE_SIZE - 1;
+const VPN_MASK: u32 = 0x1F_FFFF;
+
+/// Memory management fault parameter: the fault was a length violation.
+pub const MM_PARAM_LENGTH: u32 = 0x1;
+/// Memory management fault parameter: the fault occured while fetching a process PTE.
+pub const MM_PARAM_PTE_REF: u32 = 0x2;
+/// Memory management fault parameter: the faulting access was a write or modify.
+pub const MM_PARAM_WRITE: u32 = 0x4;
+
 #[derive(Clone, Debug)]
 pub enum MMUDenyReasons {
     InvalidPTE(u32),
@@ -48,30 +59,559 @@ pub enum MemoryAccessType {
     Write,
 }
 
+/// Least privileged mode allowed to (write, read) a page, indexed by protection code.
+/// -1 means no mode has that access.
+const PROTECTION_TABLE: [(i8, i8); 16] = [
+    (-1, -1), (-1, 3),
+    ( 0,  0), (-1, 0),
+    ( 3,  3), ( 1, 1),
+    ( 0,  1), (-1, 1),
+    ( 2,  2), ( 1, 2),
+    ( 0,  2), (-1, 2),
+    ( 2,  3), ( 1, 3),
+    ( 0,  3), (-1, 3),
+];
+
 impl PTEProtectionCode {
     pub fn can_access(self, mode: PrivilegeMode, access: MemoryAccessType) -> bool {
-        let m = self.to_u8().unwrap();
-        let rm = ((m & 0b1100) >> 2) as u8;
-        let wm = !(m & 0b0011) as u8;
+        let (wm, rm) = PROTECTION_TABLE[self.to_u8().unwrap() as usize];
+        let m = mode.to_i8().unwrap();
+
+        // Write access always implies read access, so only writes need the tighter check.
+        match access {
+            MemoryAccessType::Read => m <= rm,
+            MemoryAccessType::Write => m <= wm,
+        }
+    }
+}
+
+/// The four quarters of the 32-bit virtual address space.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VirtualRegion {
+    /// Program region, grows upwards from 0x0000_0000.
+    P0,
+    /// Control region, grows downwards from 0x7FFF_FFFF.
+    P1,
+    /// System region, begins at 0x8000_0000.
+    S0,
+    /// Reserved region, any access is a length violation.
+    Reserved,
+}
+
+impl VirtualRegion {
+    pub fn of(va: u32) -> VirtualRegion {
+        match va >> 30 {
+            0 => VirtualRegion::P0,
+            1 => VirtualRegion::P1,
+            2 => VirtualRegion::S0,
+            _ => VirtualRegion::Reserved,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C)]
+/// Page Table Entry
+///
+/// ```text
+///  3 3     2 2 2 2 2 2 2 2 2 1
+///  1 0     7 6 5     1 0 9 8                                     0
+/// +-+-------+-+---------+-------------------------------------------+
+/// |V| PROT  |M|   MBZ   |                    PFN                    |
+/// +-+-------+-+---------+-------------------------------------------+
+/// ```
+/// - V: Valid
+/// - PROT: Protection Code
+/// - M: Modified
+/// - PFN: Page Frame Number
+pub struct PTE(pub u32);
+
+impl PTE {
+    pub fn get_v(self) -> bool {
+        (self.0 & 0x8000_0000) != 0
+    }
+
+    pub fn get_prot(self) -> PTEProtectionCode {
+        PTEProtectionCode::from_u32((self.0 >> 27) & 0xF).unwrap()
+    }
+
+    pub fn get_m(self) -> bool {
+        (self.0 & 0x0400_0000) != 0
+    }
+
+    pub fn set_m(&mut self, val: bool) {
+        let val = val as u32;
+        self.0 &= !0x0400_0000;
+        self.0 |= val << 26;
+    }
+
+    pub fn get_pfn(self) -> u32 {
+        self.0 & 0x001F_FFFF
+    }
+}
+
+#[inline]
+pub fn page_offset(addr: u32) -> u32 {
+    addr & PAGE_OFFSET_MASK
+}
+
+#[inline]
+pub fn vpn(va: u32) -> u32 {
+    (va >> PAGE_SHIFT) & VPN_MASK
+}
+
+/// Number of entries in each half of the translation buffer.
+const TB_ENTRIES: usize = 256;
+
+#[derive(Copy, Clone, Debug)]
+struct TBEntry {
+    /// Virtual page (VA<31:9>) this entry maps, or `None` if the entry is empty.
+    tag: Option<u32>,
+    pte: PTE,
+    /// Physical address the PTE was fetched from, for writing back the M bit.
+    pte_addr: u32,
+}
+
+const EMPTY_TB_ENTRY: TBEntry = TBEntry {
+    tag: None,
+    pte: PTE(0),
+    pte_addr: 0,
+};
+
+/// Software translation buffer.
+///
+/// Caches valid PTEs in two direct mapped halves, one for system space and one for
+/// process space, so that a context switch only has to throw away the latter.
+pub struct TranslationBuffer {
+    system: [TBEntry; TB_ENTRIES],
+    process: [TBEntry; TB_ENTRIES],
+
+    hits: u64,
+    misses: u64,
+}
+
+impl TranslationBuffer {
+    pub fn new() -> Self {
+        TranslationBuffer {
+            system: [EMPTY_TB_ENTRY; TB_ENTRIES],
+            process: [EMPTY_TB_ENTRY; TB_ENTRIES],
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    #[inline]
+    fn slot(&mut self, va: u32) -> &mut TBEntry {
+        let idx = (va >> PAGE_SHIFT) as usize % TB_ENTRIES;
+        if va & 0x8000_0000 != 0 {
+            &mut self.system[idx]
+        } else {
+            &mut self.process[idx]
+        }
+    }
+
+    #[inline]
+    fn lookup(&mut self, va: u32) -> Option<(PTE, u32)> {
+        let tag = va >> PAGE_SHIFT;
+        let entry = *self.slot(va);
+        if entry.tag == Some(tag) {
+            self.hits += 1;
+            Some((entry.pte, entry.pte_addr))
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    #[inline]
+    fn insert(&mut self, va: u32, pte: PTE, pte_addr: u32) {
+        *self.slot(va) = TBEntry {
+            tag: Some(va >> PAGE_SHIFT),
+            pte,
+            pte_addr,
+        };
+    }
+
+    /// Check if there is a valid translation cached for `va`. Used by TBCHK.
+    pub fn check(&mut self, va: u32) -> bool {
+        self.slot(va).tag == Some(va >> PAGE_SHIFT)
+    }
+
+    /// Drop every cached translation. Used by TBIA.
+    pub fn invalidate_all(&mut self) {
+        self.system = [EMPTY_TB_ENTRY; TB_ENTRIES];
+        self.process = [EMPTY_TB_ENTRY; TB_ENTRIES];
+    }
+
+    /// Drop every cached P0 and P1 translation. Used by LDPCTX.
+    pub fn invalidate_process(&mut self) {
+        self.process = [EMPTY_TB_ENTRY; TB_ENTRIES];
+    }
+
+    /// Drop the cached translation for the page containing `va`, if any. Used by TBIS.
+    pub fn invalidate_single(&mut self, va: u32) {
+        let entry = self.slot(va);
+        if entry.tag == Some(va >> PAGE_SHIFT) {
+            *entry = EMPTY_TB_ENTRY;
+        }
+    }
+
+    /// Number of translations served from the buffer.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of translations that required a page table walk.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
 
-        // This is a magic algorithm that DEC used for this same purpose.
-        // Don't worry too much about how it works, it just does(tm)
-        match mode.to_u8().unwrap() {
-            16..=std::u8::MAX => unreachable!(),
+impl Default for TranslationBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            0 => false,
-            4 => true,
+impl<B: VAXBus> VAXCPU<'_, B> {
+    /// Translate a virtual address to a physical address, as if accessed in `mode`.
+    ///
+    /// Raises the architecturally defined access control violation or translation not
+    /// valid fault if the access isn't possible, and sets the PTE's M bit on writes.
+    pub fn translate(&mut self, va: u32, mode: PrivilegeMode, access: MemoryAccessType)
+        -> Result<u32, Error>
+    {
+        let wparam = if access == MemoryAccessType::Write { MM_PARAM_WRITE } else { 0 };
 
-            v if v < wm => {
-                true
+        let (mut pte, pte_addr) = match self.tb.lookup(va) {
+            Some(v) => v,
+            None => {
+                let pte_addr = self.walk_page_tables(va, wparam)?;
+                (PTE(self.read_phys(pte_addr)?), pte_addr)
+            }
+        };
+
+        // Protection is checked before validity, so an inaccessible invalid page
+        // is an access violation rather than a page fault.
+        if !pte.get_prot().can_access(mode, access) {
+            return Err(Error::new_access_control_violation(wparam, va));
+        }
+
+        if !pte.get_v() {
+            return Err(Error::new_translation_not_valid(wparam, va));
+        }
+
+        if access == MemoryAccessType::Write && !pte.get_m() {
+            pte.set_m(true);
+            self.write_phys(pte_addr, pte.0)?;
+        }
+        self.tb.insert(va, pte, pte_addr);
+
+        Ok((pte.get_pfn() << PAGE_SHIFT) | page_offset(va))
+    }
+
+    /// Check if `va` could be accessed in `mode` without an access control
+    /// violation, ignoring the PTE's valid bit. Used by PROBER and PROBEW, so
+    /// a length violation just means no access, but a process page table that
+    /// isn't valid still faults.
+    pub fn probe(&mut self, va: u32, mode: PrivilegeMode, access: MemoryAccessType)
+        -> Result<bool, Error>
+    {
+        if !self.regfile.get_mapen() {
+            return Ok(true);
+        }
+        let pte = match self.tb.lookup(va) {
+            Some((pte, _)) => pte,
+            None => match self.walk_page_tables(va, 0) {
+                Ok(pte_addr) => PTE(self.read_phys(pte_addr)?),
+                Err(e) if e.kind() == ErrorKind::AccessControlViolation => return Ok(false),
+                Err(e) => return Err(e),
             },
+        };
+        Ok(pte.get_prot().can_access(mode, access))
+    }
+
+    /// Find the physical address of the PTE mapping `va`, checking region lengths.
+    fn walk_page_tables(&mut self, va: u32, wparam: u32) -> Result<u32, Error> {
+        let vpn = vpn(va);
 
-            v if (access == MemoryAccessType::Read) && (v <= rm)  => {
-                true
+        match VirtualRegion::of(va) {
+            VirtualRegion::S0 => {
+                if vpn >= self.regfile.get_slr() {
+                    return Err(Error::new_access_control_violation(MM_PARAM_LENGTH | wparam, va));
+                }
+                Ok(self.regfile.get_sbr().wrapping_add(vpn << 2))
+            },
+            VirtualRegion::P0 => {
+                if vpn >= self.regfile.get_p0lr() {
+                    return Err(Error::new_access_control_violation(MM_PARAM_LENGTH | wparam, va));
+                }
+                let pte_va = self.regfile.get_p0br().wrapping_add(vpn << 2);
+                self.process_pte_address(pte_va, va, wparam)
+            },
+            VirtualRegion::P1 => {
+                if vpn < self.regfile.get_p1lr() {
+                    return Err(Error::new_access_control_violation(MM_PARAM_LENGTH | wparam, va));
+                }
+                let pte_va = self.regfile.get_p1br().wrapping_add(vpn << 2);
+                self.process_pte_address(pte_va, va, wparam)
             },
+            VirtualRegion::Reserved => {
+                Err(Error::new_access_control_violation(MM_PARAM_LENGTH | wparam, va))
+            },
+        }
+    }
+
+    /// Find the physical address of a process PTE, given its address in system space.
+    /// Faults here are reported against the original virtual address with the
+    /// PTE reference bit set.
+    fn process_pte_address(&mut self, pte_va: u32, va: u32, wparam: u32) -> Result<u32, Error> {
+        let param = MM_PARAM_PTE_REF | wparam;
+        let svpn = vpn(pte_va);
+
+        if VirtualRegion::of(pte_va) != VirtualRegion::S0 || svpn >= self.regfile.get_slr() {
+            return Err(Error::new_access_control_violation(MM_PARAM_LENGTH | param, va));
+        }
+
+        let spte = PTE(self.read_phys(self.regfile.get_sbr().wrapping_add(svpn << 2))?);
+        if !spte.get_v() {
+            return Err(Error::new_translation_not_valid(param, va));
+        }
+
+        Ok((spte.get_pfn() << PAGE_SHIFT) | page_offset(pte_va))
+    }
+
+    /// Translate an access of `len` bytes, which may straddle a page boundary.
+    /// Both pages are checked before anything is touched, so a fault on the second page
+    /// leaves no partial access behind.
+    pub(crate) fn translate_range(&mut self, va: u32, len: usize, access: MemoryAccessType)
+        -> Result<(u32, Option<u32>), Error>
+    {
+        let mode = PrivilegeMode::from_u8(self.regfile.get_psl().get_cur_mod()).unwrap();
+        let first = self.translate(va, mode, access)?;
+        if page_offset(va) as usize + len > PAGE_SIZE as usize {
+            let second_va = (va | PAGE_OFFSET_MASK).wrapping_add(1);
+            let second = self.translate(second_va, mode, access)?;
+            Ok((first, Some(second)))
+        } else {
+            Ok((first, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::exec::simple_test_cpu;
+    use crate::ErrorKind;
+
+    const SBR: u32 = 0x1000;
 
-            _ => false,
+    fn map_system_page(bus: &mut crate::bus::RAMBus, vpn: u32, pte: u32) {
+        let addr = (SBR + vpn * 4) as usize;
+        bus.ram_mut()[addr..addr+4].copy_from_slice(&pte.to_le_bytes());
+    }
+
+    #[test]
+    fn protection_codes() {
+        use MemoryAccessType::*;
+        assert!(PTEProtectionCode::KernW.can_access(PrivilegeMode::Kernel, Write));
+        assert!(!PTEProtectionCode::KernW.can_access(PrivilegeMode::Executive, Read));
+        assert!(PTEProtectionCode::UserRKernW.can_access(PrivilegeMode::User, Read));
+        assert!(!PTEProtectionCode::UserRKernW.can_access(PrivilegeMode::Executive, Write));
+        assert!(PTEProtectionCode::SuperRExecW.can_access(PrivilegeMode::Supervisor, Read));
+        assert!(!PTEProtectionCode::NoAccess.can_access(PrivilegeMode::Kernel, Read));
+    }
+
+    #[test]
+    fn system_space_translation() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 page 0 -> PFN 4, kernel writable, not yet modified.
+        map_system_page(&mut bus, 0, 0x8000_0000 | (0b0010 << 27) | 4);
+        // S0 page 1 -> invalid, user readable.
+        map_system_page(&mut bus, 1, 0b1111 << 27);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_mapen(true);
+
+        cpu.write_val::<u32>(0x8000_0010, 0xDEAD_BEEF).unwrap();
+        assert_eq!(cpu.read_phys::<u32>(0x810).unwrap(), 0xDEAD_BEEF);
+        assert!(PTE(cpu.read_phys::<u32>(SBR).unwrap()).get_m());
+
+        let e = cpu.read_val::<u32>(0x8000_0200).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TranslationNotValid);
+        assert_eq!(e.data(), [0, 0x8000_0200]);
+
+        let e = cpu.write_val::<u8>(0x8000_0400, 0).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AccessControlViolation);
+        assert_eq!(e.data(), [MM_PARAM_LENGTH | MM_PARAM_WRITE, 0x8000_0400]);
+
+        // Straddling into the invalid page must fault without writing the first half.
+        assert!(cpu.write_val::<u32>(0x8000_01FE, 0x1234_5678).is_err());
+        assert_eq!(cpu.read_phys::<u16>(0x9FE).unwrap(), 0);
+
+        cpu.regfile.get_psl_mut().set_cur_mod(3);
+        let e = cpu.read_val::<u32>(0x8000_0000).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AccessControlViolation);
+        assert_eq!(e.data(), [0, 0x8000_0000]);
+    }
+
+    #[test]
+    fn block_access() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 pages 0 and 1 are physically backwards, page 2 is invalid.
+        map_system_page(&mut bus, 0, 0x8000_0000 | (0b0010 << 27) | 5);
+        map_system_page(&mut bus, 1, 0x8000_0000 | (0b0010 << 27) | 4);
+        map_system_page(&mut bus, 2, 0b0010 << 27);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(3);
+        cpu.regfile.set_mapen(true);
+
+        cpu.write_block(0x8000_01FE, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(cpu.read_phys::<u16>(0xBFE).unwrap(), 0x0201);
+        assert_eq!(cpu.read_phys::<u16>(0x800).unwrap(), 0x0403);
+        let mut buf = [0; 4];
+        cpu.read_block(0x8000_01FE, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        // Overlapping copies see the source as it was.
+        cpu.copy_block(0x8000_01FE, 0x8000_01FF, 4).unwrap();
+        cpu.read_block(0x8000_01FE, &mut buf).unwrap();
+        assert_eq!(buf, [1, 1, 2, 3]);
+        cpu.copy_block(0x8000_01FF, 0x8000_01FE, 4).unwrap();
+        cpu.read_block(0x8000_01FE, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        // Faults on any page come before anything is written.
+        let e = cpu.fill_block(0x8000_03FE, 0xAA, 4).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TranslationNotValid);
+        assert_eq!(e.data(), [MM_PARAM_WRITE, 0x8000_0400]);
+        assert_eq!(cpu.read_phys::<u16>(0x9FE).unwrap(), 0);
+        cpu.fill_block(0x8000_03FE, 0xAA, 2).unwrap();
+        assert_eq!(cpu.read_phys::<u16>(0x9FE).unwrap(), 0xAAAA);
+    }
+
+    #[test]
+    fn overlapping_block_copies() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 pages 0 to 2 are scattered around physical memory.
+        for (vpn, pfn) in [6, 4, 5].iter().enumerate() {
+            map_system_page(&mut bus, vpn as u32, 0x8000_0000 | (0b0010 << 27) | pfn);
+        }
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(3);
+        cpu.regfile.set_mapen(true);
 
+        let mut model: Vec<u8> = (0..0x600).map(|i| (i * 7 + i / 256) as u8).collect();
+        cpu.write_block(0x8000_0000, &model).unwrap();
+        // Upwards and downwards, each crossing both page boundaries on both
+        // sides, at different offsets into the pages.
+        for &(src, dst, len) in &[(0x1E0, 0x1F0, 0x230), (0x3F0, 0x1E8, 0x200), (0x10, 0x1FF, 0x3F0)] {
+            cpu.copy_block(0x8000_0000 + src, 0x8000_0000 + dst, len).unwrap();
+            model.copy_within(src as usize..(src + len) as usize, dst as usize);
+            let mut buf = vec![0; 0x600];
+            cpu.read_block(0x8000_0000, &mut buf).unwrap();
+            assert!(buf == model, "copy {:#x} -> {:#x}", src, dst);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn probe_checks_protection_only() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 page 0 -> PFN 4, kernel writable.
+        map_system_page(&mut bus, 0, 0x8000_0000 | (0b0010 << 27) | 4);
+        // S0 page 1 -> invalid, user readable.
+        map_system_page(&mut bus, 1, 0b1111 << 27);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_mapen(true);
+
+        use MemoryAccessType::*;
+        assert!(cpu.probe(0x8000_0000, PrivilegeMode::Kernel, Write).unwrap());
+        assert!(!cpu.probe(0x8000_0000, PrivilegeMode::User, Read).unwrap());
+        assert!(cpu.probe(0x8000_0200, PrivilegeMode::User, Read).unwrap());
+        assert!(!cpu.probe(0x8000_0200, PrivilegeMode::User, Write).unwrap());
+        // Length violations are just no access.
+        assert!(!cpu.probe(0x8000_0400, PrivilegeMode::Kernel, Read).unwrap());
+
+        // But an invalid process page table still faults.
+        cpu.regfile.set_p0br(0x8000_0200);
+        cpu.regfile.set_p0lr(1);
+        let e = cpu.probe(0, PrivilegeMode::Kernel, Read).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TranslationNotValid);
+    }
+
+    #[test]
+    fn process_space_translation() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        // S0 page 0 holds the P0 page table, at physical page 3.
+        map_system_page(&mut bus, 0, 0x8000_0000 | (0b0010 << 27) | 3);
+        // S0 page 1 holds nothing.
+        map_system_page(&mut bus, 1, 0b0010 << 27);
+        // P0 page 0 -> PFN 5, user writable.
+        bus.ram_mut()[0x600..0x604].copy_from_slice(&(0x8000_0000_u32 | (0b0100 << 27) | 5).to_le_bytes());
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_p0br(0x8000_0000);
+        cpu.regfile.set_p0lr(1);
+        cpu.regfile.set_mapen(true);
+
+        cpu.write_val::<u16>(0x0000_0020, 0xAA55).unwrap();
+        assert_eq!(cpu.read_phys::<u16>(0xA20).unwrap(), 0xAA55);
+
+        let e = cpu.read_val::<u8>(0x0000_0200).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AccessControlViolation);
+        assert_eq!(e.data(), [MM_PARAM_LENGTH, 0x200]);
+
+        // A page table living in an invalid system page faults with the PTE reference bit.
+        cpu.regfile.set_p0br(0x8000_0200);
+        cpu.write_msr(57, 0).unwrap(); // TBIA
+        let e = cpu.read_val::<u8>(0x0000_0000).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TranslationNotValid);
+        assert_eq!(e.data(), [MM_PARAM_PTE_REF, 0]);
+    }
+
+    #[test]
+    fn translation_buffer() {
+        let (mut cpu, mut bus) = simple_test_cpu();
+        map_system_page(&mut bus, 0, 0x8000_0000 | (0b0010 << 27) | (1 << 26) | 4);
+        map_system_page(&mut bus, 1, 0x8000_0000 | (0b0010 << 27) | 5);
+        cpu.give_bus(&mut bus);
+        cpu.regfile.set_sbr(SBR);
+        cpu.regfile.set_slr(2);
+        cpu.regfile.set_mapen(true);
+
+        cpu.read_val::<u32>(0x8000_0000).unwrap();
+        cpu.read_val::<u32>(0x8000_0004).unwrap();
+        assert_eq!((cpu.tb.hits(), cpu.tb.misses()), (1, 1));
+
+        cpu.write_msr(63, 0x8000_0000).unwrap(); // TBCHK
+        assert!(cpu.regfile.get_psl().get_z());
+        cpu.write_msr(63, 0x8000_0200).unwrap();
+        assert!(!cpu.regfile.get_psl().get_z());
+
+        // Remapping a page isn't seen until the stale entry is invalidated.
+        cpu.write_phys::<u32>(SBR, 0x8000_0000 | (0b0010 << 27) | (1 << 26) | 6).unwrap();
+        cpu.write_val::<u8>(0x8000_0000, 0x11).unwrap();
+        assert_eq!(cpu.read_phys::<u8>(0x800).unwrap(), 0x11);
+        cpu.write_msr(58, 0x8000_0123).unwrap(); // TBIS
+        cpu.write_val::<u8>(0x8000_0000, 0x22).unwrap();
+        assert_eq!(cpu.read_phys::<u8>(0xC00).unwrap(), 0x22);
+
+        cpu.tb.reset_counters();
+        cpu.read_val::<u8>(0x8000_0200).unwrap();
+        cpu.tb.invalidate_all();
+        cpu.read_val::<u8>(0x8000_0200).unwrap();
+        assert_eq!((cpu.tb.hits(), cpu.tb.misses()), (0, 2));
+    }
+}